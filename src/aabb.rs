@@ -0,0 +1,145 @@
+//! Axis-aligned bounding boxes
+//!
+//! Bounding boxes are used by the acceleration structures to cheaply cull groups of objects that
+//! a ray cannot possibly hit.
+
+use crate::na::{self, RealField, Vector3};
+use crate::ray::Ray;
+
+/// An axis-aligned bounding box, defined by its minimum and maximum corners
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct AABB<N: RealField> {
+    /// The corner of the box with the smallest coordinates
+    pub min: Vector3<N>,
+
+    /// The corner of the box with the largest coordinates
+    pub max: Vector3<N>,
+}
+
+impl<N: RealField> AABB<N> {
+    /// Create a new bounding box from two corners. The corners do not need to be ordered.
+    pub fn new(a: &Vector3<N>, b: &Vector3<N>) -> Self {
+        Self {
+            min: na::inf(a, b),
+            max: na::sup(a, b),
+        }
+    }
+
+    /// Return the smallest box that contains both `self` and `other`
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: na::inf(&self.min, &other.min),
+            max: na::sup(&self.max, &other.max),
+        }
+    }
+
+    /// Return the smallest box that contains both `self` and the point `p`
+    pub fn union_point(&self, p: &Vector3<N>) -> Self {
+        Self {
+            min: na::inf(&self.min, p),
+            max: na::sup(&self.max, p),
+        }
+    }
+
+    /// The point in the middle of the box
+    pub fn centroid(&self) -> Vector3<N> {
+        (self.min + self.max) * N::from_f32(0.5).unwrap()
+    }
+
+    /// The vector spanning the box from its minimum to its maximum corner
+    pub fn extent(&self) -> Vector3<N> {
+        self.max - self.min
+    }
+
+    /// The index of the axis (0 for x, 1 for y, 2 for z) along which the box is longest
+    pub fn longest_axis(&self) -> usize {
+        self.extent().imax()
+    }
+
+    /// The total surface area of the box
+    pub fn surface_area(&self) -> N {
+        let d = self.extent();
+        N::from_f32(2.0).unwrap() * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Whether the ray hits the box anywhere in the parametric range `[t_min, t_max]`.
+    ///
+    /// This uses the slab method, which intersects the ray with the pair of planes bounding each
+    /// axis and checks whether the resulting intervals overlap.
+    pub fn hit(&self, ray: &Ray<N>, t_min: N, t_max: N) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+
+        for axis in 0..3 {
+            let inv_d = N::one() / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+
+            if inv_d < N::zero() {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // written this way so that NaNs (from 0 * inf) don't shrink the interval
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::typedefs::Vector3f;
+
+    #[test]
+    fn test_new_orders_corners() {
+        let b = AABB::new(
+            &Vector3f::new(1.0, -1.0, 2.0),
+            &Vector3f::new(-1.0, 1.0, 0.0),
+        );
+        assert_eq!(b.min, Vector3f::new(-1.0, -1.0, 0.0));
+        assert_eq!(b.max, Vector3f::new(1.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn test_union_and_area() {
+        let a = AABB::new(&Vector3f::new(0.0, 0.0, 0.0), &Vector3f::new(1.0, 1.0, 1.0));
+        let b = AABB::new(&Vector3f::new(1.0, 0.0, 0.0), &Vector3f::new(2.0, 1.0, 1.0));
+        let u = a.union(&b);
+        assert_eq!(u.min, Vector3f::new(0.0, 0.0, 0.0));
+        assert_eq!(u.max, Vector3f::new(2.0, 1.0, 1.0));
+        assert_eq!(u.surface_area(), 10.0);
+        assert_eq!(u.longest_axis(), 0);
+        assert_eq!(u.centroid(), Vector3f::new(1.0, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_hit() {
+        let b = AABB::new(
+            &Vector3f::new(-1.0, -1.0, -1.0),
+            &Vector3f::new(1.0, 1.0, 1.0),
+        );
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, -5.0),
+            &Vector3f::new(0.0, 0.0, 1.0),
+        );
+        assert!(b.hit(&ray, 0.0, 100.0));
+
+        // the box is behind the ray's valid range
+        assert!(!b.hit(&ray, 0.0, 3.0));
+
+        // axis-aligned ray that misses, which has infinite inverse direction components
+        let ray = Ray::new(
+            &Vector3f::new(2.0, 0.0, -5.0),
+            &Vector3f::new(0.0, 0.0, 1.0),
+        );
+        assert!(!b.hit(&ray, 0.0, 100.0));
+    }
+}
@@ -0,0 +1,493 @@
+use super::Accelerator;
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, ObjRef, ObjVec};
+use crate::na::{self, RealField, Vector3};
+use crate::ray::Ray;
+
+/// The number of buckets that object centroids are binned into when evaluating split candidates
+const BUCKETS: usize = 12;
+
+/// The number of objects at or below which a node may become a leaf
+const MAX_LEAF_SIZE: usize = 4;
+
+/// The cost of traversing an interior node, relative to the cost of intersecting one object
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// A node in the flattened tree. The first child of an interior node is always stored directly
+/// after it, so only the index of the second child needs to be recorded.
+#[derive(Clone, Debug, Copy)]
+enum Node<N: RealField> {
+    Interior {
+        bounds: AABB<N>,
+        second_child: usize,
+        axis: usize,
+    },
+    Leaf {
+        bounds: AABB<N>,
+        first: usize,
+        count: usize,
+    },
+}
+
+/// Build-time information about a single bounded object
+#[derive(Clone, Debug, Copy)]
+struct ObjInfo<N: RealField> {
+    /// The index of the object in the original `ObjVec`
+    index: usize,
+    bounds: AABB<N>,
+    centroid: Vector3<N>,
+}
+
+/// A bounding volume hierarchy
+///
+/// The hierarchy is built top-down using a binned surface area heuristic, and is stored as a flat
+/// array of nodes so that it can be traversed without recursion. The BVH takes ownership of the
+/// scene's objects and reorders them so that every leaf refers to a contiguous range. Objects
+/// without a bounding box are stored after the bounded objects and are always tested.
+pub struct BVH<N: RealField> {
+    objects: ObjVec<N>,
+    nodes: Vec<Node<N>>,
+
+    /// The number of objects that are part of the hierarchy. Objects at or after this index are
+    /// unbounded.
+    bounded: usize,
+}
+
+impl<N: RealField + Sync> BVH<N> {
+    /// Construct a BVH over a list of objects
+    pub fn new(objects: ObjVec<N>) -> Self {
+        let mut infos = Vec::with_capacity(objects.len());
+        let mut unbounded = Vec::new();
+
+        for (index, obj) in objects.iter().enumerate() {
+            match obj.hittable.bounding_box() {
+                Some(bounds) => infos.push(ObjInfo {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                }),
+                None => unbounded.push(index),
+            }
+        }
+        let mut nodes = Vec::new();
+
+        if !infos.is_empty() {
+            build(&mut infos, 0, &mut nodes);
+        }
+
+        // Move the objects into the order the leaves expect
+        let order = infos.iter().map(|info| info.index).chain(unbounded);
+        let mut slots: Vec<Option<ObjRef<N>>> = objects.into_iter().map(Some).collect();
+        let objects = order.map(|idx| slots[idx].take().unwrap()).collect();
+
+        Self {
+            objects,
+            nodes,
+            bounded: infos.len(),
+        }
+    }
+
+    /// The objects that are contained in the BVH. Note that these are not necessarily in the same
+    /// order as the list the BVH was constructed from.
+    pub fn objects(&self) -> &ObjVec<N> {
+        &self.objects
+    }
+}
+
+/// Convert a float to a bucket index for a centroid that lies `offset` of the way along the
+/// centroid bounds
+fn bucket_index<N: RealField>(offset: N) -> usize {
+    let offset: f64 = na::try_convert(offset).unwrap_or(0.0);
+    let idx = (offset * BUCKETS as f64) as usize;
+    idx.min(BUCKETS - 1)
+}
+
+/// Reorder a slice so that every element that satisfies `pred` comes before every element that
+/// doesn't, returning the number of elements that satisfy `pred`.
+fn partition<T, F: Fn(&T) -> bool>(items: &mut [T], pred: F) -> usize {
+    let mut mid = 0;
+
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// Recursively build the tree over `infos`, whose objects will start at `offset` in the final
+/// ordering. Nodes are appended to `nodes` in depth-first order. Returns the index of the node
+/// that was created.
+fn build<N: RealField>(infos: &mut [ObjInfo<N>], offset: usize, nodes: &mut Vec<Node<N>>) -> usize {
+    let bounds = infos[1..]
+        .iter()
+        .fold(infos[0].bounds, |acc, info| acc.union(&info.bounds));
+    let node_idx = nodes.len();
+    let leaf = Node::Leaf {
+        bounds,
+        first: offset,
+        count: infos.len(),
+    };
+
+    if infos.len() == 1 {
+        nodes.push(leaf);
+        return node_idx;
+    }
+    let centroid_bounds = infos[1..].iter().fold(
+        AABB::new(&infos[0].centroid, &infos[0].centroid),
+        |acc, info| acc.union_point(&info.centroid),
+    );
+    let axis = centroid_bounds.longest_axis();
+    let c_min = centroid_bounds.min[axis];
+    let c_extent = centroid_bounds.max[axis] - c_min;
+
+    // Every centroid is in the same spot, so there is no way to split these objects up
+    if c_extent <= N::zero() {
+        nodes.push(leaf);
+        return node_idx;
+    }
+    let bucket_of = |info: &ObjInfo<N>| bucket_index((info.centroid[axis] - c_min) / c_extent);
+
+    let mut counts = [0usize; BUCKETS];
+    let mut bucket_bounds: [Option<AABB<N>>; BUCKETS] = [None; BUCKETS];
+
+    for info in infos.iter() {
+        let b = bucket_of(info);
+        counts[b] += 1;
+        bucket_bounds[b] = Some(match bucket_bounds[b] {
+            Some(bb) => bb.union(&info.bounds),
+            None => info.bounds,
+        });
+    }
+
+    // Evaluate the SAH cost of splitting after each bucket, skipping splits that would leave one
+    // side empty
+    let total_area: f64 = na::try_convert(bounds.surface_area()).unwrap_or(0.0);
+    let mut best: Option<(usize, f64)> = None;
+
+    for split in 0..(BUCKETS - 1) {
+        let side = |range: std::ops::Range<usize>| {
+            range.fold((0, None), |(count, acc): (usize, Option<AABB<N>>), b| {
+                let acc = match (acc, bucket_bounds[b]) {
+                    (Some(a), Some(bb)) => Some(a.union(&bb)),
+                    (a, bb) => a.or(bb),
+                };
+                (count + counts[b], acc)
+            })
+        };
+        let (count_a, bounds_a) = side(0..(split + 1));
+        let (count_b, bounds_b) = side((split + 1)..BUCKETS);
+
+        if let (Some(a), Some(b)) = (bounds_a, bounds_b) {
+            let area_a: f64 = na::try_convert(a.surface_area()).unwrap_or(0.0);
+            let area_b: f64 = na::try_convert(b.surface_area()).unwrap_or(0.0);
+            let cost = if total_area > 0.0 {
+                TRAVERSAL_COST + (count_a as f64 * area_a + count_b as f64 * area_b) / total_area
+            } else {
+                TRAVERSAL_COST + infos.len() as f64 / 2.0
+            };
+
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((split, cost));
+            }
+        }
+    }
+
+    let (split, cost) = match best {
+        Some(best) => best,
+        None => {
+            nodes.push(leaf);
+            return node_idx;
+        }
+    };
+
+    // Splitting isn't worth it if intersecting everything directly is cheaper
+    if infos.len() <= MAX_LEAF_SIZE && cost >= infos.len() as f64 {
+        nodes.push(leaf);
+        return node_idx;
+    }
+    let mid = partition(infos, |info| bucket_of(info) <= split);
+
+    // placeholder that gets filled in once we know where the second child ends up
+    nodes.push(leaf);
+    let (left, right) = infos.split_at_mut(mid);
+    build(left, offset, nodes);
+    let second_child = build(right, offset + mid, nodes);
+    nodes[node_idx] = Node::Interior {
+        bounds,
+        second_child,
+        axis,
+    };
+    node_idx
+}
+
+impl<N: RealField + Sync> BVH<N> {
+    /// Call `visit` for every object whose node is hit by the ray between `t_min` and `t_max`.
+    /// `visit` may lower `t_max`, which culls the nodes past it, and traversal stops as soon as
+    /// `visit` returns true.
+    fn traverse<'a, F>(&'a self, ray: &Ray<N>, t_min: N, mut t_max: N, mut visit: F)
+    where
+        F: FnMut(&'a ObjRef<N>, &mut N) -> bool,
+    {
+        let dir_is_neg = [
+            ray.direction.x < N::zero(),
+            ray.direction.y < N::zero(),
+            ray.direction.z < N::zero(),
+        ];
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(idx) = stack.pop() {
+            match self.nodes[idx] {
+                Node::Leaf {
+                    bounds,
+                    first,
+                    count,
+                } => {
+                    if bounds.hit(ray, t_min, t_max) {
+                        for obj in &self.objects[first..(first + count)] {
+                            if visit(obj, &mut t_max) {
+                                return;
+                            }
+                        }
+                    }
+                }
+                Node::Interior {
+                    bounds,
+                    second_child,
+                    axis,
+                } => {
+                    if bounds.hit(ray, t_min, t_max) {
+                        // visit the child that is closer to the ray origin first, so the far
+                        // child can be culled by the closest hit distance
+                        if dir_is_neg[axis] {
+                            stack.push(idx + 1);
+                            stack.push(second_child);
+                        } else {
+                            stack.push(second_child);
+                            stack.push(idx + 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        for obj in &self.objects[self.bounded..] {
+            if visit(obj, &mut t_max) {
+                return;
+            }
+        }
+    }
+}
+
+impl<N: RealField + Sync> Accelerator<N> for BVH<N> {
+    fn any_hit(
+        &self,
+        ray: &Ray<N>,
+        t_min: Option<N>,
+        t_max: Option<N>,
+    ) -> Option<(HitRecord<N>, &ObjRef<N>)> {
+        let t_min = t_min.unwrap_or_else(|| -N::max_value());
+        let t_max = t_max.unwrap_or_else(N::max_value);
+        let mut closest: Option<(HitRecord<N>, &ObjRef<N>)> = None;
+
+        self.traverse(ray, t_min, t_max, |obj, closest_t| {
+            if let Some(hit_record) = obj.hittable.hit(ray, t_min, *closest_t) {
+                *closest_t = hit_record.t;
+                closest = Some((hit_record, obj));
+            }
+            false
+        });
+        closest
+    }
+
+    fn occluded(&self, ray: &Ray<N>, t_min: Option<N>, t_max: Option<N>) -> bool {
+        let t_min = t_min.unwrap_or_else(|| -N::max_value());
+        let t_max = t_max.unwrap_or_else(N::max_value);
+        let mut occluded = false;
+
+        self.traverse(ray, t_min, t_max, |obj, t_max| {
+            occluded = obj.hittable.hit(ray, t_min, *t_max).is_some();
+            occluded
+        });
+        occluded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittable::{any_hit, Hittable};
+    use crate::material::diffuse::Diffuse;
+    use crate::primitives::sphere::Sphere;
+    use crate::typedefs::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// An infinite plane at y = `height`, which has no bounding box
+    struct Floor {
+        height: f,
+    }
+
+    impl Hittable for Floor {
+        type NumType = f;
+
+        fn hit(&self, ray: &Ray3f, t_min: f, t_max: f) -> Option<HitRecord<f>> {
+            if ray.direction.y == 0.0 {
+                return None;
+            }
+            let t = (self.height - ray.origin.y) / ray.direction.y;
+
+            if t < t_min || t > t_max {
+                return None;
+            }
+            let hr = HitRecord {
+                t,
+                p: ray.point_at_param(t),
+                normal: Vector3f::new(0.0, 1.0, 0.0),
+                shading_normal: Vector3f::new(0.0, 1.0, 0.0),
+                uv: Vector2f::new(0.0, 0.0),
+                front_face: true,
+            };
+            Some(hr.face_forward(&ray.direction))
+        }
+
+        fn bounding_box(&self) -> Option<AABB<f>> {
+            None
+        }
+    }
+
+    /// Generate a deterministic, randomized list of spheres
+    fn random_spheres(n: usize, with_floor: bool) -> ObjVec<f> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut objects: ObjVec<f> = (0..n)
+            .map(|_| ObjRef {
+                hittable: Box::new(Sphere {
+                    center: Vector3f::new(
+                        rng.gen_range(-20.0, 20.0),
+                        rng.gen_range(-20.0, 20.0),
+                        rng.gen_range(-20.0, 20.0),
+                    ),
+                    radius: rng.gen_range(0.1, 2.0),
+                }),
+                material: Box::new(Diffuse {
+                    albedo: Vector3f::new(0.5, 0.5, 0.5),
+                }),
+            })
+            .collect();
+
+        if with_floor {
+            objects.push(ObjRef {
+                hittable: Box::new(Floor { height: -25.0 }),
+                material: Box::new(Diffuse {
+                    albedo: Vector3f::new(0.5, 0.5, 0.5),
+                }),
+            });
+        }
+        objects
+    }
+
+    /// Fire a bunch of random rays at both the linear list and the BVH and make sure that they
+    /// agree on the closest hit
+    fn check_matches_linear_scan(n: usize, with_floor: bool) {
+        let list = random_spheres(n, with_floor);
+        let bvh = BVH::new(random_spheres(n, with_floor));
+        assert_eq!(bvh.objects().len(), list.len());
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut hits = 0;
+
+        for _ in 0..2000 {
+            let origin = Vector3f::new(
+                rng.gen_range(-30.0, 30.0),
+                rng.gen_range(-30.0, 30.0),
+                rng.gen_range(-30.0, 30.0),
+            );
+            let direction = Vector3f::new(
+                rng.gen_range(-1.0, 1.0),
+                rng.gen_range(-1.0, 1.0),
+                rng.gen_range(-1.0, 1.0),
+            );
+            let ray = Ray::new(&origin, &direction);
+            let expected = any_hit(&list, &ray, Some(0.001), None).map(|(hr, _)| hr);
+            let actual = bvh.any_hit(&ray, Some(0.001), None).map(|(hr, _)| hr);
+            assert_eq!(expected, actual);
+
+            // shadow rays only care whether there's a hit within the range
+            let t_max = rng.gen_range(1.0, 40.0);
+            let blocked = any_hit(&list, &ray, Some(0.001), Some(t_max)).is_some();
+            assert_eq!(bvh.occluded(&ray, Some(0.001), Some(t_max)), blocked);
+            assert_eq!(bvh.occluded(&ray, Some(0.001), None), expected.is_some());
+
+            if expected.is_some() {
+                hits += 1;
+            }
+        }
+        // make sure the test is actually exercising something
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        check_matches_linear_scan(500, false);
+    }
+
+    #[test]
+    fn test_matches_linear_scan_unbounded() {
+        check_matches_linear_scan(100, true);
+    }
+
+    #[test]
+    fn test_single_object() {
+        check_matches_linear_scan(1, false);
+    }
+
+    #[test]
+    fn test_empty() {
+        let bvh: BVH<f> = BVH::new(Vec::new());
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 0.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        assert!(bvh.any_hit(&ray, None, None).is_none());
+    }
+
+    #[test]
+    fn test_t_max_culls_hits() {
+        // a row of unit spheres along the z axis, with more spheres off to the side so that the
+        // BVH has more than one level
+        let objects: ObjVec<f> = (0..8)
+            .map(|i| ObjRef {
+                hittable: Box::new(Sphere {
+                    center: Vector3f::new(if i % 2 == 0 { 0.0 } else { 10.0 }, 0.0, i as f * 5.0),
+                    radius: 1.0,
+                }),
+                material: Box::new(Diffuse {
+                    albedo: Vector3f::new(0.5, 0.5, 0.5),
+                }),
+            })
+            .collect();
+        let bvh = BVH::new(objects);
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, -40.0),
+            &Vector3f::new(0.0, 0.0, 1.0),
+        );
+
+        // the closest sphere on the ray is the one at the origin
+        let (hr, _) = bvh
+            .any_hit(&ray, None, None)
+            .expect("the ray should hit the sphere at the origin");
+        assert!((hr.t - 39.0).abs() < 1e-4, "{}", hr.t);
+
+        // nothing is in front of it, so a shorter ray misses everything
+        assert!(bvh.any_hit(&ray, None, Some(38.0)).is_none());
+
+        // a t_max just past the sphere still finds it
+        let (hr, _) = bvh.any_hit(&ray, None, Some(39.5)).unwrap();
+        assert!((hr.t - 39.0).abs() < 1e-4, "{}", hr.t);
+    }
+}
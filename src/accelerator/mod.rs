@@ -0,0 +1,46 @@
+//! The accelerator module defines a trait for structures that can find the closest object hit by a
+//! ray, along with implementations of those structures.
+//!
+//! A plain `ObjVec` is the simplest accelerator: it tests every object in the scene. Anything
+//! beyond a few dozen objects should use a `BVH` instead.
+
+use crate::hittable::{any_hit, HitRecord, ObjRef, ObjVec};
+use crate::na::RealField;
+use crate::ray::Ray;
+
+pub mod bvh;
+
+pub use bvh::BVH;
+
+/// The `Accelerator` trait should be implemented for any structure that can be queried for ray
+/// intersections against a collection of objects.
+pub trait Accelerator<N: RealField + Sync> {
+    /// Return a tuple with the closest `HitRecord` and the object that was hit, if any object in
+    /// the structure is hit by the ray within the bounds. If nothing is hit, `None` will be
+    /// returned.
+    fn any_hit(
+        &self,
+        ray: &Ray<N>,
+        t_min: Option<N>,
+        t_max: Option<N>,
+    ) -> Option<(HitRecord<N>, &ObjRef<N>)>;
+
+    /// Whether any object in the structure is hit by the ray within the bounds. This is used for
+    /// shadow rays, which only need to know whether something is in the way, so structures
+    /// should stop at the first hit they find rather than looking for the closest one.
+    fn occluded(&self, ray: &Ray<N>, t_min: Option<N>, t_max: Option<N>) -> bool {
+        self.any_hit(ray, t_min, t_max).is_some()
+    }
+}
+
+impl<N: RealField + Sync> Accelerator<N> for ObjVec<N> {
+    /// A linear scan over every object in the list
+    fn any_hit(
+        &self,
+        ray: &Ray<N>,
+        t_min: Option<N>,
+        t_max: Option<N>,
+    ) -> Option<(HitRecord<N>, &ObjRef<N>)> {
+        any_hit(self, ray, t_min, t_max)
+    }
+}
@@ -0,0 +1,78 @@
+//! The background is the radiance that a ray receives when it escapes the scene without hitting
+//! anything.
+
+use crate::na::{RealField, Vector3};
+use std::default::Default;
+
+/// The radiance that is seen in every direction that isn't blocked by an object
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum Background<N: RealField> {
+    /// A vertical blend between two colors, based on the angle of the ray with the horizon
+    Gradient {
+        /// The color seen when looking straight down
+        bottom: Vector3<N>,
+
+        /// The color seen when looking straight up
+        top: Vector3<N>,
+    },
+
+    /// A single color in every direction
+    Constant(Vector3<N>),
+}
+
+impl<N: RealField> Background<N> {
+    /// The radiance arriving from the background along `direction`
+    pub fn radiance(&self, direction: &Vector3<N>) -> Vector3<N> {
+        match self {
+            Background::Gradient { bottom, top } => {
+                let unit_dir = direction.normalize();
+                let t = (unit_dir.y + N::one()) / (N::one() + N::one());
+
+                // linearly interpolate a color based on the angle of the ray
+                bottom * (N::one() - t) + top * t
+            }
+            Background::Constant(color) => *color,
+        }
+    }
+}
+
+impl<N: RealField> Default for Background<N> {
+    /// The blend of blue and white sky from "Ray Tracing in One Weekend"
+    fn default() -> Self {
+        let color = |r, g, b| Vector3::new(r, g, b).map(|c: f32| N::from_f32(c).unwrap());
+        Background::Gradient {
+            bottom: color(1.0, 1.0, 1.0),
+            top: color(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::typedefs::*;
+
+    #[test]
+    fn test_gradient() {
+        let bg = Background::default();
+        assert_eq!(
+            bg.radiance(&Vector3f::new(0.0, -2.0, 0.0)),
+            Color3f::new(1.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            bg.radiance(&Vector3f::new(0.0, 1.0, 0.0)),
+            Color3f::new(0.5, 0.7, 1.0)
+        );
+        assert_eq!(
+            bg.radiance(&Vector3f::new(1.0, 0.0, 0.0)),
+            Color3f::new(0.75, 0.85, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_constant() {
+        let color = Color3f::new(0.1, 0.2, 0.3);
+        let bg = Background::Constant(color);
+        assert_eq!(bg.radiance(&Vector3f::new(0.0, 1.0, 0.0)), color);
+    }
+}
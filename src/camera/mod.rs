@@ -4,13 +4,20 @@
 
 use crate::na::RealField;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 pub mod pinhole;
+pub mod thin_lens;
 
 pub use pinhole::Pinhole;
+pub use thin_lens::{Aperture, ThinLens};
 
 /// The `Camera` trait should be implemented for any specific camera implementation.
 pub trait Camera<N: RealField + Sync> {
-    /// Given uv coordinates, return an outgoing ray originating from the viewer's eye
-    fn get_ray(&self, u: N, v: N) -> Ray<N>;
+    /// Given uv coordinates, return an outgoing ray originating from the viewer's eye.
+    /// `sampler` provides the values for cameras that choose where rays start randomly.
+    fn get_ray(&self, u: N, v: N, sampler: &mut dyn Sampler) -> Ray<N>;
 }
+
+/// An owned reference to a camera trait object that is also `Sync`
+pub type CameraRef<N> = Box<dyn Camera<N> + Sync>;
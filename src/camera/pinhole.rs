@@ -1,6 +1,7 @@
 use super::Camera;
 use crate::na::{RealField, Vector3};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::typedefs::Vector3f;
 use std::default::Default;
 
@@ -19,9 +20,63 @@ pub struct Pinhole<N: RealField> {
     pub lower_left: Vector3<N>,
 }
 
+impl<N: RealField> Pinhole<N> {
+    /// Check that a camera at `eye` can be pointed towards `target` with `up` as the upwards
+    /// direction. The eye and the target must be different points, and `up` can't be parallel to
+    /// the direction between them, since the camera couldn't be oriented otherwise. Returns the
+    /// reason why the camera is invalid, if it is.
+    pub fn check_look_at(
+        eye: &Vector3<N>,
+        target: &Vector3<N>,
+        up: &Vector3<N>,
+    ) -> Result<(), &'static str> {
+        let w = eye - target;
+
+        if w.norm_squared() == N::zero() {
+            return Err("the camera's eye and target must be different points");
+        }
+        if up.cross(&w).norm_squared() == N::zero() {
+            return Err("the camera's up direction can't be parallel to the direction it looks in");
+        }
+        Ok(())
+    }
+
+    /// Create a camera at `eye` that is pointed towards `target`.
+    ///
+    /// `up` is the direction that should appear upwards in the image. It only needs to point
+    /// roughly upwards, as long as it isn't parallel to the viewing direction (see
+    /// `check_look_at`). `vfov` is the angle (in degrees) that the image spans from top to
+    /// bottom, and `aspect` is the ratio of the image's width to its height.
+    pub fn look_at(
+        eye: &Vector3<N>,
+        target: &Vector3<N>,
+        up: &Vector3<N>,
+        vfov: N,
+        aspect: N,
+    ) -> Self {
+        debug_assert!(Self::check_look_at(eye, target, up).is_ok());
+        let two = N::one() + N::one();
+        let theta = vfov * N::pi() / N::from_u32(180).unwrap();
+        let half_height = (theta / two).tan();
+        let half_width = aspect * half_height;
+
+        // an orthonormal basis for the camera, which looks down the negative w axis
+        let w = (eye - target).normalize();
+        let u = up.cross(&w).normalize();
+        let v = w.cross(&u);
+
+        Self {
+            origin: *eye,
+            horizontal: u * (two * half_width),
+            vertical: v * (two * half_height),
+            lower_left: eye - u * half_width - v * half_height - w,
+        }
+    }
+}
+
 impl<N: RealField> Camera<N> for Pinhole<N> {
     /// Return an outgoing directional ray for a camera based on supplied uv coordinates
-    fn get_ray(&self, u: N, v: N) -> Ray<N> {
+    fn get_ray(&self, u: N, v: N, _sampler: &mut dyn Sampler) -> Ray<N> {
         let direction =
             self.lower_left + self.horizontal.map(|e| e * u) + self.vertical.map(|e| e * v)
                 - self.origin;
@@ -47,44 +102,95 @@ impl Default for Pinhole<f32> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::sampler::Independent;
 
     #[test]
     fn test_get_ray() {
         let camera: Pinhole<f32> = Default::default();
+        let mut sampler = Independent::new(0);
 
         // this is equivalent to the lower left corner of the frame
         let ray: Ray<f32> = Ray {
             origin: Vector3f::new(0.0, 0.0, 0.0),
             direction: Vector3f::new(-2.0, -1.0, -1.0),
         };
-        assert_eq!(camera.get_ray(0.0, 0.0), ray);
+        assert_eq!(camera.get_ray(0.0, 0.0, &mut sampler), ray);
 
         // middle
         let ray: Ray<f32> = Ray {
             origin: Vector3f::new(0.0, 0.0, 0.0),
             direction: Vector3f::new(0.0, 0.0, -1.0),
         };
-        assert_eq!(camera.get_ray(0.5, 0.5), ray);
+        assert_eq!(camera.get_ray(0.5, 0.5, &mut sampler), ray);
 
         // upper left corner
         let ray: Ray<f32> = Ray {
             origin: Vector3f::new(0.0, 0.0, 0.0),
             direction: Vector3f::new(-2.0, 1.0, -1.0),
         };
-        assert_eq!(camera.get_ray(0.0, 1.0), ray);
+        assert_eq!(camera.get_ray(0.0, 1.0, &mut sampler), ray);
 
         // upper right corner
         let ray: Ray<f32> = Ray {
             origin: Vector3f::new(0.0, 0.0, 0.0),
             direction: Vector3f::new(2.0, 1.0, -1.0),
         };
-        assert_eq!(camera.get_ray(1.0, 1.0), ray);
+        assert_eq!(camera.get_ray(1.0, 1.0, &mut sampler), ray);
 
         // lower right corner
         let ray: Ray<f32> = Ray {
             origin: Vector3f::new(0.0, 0.0, 0.0),
             direction: Vector3f::new(2.0, -1.0, -1.0),
         };
-        assert_eq!(camera.get_ray(1.0, 0.0), ray);
+        assert_eq!(camera.get_ray(1.0, 0.0, &mut sampler), ray);
+    }
+
+    #[test]
+    fn test_look_at() {
+        // the default camera is equivalent to looking down the negative z axis with a 2:1 aspect
+        let camera = Pinhole::look_at(
+            &Vector3f::new(0.0, 0.0, 0.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+            &Vector3f::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+        );
+        let default = Pinhole::default();
+        let mut sampler = Independent::new(0);
+        assert_eq!(camera.origin, default.origin);
+        assert!((camera.horizontal - default.horizontal).norm() < 1e-6);
+        assert!((camera.vertical - default.vertical).norm() < 1e-6);
+        assert!((camera.lower_left - default.lower_left).norm() < 1e-6);
+
+        // the center of the image is in the direction of the target, no matter where it is
+        let eye = Vector3f::new(1.0, 2.0, 3.0);
+        let target = Vector3f::new(-2.0, 0.0, 1.0);
+        let camera = Pinhole::look_at(&eye, &target, &Vector3f::new(0.0, 1.0, 0.0), 40.0, 1.5);
+        let ray = camera.get_ray(0.5, 0.5, &mut sampler);
+        assert_eq!(ray.origin, eye);
+        assert!((ray.direction.normalize() - (target - eye).normalize()).norm() < 1e-6);
+
+        // the field of view and aspect ratio determine the span of the image
+        let top = camera.get_ray(0.5, 1.0, &mut sampler).direction.normalize();
+        let bottom = camera.get_ray(0.5, 0.0, &mut sampler).direction.normalize();
+        assert!((top.angle(&bottom).to_degrees() - 40.0).abs() < 1e-4);
+        assert!((camera.horizontal.norm() / camera.vertical.norm() - 1.5).abs() < 1e-6);
+
+        // the horizontal axis is level, since the up vector is vertical
+        assert!(camera.horizontal.y.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_check_look_at() {
+        let eye = Vector3f::new(1.0, 2.0, 3.0);
+        let up = Vector3f::new(0.0, 1.0, 0.0);
+        assert!(Pinhole::check_look_at(&eye, &Vector3f::new(0.0, 0.0, 0.0), &up).is_ok());
+        assert!(Pinhole::check_look_at(&eye, &eye, &up).is_err());
+
+        // looking straight down, with up along the same line
+        let below = Vector3f::new(1.0, -5.0, 3.0);
+        assert!(Pinhole::check_look_at(&eye, &below, &up).is_err());
+        assert!(Pinhole::check_look_at(&eye, &below, &-up).is_err());
+        assert!(Pinhole::check_look_at(&eye, &below, &Vector3f::zeros()).is_err());
     }
 }
@@ -0,0 +1,151 @@
+use super::{Camera, Pinhole};
+use crate::na::{RealField, Vector2, Vector3};
+use crate::ray::Ray;
+use crate::sample::{concentric_disk, unit_polygon};
+use crate::sampler::Sampler;
+use num::FromPrimitive;
+
+/// The shape of the opening that light passes through in a thin lens camera. This determines
+/// the shape of out of focus highlights (bokeh).
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum Aperture {
+    /// A round opening
+    Circular,
+
+    /// A regular polygon with the given number of sides, like the opening formed by the blades
+    /// of a real diaphragm
+    Polygonal(usize),
+}
+
+impl Aperture {
+    /// Map a point in the unit square to a point on the aperture, scaled so that it fits within
+    /// the unit circle
+    fn sample<N: RealField + FromPrimitive>(&self, u: &Vector2<N>) -> Vector2<N> {
+        match *self {
+            Aperture::Circular => concentric_disk(u),
+            Aperture::Polygonal(sides) => unit_polygon(sides, u),
+        }
+    }
+}
+
+/// A camera with a thin lens, which produces depth of field.
+///
+/// Rays originate from random points on the lens and converge on the plane of focus, so objects
+/// on that plane are sharp, and everything else is blurred in proportion to its distance from
+/// it.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct ThinLens<N: RealField> {
+    /// The center of the lens
+    pub origin: Vector3<N>,
+
+    /// The horizontal span of the image, on the plane of focus
+    pub horizontal: Vector3<N>,
+
+    /// The vertical span of the image, on the plane of focus
+    pub vertical: Vector3<N>,
+
+    /// The lower left corner of the image, on the plane of focus
+    pub lower_left: Vector3<N>,
+
+    /// The radius of the aperture. A radius of zero is equivalent to a pinhole camera.
+    pub aperture_radius: N,
+
+    /// The shape of the aperture
+    pub aperture: Aperture,
+}
+
+impl<N: RealField> ThinLens<N> {
+    /// Create a thin lens camera with the same view as a pinhole camera.
+    ///
+    /// `focus_distance` is the distance from the camera to the plane that is in focus, measured
+    /// along the direction that the camera is looking in.
+    pub fn new(
+        pinhole: &Pinhole<N>,
+        aperture_radius: N,
+        focus_distance: N,
+        aperture: Aperture,
+    ) -> Self {
+        let two = N::one() + N::one();
+        let center = pinhole.lower_left + (pinhole.horizontal + pinhole.vertical) / two;
+
+        // move the pinhole's image plane onto the plane of focus
+        let scale = focus_distance / (center - pinhole.origin).norm();
+
+        Self {
+            origin: pinhole.origin,
+            horizontal: pinhole.horizontal * scale,
+            vertical: pinhole.vertical * scale,
+            lower_left: pinhole.origin + (pinhole.lower_left - pinhole.origin) * scale,
+            aperture_radius,
+            aperture,
+        }
+    }
+}
+
+impl<N: RealField + FromPrimitive> Camera<N> for ThinLens<N> {
+    fn get_ray(&self, u: N, v: N, sampler: &mut dyn Sampler) -> Ray<N> {
+        let point = sampler.get_2d().map(|x| N::from_f32(x).unwrap());
+        let lens = self.aperture.sample(&point) * self.aperture_radius;
+        let offset = self.horizontal.normalize() * lens.x + self.vertical.normalize() * lens.y;
+        let origin = self.origin + offset;
+        let target = self.lower_left + self.horizontal * u + self.vertical * v;
+
+        Ray {
+            origin,
+            direction: target - origin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::Independent;
+    use crate::typedefs::*;
+
+    fn camera(aperture: Aperture) -> ThinLens<f> {
+        let pinhole = Pinhole::look_at(
+            &Vector3f::new(0.0, 0.0, 0.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+            &Vector3f::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+        );
+        ThinLens::new(&pinhole, 0.5, 4.0, aperture)
+    }
+
+    #[test]
+    fn test_focus() {
+        for &aperture in &[Aperture::Circular, Aperture::Polygonal(6)] {
+            let camera = camera(aperture);
+            let mut sampler = Independent::new(0);
+
+            for i in 0..100 {
+                // every ray for a pixel passes through the same point on the plane of focus
+                sampler.start_sample((0, 0), i);
+                let ray = camera.get_ray(0.25, 0.75, &mut sampler);
+                let t = (-4.0 - ray.origin.z) / ray.direction.z;
+                let p = ray.point_at_param(t);
+                assert!((p - Vector3f::new(-4.0, 2.0, -4.0)).norm() < 1e-4);
+
+                // and starts on the lens
+                assert_eq!(ray.origin.z, 0.0);
+                assert!(ray.origin.norm() <= 0.5 + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pinhole_equivalence() {
+        // with no aperture, the camera sees the same thing as the pinhole camera it came from
+        let pinhole = Pinhole::default();
+        let camera = ThinLens::new(&pinhole, 0.0, 2.0, Aperture::Circular);
+        let mut sampler = Independent::new(0);
+
+        for &(u, v) in &[(0.0, 0.0), (0.5, 0.5), (1.0, 0.25)] {
+            let expected = pinhole.get_ray(u, v, &mut sampler).direction.normalize();
+            let actual = camera.get_ray(u, v, &mut sampler).direction.normalize();
+            assert!((expected - actual).norm() < 1e-6);
+        }
+    }
+}
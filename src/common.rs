@@ -3,8 +3,6 @@
 
 use crate::na::{Matrix, RealField, Vector3};
 use num::FromPrimitive;
-use rand::prelude::*;
-use rand::thread_rng;
 
 /// Mirror a vector about a unit direction. `vector` is the incoming vector, and `normal` is the
 /// vector to mirror `vector` around. Returns a mirrored vector. Note that `normal` must be a
@@ -16,20 +14,119 @@ pub fn mirror<N: RealField + FromPrimitive>(
     vector - normal.map(|e| e * (N::from_u32(2).unwrap() * Matrix::dot(vector, normal)))
 }
 
-/// Generate a random point within the unit sphere
-pub fn random_in_unit_sphere<N>() -> Vector3<N>
-where
-    rand::distributions::Standard: rand::distributions::Distribution<N>,
-    N: RealField + FromPrimitive + num::Float,
-{
-    let mut rng = thread_rng();
-
-    let x: N = rng.gen();
-    let y: N = rng.gen();
-    let z: N = rng.gen();
-
-    // Get the magnitude of the sphere
-    let mag = num::Float::sqrt(x * x + y * y + z * z);
-    let norm_factor = num::Float::powf(mag, N::from(1.0 / 3.0).unwrap());
-    Vector3::<N>::new(x * norm_factor, y * norm_factor, z * norm_factor)
+/// Refract a unit vector through a surface with a unit `normal` that points against `vector`.
+/// `eta` is the ratio of the index of refraction on the incident side to the index of refraction
+/// on the transmitted side.
+///
+/// Returns `None` if the ray undergoes total internal reflection.
+pub fn refract<N: RealField>(
+    vector: &Vector3<N>,
+    normal: &Vector3<N>,
+    eta: N,
+) -> Option<Vector3<N>> {
+    let cos_i = -Matrix::dot(vector, normal);
+    let sin2_t = eta * eta * (N::one() - cos_i * cos_i);
+
+    if sin2_t > N::one() {
+        return None;
+    }
+    let cos_t = (N::one() - sin2_t).sqrt();
+    Some(vector * eta + normal * (eta * cos_i - cos_t))
+}
+
+/// The fraction of light that is reflected at the boundary between two dielectrics, using the
+/// exact Fresnel equations for unpolarized light.
+///
+/// `cos_i` is the cosine of the angle between the incident direction and the surface normal,
+/// `eta_i` is the index of refraction on the incident side, and `eta_t` is the index of
+/// refraction on the transmitted side. Total internal reflection results in a reflectance of 1.
+pub fn fresnel_dielectric<N: RealField>(cos_i: N, eta_i: N, eta_t: N) -> N {
+    let cos_i = num::clamp(cos_i, N::zero(), N::one());
+    let sin_t = eta_i / eta_t * (N::one() - cos_i * cos_i).sqrt();
+
+    if sin_t >= N::one() {
+        return N::one();
+    }
+    let cos_t = (N::one() - sin_t * sin_t).sqrt();
+    let r_parallel = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    let r_perpendicular = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / N::from_u32(2).unwrap()
+}
+
+/// Two unit vectors that are perpendicular to each other and to the unit vector `normal`, which
+/// together form an orthonormal basis. This is the branchless construction from Duff et al.,
+/// "Building an Orthonormal Basis, Revisited".
+pub fn coordinate_system<N: RealField>(normal: &Vector3<N>) -> (Vector3<N>, Vector3<N>) {
+    let sign = if normal.z >= N::zero() {
+        N::one()
+    } else {
+        -N::one()
+    };
+    let a = -N::one() / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+    (
+        Vector3::new(
+            N::one() + sign * normal.x * normal.x * a,
+            sign * b,
+            -sign * normal.x,
+        ),
+        Vector3::new(b, sign + normal.y * normal.y * a, -normal.y),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::typedefs::*;
+
+    #[test]
+    fn test_refract() {
+        // straight through at normal incidence
+        let v = Vector3f::new(0.0, -1.0, 0.0);
+        let n = Vector3f::new(0.0, 1.0, 0.0);
+        assert_eq!(refract(&v, &n, 1.0 / 1.5), Some(v));
+
+        // Snell's law: eta_i * sin_i = eta_t * sin_t
+        let v = Vector3f::new(1.0, -1.0, 0.0).normalize();
+        let t = refract(&v, &n, 1.0 / 1.5).unwrap();
+        assert!((t.norm() - 1.0).abs() < 1e-6);
+        assert!((v.x - 1.5 * t.x).abs() < 1e-6);
+        assert!(t.y < 0.0);
+
+        // total internal reflection when leaving glass at a grazing angle
+        let v = Vector3f::new(1.0, -0.2, 0.0).normalize();
+        assert_eq!(refract(&v, &n, 1.5), None);
+    }
+
+    #[test]
+    fn test_fresnel_dielectric() {
+        // ((1 - 1.5) / (1 + 1.5))^2 at normal incidence
+        assert!((fresnel_dielectric::<f>(1.0, 1.0, 1.5) - 0.04).abs() < 1e-6);
+
+        // reflectance increases towards grazing angles, and is the same from either side at
+        // normal incidence
+        assert!(fresnel_dielectric::<f>(0.1, 1.0, 1.5) > fresnel_dielectric(0.9, 1.0, 1.5));
+        assert!((fresnel_dielectric::<f>(1.0, 1.5, 1.0) - 0.04).abs() < 1e-6);
+
+        // total internal reflection
+        assert_eq!(fresnel_dielectric::<f>(0.2, 1.5, 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_coordinate_system() {
+        for n in &[
+            Vector3f::new(0.0, 0.0, 1.0),
+            Vector3f::new(0.0, 0.0, -1.0),
+            Vector3f::new(1.0, 2.0, -3.0).normalize(),
+            Vector3f::new(-0.5, 0.1, 0.2).normalize(),
+        ] {
+            let (s, t) = coordinate_system(n);
+            assert!((s.norm() - 1.0).abs() < 1e-6 && (t.norm() - 1.0).abs() < 1e-6);
+            assert!(s.dot(&t).abs() < 1e-6);
+            assert!(s.dot(n).abs() < 1e-6 && t.dot(n).abs() < 1e-6);
+
+            // the basis is right handed
+            assert!((s.cross(&t) - n).norm() < 1e-6);
+        }
+    }
 }
@@ -0,0 +1,194 @@
+//! Piecewise constant probability distributions, which are used to sample functions that are
+//! given as a table of values, such as the brightness of the pixels of an environment map.
+
+use crate::na::{self, RealField, Vector2};
+
+/// The largest number that is less than one
+fn one_minus_epsilon<N: RealField>() -> N {
+    N::one() - N::default_epsilon() / (N::one() + N::one())
+}
+
+/// A distribution over [0, 1) whose density is proportional to a step function, with a step for
+/// each value of the function
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution1D<N: RealField> {
+    /// The value of the function in each step, which must not be negative
+    func: Vec<N>,
+
+    /// The cumulative distribution at the start of each step, and at the end of the last one
+    cdf: Vec<N>,
+
+    /// The integral of the function over [0, 1)
+    integral: N,
+}
+
+impl<N: RealField> Distribution1D<N> {
+    /// Create a distribution for a function with the given values. If the function is zero
+    /// everywhere, the distribution is uniform.
+    pub fn new(func: Vec<N>) -> Self {
+        assert!(!func.is_empty(), "a distribution needs at least one value");
+        let n = N::from_usize(func.len()).unwrap();
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(N::zero());
+
+        for value in &func {
+            let last = cdf[cdf.len() - 1];
+            cdf.push(last + *value / n);
+        }
+        let integral = cdf[func.len()];
+
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > N::zero() {
+                *c / integral
+            } else {
+                N::from_usize(i).unwrap() / n
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// The number of steps of the function
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// The integral of the function over [0, 1)
+    pub fn integral(&self) -> N {
+        self.integral
+    }
+
+    /// Map a uniformly distributed value `u` in [0, 1) to a value from the distribution. Returns
+    /// the value, its density, and the index of the step that it's in.
+    pub fn sample(&self, u: N) -> (N, N, usize) {
+        // the last step whose cumulative distribution starts at or before `u`
+        let index = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.count() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > N::zero() {
+            (u - self.cdf[index]) / width
+        } else {
+            N::zero()
+        };
+        let x = (N::from_usize(index).unwrap() + offset) / N::from_usize(self.count()).unwrap();
+        (x.min(one_minus_epsilon()), self.step_pdf(index), index)
+    }
+
+    /// The density of the distribution at `x`
+    pub fn pdf(&self, x: N) -> N {
+        self.step_pdf(step(x, self.count()))
+    }
+
+    /// The density of the distribution in the step at `index`
+    fn step_pdf(&self, index: usize) -> N {
+        if self.integral > N::zero() {
+            self.func[index] / self.integral
+        } else {
+            N::one()
+        }
+    }
+}
+
+/// The index of the step that `x` in [0, 1) is in, out of `count` equal steps
+fn step<N: RealField>(x: N, count: usize) -> usize {
+    let x: f64 = na::try_convert(x).unwrap_or(0.0);
+    ((x * count as f64) as usize).min(count - 1)
+}
+
+/// A distribution over the unit square whose density is proportional to a function that is
+/// constant within each cell of a grid
+///
+/// Points are sampled by first picking a row from the marginal distribution of the rows, and
+/// then picking a point in the row from its conditional distribution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution2D<N: RealField> {
+    /// The distribution within each row
+    conditionals: Vec<Distribution1D<N>>,
+
+    /// The distribution of the rows, which is proportional to the integral of each row
+    marginal: Distribution1D<N>,
+}
+
+impl<N: RealField> Distribution2D<N> {
+    /// Create a distribution for a function on a `width` by `height` grid, whose values are given
+    /// in row-major order. Row 0 is at y = 0.
+    pub fn new(func: &[N], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let conditionals: Vec<Distribution1D<N>> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditionals.iter().map(|c| c.integral()).collect());
+        Self {
+            conditionals,
+            marginal,
+        }
+    }
+
+    /// Map a uniformly distributed point `u` in the unit square to a point from the distribution.
+    /// Returns the point and its density.
+    pub fn sample(&self, u: &Vector2<N>) -> (Vector2<N>, N) {
+        let (y, pdf_y, row) = self.marginal.sample(u.y);
+        let (x, pdf_x, _) = self.conditionals[row].sample(u.x);
+        (Vector2::new(x, y), pdf_x * pdf_y)
+    }
+
+    /// The density of the distribution at `p`
+    pub fn pdf(&self, p: &Vector2<N>) -> N {
+        let row = step(p.y, self.conditionals.len());
+        self.marginal.pdf(p.y) * self.conditionals[row].pdf(p.x)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::typedefs::*;
+
+    #[test]
+    fn test_1d() {
+        let distribution = Distribution1D::<f>::new(vec![1.0, 0.0, 3.0]);
+        assert!((distribution.integral() - 4.0 / 3.0).abs() < 1e-6);
+
+        // the first step gets a quarter of the samples and the last one gets the rest
+        let (x, pdf, index) = distribution.sample(0.125);
+        assert!((x - 1.0 / 6.0).abs() < 1e-6);
+        assert!((pdf - 0.75).abs() < 1e-6);
+        assert_eq!(index, 0);
+
+        let (x, pdf, index) = distribution.sample(0.625);
+        assert!((x - 5.0 / 6.0).abs() < 1e-6);
+        assert!((pdf - 2.25).abs() < 1e-6);
+        assert_eq!(index, 2);
+
+        assert_eq!(distribution.pdf(0.5), 0.0);
+        assert!(distribution.sample(one_minus_epsilon::<f>()).0 < 1.0);
+
+        // a function that's zero everywhere is sampled uniformly
+        let distribution = Distribution1D::<f>::new(vec![0.0, 0.0]);
+        let (x, pdf, _) = distribution.sample(0.75);
+        assert!((x - 0.75).abs() < 1e-6);
+        assert_eq!(pdf, 1.0);
+    }
+
+    #[test]
+    fn test_2d() {
+        // a 2x2 grid where the bottom right cell has half of the weight
+        let distribution = Distribution2D::new(&[1.0, 1.0, 0.0, 2.0], 2, 2);
+        let mut counts = [0; 4];
+        let n = 64;
+
+        for i in 0..n {
+            for j in 0..n {
+                let u = Vector2f::new((i as f + 0.5) / n as f, (j as f + 0.5) / n as f);
+                let (p, pdf) = distribution.sample(&u);
+                assert!((pdf - distribution.pdf(&p)).abs() < 1e-5);
+                counts[(p.y * 2.0) as usize * 2 + (p.x * 2.0) as usize] += 1;
+            }
+        }
+        assert_eq!(counts, [n * n / 4, n * n / 4, 0, n * n / 2]);
+        assert!((distribution.pdf(&Vector2f::new(0.75, 0.75)) - 2.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,271 @@
+//! Environment maps surround the scene with an image that is infinitely far away, such as a
+//! photograph of the sky, which lights the scene from every direction.
+
+use crate::distribution::Distribution2D;
+use crate::na::{self, RealField, Vector2, Vector3};
+use crate::raster::Raster;
+use crate::tonemap::luminance;
+use crate::{hdr, pfm};
+use std::io;
+use std::path::Path;
+
+/// An equirectangular image that surrounds the scene
+///
+/// The columns of the image span every direction around the vertical (y) axis, and its rows go
+/// from straight up at the top to straight down at the bottom. The center of the image is in the
+/// direction of the negative z axis, which is where the default camera looks, and the positive x
+/// axis is to the right of it.
+///
+/// Directions are sampled in proportion to the luminance of the pixels, so that bright parts of
+/// the image like the sun are found with few samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvironmentMap<N: RealField> {
+    /// The radiance of the environment
+    image: Raster,
+
+    /// The distribution that points in the image are sampled from
+    distribution: Distribution2D<N>,
+
+    /// How far the image is rotated around the vertical axis, in radians
+    rotation: N,
+
+    /// The factor that the radiance of the image is scaled by
+    intensity: N,
+}
+
+impl<N: RealField> EnvironmentMap<N> {
+    /// Create an environment map from an image, which is rotated counterclockwise around the
+    /// vertical axis by `rotation` degrees (as seen from above), and whose radiance is scaled by
+    /// `intensity`
+    pub fn new(image: Raster, rotation: N, intensity: N) -> Self {
+        assert!(
+            image.width > 0 && image.height > 0,
+            "an environment map can't be empty"
+        );
+        let height = N::from_usize(image.height).unwrap();
+        let half = N::from_f32(0.5).unwrap();
+
+        // each pixel covers a solid angle that's proportional to the sine of its polar angle, so
+        // the pixels near the poles are less likely to be picked. Pixels that aren't finite are
+        // never picked, since a single one would make the whole distribution NaN.
+        let weights: Vec<N> = image
+            .pixels
+            .iter()
+            .enumerate()
+            .map(|(i, color)| {
+                let row = N::from_usize(i / image.width).unwrap();
+                let theta = N::pi() * (row + half) / height;
+                let y = luminance(color);
+                let y = if y.is_finite() { y.max(0.0) } else { 0.0 };
+                N::from_f32(y).unwrap() * theta.sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(&weights, image.width, image.height);
+
+        Self {
+            image,
+            distribution,
+            rotation: rotation * N::pi() / N::from_u32(180).unwrap(),
+            intensity,
+        }
+    }
+
+    /// Load an environment map from a Radiance HDR (`.hdr`) or PFM (`.pfm`) file
+    pub fn load<P: AsRef<Path>>(path: P, rotation: N, intensity: N) -> io::Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        let image = match extension.as_deref() {
+            Some("hdr") => hdr::load(path)?,
+            Some("pfm") => pfm::load(path)?,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "environment maps must be .hdr or .pfm files",
+                ))
+            }
+        };
+
+        if image.width == 0 || image.height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the environment map is empty",
+            ));
+        }
+        Ok(Self::new(image, rotation, intensity))
+    }
+
+    /// The point in the image, with both coordinates in [0, 1), that `direction` looks at
+    fn to_image(&self, direction: &Vector3<N>) -> Vector2<N> {
+        let d = direction.normalize();
+        // this is more precise than the arc cosine of y near the poles
+        let theta = (d.x * d.x + d.z * d.z).sqrt().atan2(d.y);
+        let phi = d.x.atan2(-d.z) - self.rotation;
+        let x = N::from_f32(0.5).unwrap() + phi / N::two_pi();
+        Vector2::new(x - x.floor(), theta / N::pi())
+    }
+
+    /// The direction that looks at a point in the image
+    fn to_direction(&self, p: &Vector2<N>) -> Vector3<N> {
+        let phi = (p.x - N::from_f32(0.5).unwrap()) * N::two_pi() + self.rotation;
+        let theta = p.y * N::pi();
+        Vector3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    /// The radiance of the pixel at a point in the image
+    fn lookup(&self, p: &Vector2<N>) -> Vector3<N> {
+        let p: Vector2<f64> = p.map(|x| na::try_convert(x).unwrap_or(0.0));
+        let x = ((p.x * self.image.width as f64) as usize).min(self.image.width - 1);
+        let y = ((p.y * self.image.height as f64) as usize).min(self.image.height - 1);
+        self.image.get(x, y).map(|c| N::from_f32(c).unwrap()) * self.intensity
+    }
+
+    /// The radiance that arrives from the environment along `direction`
+    pub fn radiance(&self, direction: &Vector3<N>) -> Vector3<N> {
+        self.lookup(&self.to_image(direction))
+    }
+
+    /// Sample a direction, where `u` is a uniformly distributed point in the unit square.
+    /// Returns the direction, the radiance that arrives from it, and its density with respect to
+    /// solid angle.
+    pub fn sample(&self, u: &Vector2<N>) -> Option<(Vector3<N>, Vector3<N>, N)> {
+        let (p, pdf) = self.distribution.sample(u);
+        let sin_theta = (p.y * N::pi()).sin();
+
+        if pdf == N::zero() || sin_theta <= N::zero() {
+            return None;
+        }
+        // the image is stretched over the sphere, with a factor of 2 pi for the columns and pi
+        // for the rows, which is scaled by the sine of the polar angle
+        let pdf = pdf / (N::two_pi() * N::pi() * sin_theta);
+        Some((self.to_direction(&p), self.lookup(&p), pdf))
+    }
+
+    /// The density that `sample` has for `direction`
+    pub fn pdf(&self, direction: &Vector3<N>) -> N {
+        let p = self.to_image(direction);
+        let sin_theta = (p.y * N::pi()).sin();
+
+        if sin_theta <= N::zero() {
+            return N::zero();
+        }
+        self.distribution.pdf(&p) / (N::two_pi() * N::pi() * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::{Independent, Sampler};
+    use crate::typedefs::*;
+    use std::f32::consts::PI;
+
+    /// A dim image with a bright patch, which is mostly in the upper right part of the sky
+    fn test_image() -> Raster {
+        let mut image = Raster::new(16, 8);
+
+        for y in 0..8 {
+            for x in 0..16 {
+                image.set(x, y, Color3f::new(0.1, 0.2, 0.3));
+            }
+        }
+        for y in 1..3 {
+            for x in 10..13 {
+                image.set(x, y, Color3f::new(40.0, 30.0, 20.0));
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn test_mapping() {
+        let map = EnvironmentMap::new(test_image(), 0.0, 1.0);
+        let forward = map.to_image(&Vector3f::new(0.0, 0.0, -1.0));
+        assert!((forward - Vector2f::new(0.5, 0.5)).norm() < 1e-6);
+        let right = map.to_image(&Vector3f::new(1.0, 0.0, 0.0));
+        assert!((right - Vector2f::new(0.75, 0.5)).norm() < 1e-6);
+        assert!(map.to_image(&Vector3f::new(0.0, 2.0, 0.0)).y.abs() < 1e-6);
+
+        // rotating the map by 90 degrees brings what was on the right to the front
+        let rotated = EnvironmentMap::new(test_image(), 90.0, 1.0);
+        let front = rotated.to_image(&Vector3f::new(0.0, 0.0, -1.0));
+        assert!((front - Vector2f::new(0.25, 0.5)).norm() < 1e-6);
+
+        for &(x, y) in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7), (0.3, 0.95)] {
+            let p = Vector2f::new(x, y);
+            let direction = rotated.to_direction(&p);
+            assert!((direction.norm() - 1.0).abs() < 1e-5);
+            assert!((rotated.to_image(&direction) - p).norm() < 1e-5, "{}", p);
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let map = EnvironmentMap::new(test_image(), 30.0, 2.0);
+        let mut sampler = Independent::new(0);
+        let trials = 20000;
+        let mut power = Color3f::zeros();
+        let mut bright = 0;
+
+        for i in 0..trials {
+            sampler.start_sample((0, 0), i);
+            let (direction, radiance, pdf) = map.sample(&sampler.get_2d()).unwrap();
+            assert!(
+                (map.pdf(&direction) - pdf).abs() < 1e-3 * pdf,
+                "{}",
+                direction
+            );
+            assert!((map.radiance(&direction) - radiance).norm() < 1e-4);
+
+            if radiance.x > 1.0 {
+                bright += 1;
+            }
+            power += radiance / pdf / trials as f;
+        }
+
+        // the integral of the radiance over the sphere adds up the pixels, weighted by the solid
+        // angles that they cover
+        let mut expected = Color3f::zeros();
+        let image = test_image();
+
+        for y in 0..8 {
+            let solid_angle =
+                2.0 * PI / 16.0 * ((PI * y as f / 8.0).cos() - (PI * (y + 1) as f / 8.0).cos());
+            for x in 0..16 {
+                expected += image.get(x, y) * 2.0 * solid_angle;
+            }
+        }
+        assert!(
+            (power - expected).norm() < 0.01 * expected.norm(),
+            "{} {}",
+            power,
+            expected
+        );
+
+        // the bright patch has about 90% of the power, and gets as many of the samples, even
+        // though it only covers a few pixels
+        assert!((bright as f / trials as f - 0.9).abs() < 0.01, "{}", bright);
+    }
+
+    #[test]
+    fn test_non_finite_pixels() {
+        // the broken pixels are never sampled, and the rest of the map still is
+        let mut image = test_image();
+        image.set(3, 4, Color3f::new(f::INFINITY, 1.0, 1.0));
+        image.set(4, 4, Color3f::new(f::NAN, 1.0, 1.0));
+        let map = EnvironmentMap::new(image, 0.0, 1.0);
+        let mut sampler = Independent::new(0);
+
+        for i in 0..1000 {
+            sampler.start_sample((0, 0), i);
+            let (direction, radiance, pdf) = map.sample(&sampler.get_2d()).unwrap();
+            assert!(pdf.is_finite() && pdf > 0.0, "{}", pdf);
+            assert!(radiance.iter().all(|x| x.is_finite()), "{}", direction);
+        }
+    }
+}
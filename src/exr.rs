@@ -0,0 +1,490 @@
+//! An OpenEXR encoder
+//!
+//! Images are written as single part, uncompressed scanline files, which every EXR reader
+//! supports. Auxiliary layers are stored in the same part as the color, with their channel names
+//! prefixed by the name of the layer (such as `normal.X`), which is how compositing packages
+//! expect multilayer files to be laid out.
+
+use crate::film::Film;
+use crate::typedefs::*;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// The magic number at the start of every EXR file
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// The format that channel values are stored in
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum Precision {
+    /// 16 bit floating point numbers, which halve the size of the file
+    Half,
+
+    /// 32 bit floating point numbers, which store the rendered values exactly
+    Float,
+}
+
+impl Precision {
+    /// The pixel type of the precision, as it's written in the channel list
+    fn pixel_type(self) -> i32 {
+        match self {
+            Precision::Half => 1,
+            Precision::Float => 2,
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half" => Ok(Precision::Half),
+            "float" => Ok(Precision::Float),
+            _ => Err(format!("unknown precision \"{}\"", s)),
+        }
+    }
+}
+
+/// The auxiliary layers that can be saved along with the color of an image
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum Layer {
+    /// The distance to the closest visible surface, in the `depth.Z` channel
+    Depth,
+
+    /// The shading normal, in the `normal.X`, `normal.Y` and `normal.Z` channels
+    Normal,
+
+    /// The albedo, in the `albedo.R`, `albedo.G` and `albedo.B` channels
+    Albedo,
+}
+
+impl FromStr for Layer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depth" => Ok(Layer::Depth),
+            "normal" => Ok(Layer::Normal),
+            "albedo" => Ok(Layer::Albedo),
+            _ => Err(format!("unknown layer \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Layer::Depth => write!(f, "depth"),
+            Layer::Normal => write!(f, "normal"),
+            Layer::Albedo => write!(f, "albedo"),
+        }
+    }
+}
+
+/// A single channel of an image, with one value for each pixel in row-major order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    /// The name of the channel, such as `R` or `normal.X`
+    pub name: String,
+
+    /// The value of the channel for each pixel
+    pub values: Vec<f>,
+}
+
+impl Channel {
+    fn new<I: Iterator<Item = f>>(name: &str, values: I) -> Self {
+        Self {
+            name: name.to_string(),
+            values: values.collect(),
+        }
+    }
+}
+
+/// Convert a 32 bit float to the nearest 16 bit float, rounding ties to even
+pub fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // infinity and NaN, making sure that NaN stays NaN once the mantissa is truncated
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let half_exponent = exponent - 127 + 15;
+
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // The number of low bits that are dropped from the mantissa is usually 13, but it's more when
+    // the value is so small that it has to be stored as a subnormal number, which also makes the
+    // implicit leading bit of the mantissa explicit
+    let (mantissa, shift, base) = if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        (mantissa | 0x80_0000, (14 - half_exponent) as u32, 0)
+    } else {
+        (mantissa, 13, (half_exponent as u32) << 10)
+    };
+    let truncated = mantissa >> shift;
+    let remainder = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+
+    // rounding up can carry into the exponent, which is the correct result
+    let round_up = remainder > halfway || (remainder == halfway && truncated & 1 == 1);
+    sign | (base + truncated + round_up as u32) as u16
+}
+
+/// Write a null terminated string
+fn write_string<W: Write>(out: &mut W, s: &str) -> io::Result<()> {
+    out.write_all(s.as_bytes())?;
+    out.write_all(&[0])
+}
+
+/// Write the name, type and size of a header attribute. The value should be written afterwards.
+fn write_attribute<W: Write>(out: &mut W, name: &str, ty: &str, size: usize) -> io::Result<()> {
+    write_string(out, name)?;
+    write_string(out, ty)?;
+    out.write_all(&(size as i32).to_le_bytes())
+}
+
+/// Write an image to an EXR file. Every channel must have a value for each pixel. Returns an
+/// error if the image is empty or two of the channels have the same name, neither of which EXR
+/// files can represent.
+pub fn write<W: Write>(
+    out: &mut W,
+    width: usize,
+    height: usize,
+    channels: &[Channel],
+    precision: Precision,
+) -> io::Result<()> {
+    for channel in channels {
+        assert_eq!(
+            channel.values.len(),
+            width * height,
+            "channel {} has the wrong number of values",
+            channel.name
+        );
+    }
+
+    if width == 0 || height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "an EXR file can't hold an empty image",
+        ));
+    }
+
+    // the channels have to be stored in alphabetical order
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if let Some(pair) = channels
+        .windows(2)
+        .find(|pair| pair[0].name == pair[1].name)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("the channel {} appears more than once", pair[0].name),
+        ));
+    }
+
+    // the header is buffered, since its size is needed for the offset table that follows it
+    let mut header = Vec::new();
+    header.write_all(&MAGIC)?;
+    header.write_all(&2_u32.to_le_bytes())?;
+
+    let list_size: usize = channels
+        .iter()
+        .map(|c| c.name.len() + 1 + 16)
+        .sum::<usize>()
+        + 1;
+    write_attribute(&mut header, "channels", "chlist", list_size)?;
+
+    for channel in &channels {
+        write_string(&mut header, &channel.name)?;
+        header.write_all(&precision.pixel_type().to_le_bytes())?;
+
+        // the linear flag, three reserved bytes, and the sampling rates in x and y
+        header.write_all(&[0; 4])?;
+        header.write_all(&1_i32.to_le_bytes())?;
+        header.write_all(&1_i32.to_le_bytes())?;
+    }
+    header.write_all(&[0])?;
+
+    write_attribute(&mut header, "compression", "compression", 1)?;
+    header.write_all(&[0])?;
+
+    let window = [0, 0, width as i32 - 1, height as i32 - 1];
+    for name in &["dataWindow", "displayWindow"] {
+        write_attribute(&mut header, name, "box2i", 16)?;
+        for coordinate in &window {
+            header.write_all(&coordinate.to_le_bytes())?;
+        }
+    }
+
+    // scanlines are stored from top to bottom
+    write_attribute(&mut header, "lineOrder", "lineOrder", 1)?;
+    header.write_all(&[0])?;
+    write_attribute(&mut header, "pixelAspectRatio", "float", 4)?;
+    header.write_all(&1.0_f32.to_le_bytes())?;
+    write_attribute(&mut header, "screenWindowCenter", "v2f", 8)?;
+    header.write_all(&[0; 8])?;
+    write_attribute(&mut header, "screenWindowWidth", "float", 4)?;
+    header.write_all(&1.0_f32.to_le_bytes())?;
+    header.write_all(&[0])?;
+    out.write_all(&header)?;
+
+    // Every scanline is its own block, which starts with its y coordinate and the size of its
+    // data. The offset table holds the position in the file that each block starts at.
+    let value_size = match precision {
+        Precision::Half => 2,
+        Precision::Float => 4,
+    };
+    let line_size = width * channels.len() * value_size;
+    let first_block = header.len() + height * 8;
+
+    for y in 0..height {
+        let offset = first_block + y * (8 + line_size);
+        out.write_all(&(offset as u64).to_le_bytes())?;
+    }
+
+    for y in 0..height {
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(line_size as i32).to_le_bytes())?;
+
+        for channel in &channels {
+            for &value in &channel.values[y * width..(y + 1) * width] {
+                match precision {
+                    Precision::Half => out.write_all(&to_half(value).to_le_bytes())?,
+                    Precision::Float => out.write_all(&value.to_le_bytes())?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The channels for the color of a film, along with the channels for the requested auxiliary
+/// layers. Layers that are requested more than once are only included once. Returns an error if
+/// a layer was requested but the film doesn't have AOVs.
+pub fn film_channels(film: &Film, layers: &[Layer]) -> io::Result<Vec<Channel>> {
+    let colors = film.colors();
+    let mut channels = vec![
+        Channel::new("R", colors.iter().map(|c| c.x)),
+        Channel::new("G", colors.iter().map(|c| c.y)),
+        Channel::new("B", colors.iter().map(|c| c.z)),
+    ];
+
+    if layers.is_empty() {
+        return Ok(channels);
+    }
+    let aovs = film.aovs.as_ref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "auxiliary layers were requested, but the film doesn't have any AOVs",
+        )
+    })?;
+
+    for (i, layer) in layers.iter().enumerate() {
+        if layers[..i].contains(layer) {
+            continue;
+        }
+        match layer {
+            Layer::Depth => channels.push(Channel::new("depth.Z", aovs.iter().map(|a| a.depth))),
+            Layer::Normal => {
+                channels.push(Channel::new("normal.X", aovs.iter().map(|a| a.normal.x)));
+                channels.push(Channel::new("normal.Y", aovs.iter().map(|a| a.normal.y)));
+                channels.push(Channel::new("normal.Z", aovs.iter().map(|a| a.normal.z)));
+            }
+            Layer::Albedo => {
+                channels.push(Channel::new("albedo.R", aovs.iter().map(|a| a.albedo.x)));
+                channels.push(Channel::new("albedo.G", aovs.iter().map(|a| a.albedo.y)));
+                channels.push(Channel::new("albedo.B", aovs.iter().map(|a| a.albedo.z)));
+            }
+        }
+    }
+    Ok(channels)
+}
+
+/// Save a film to an EXR file, along with the requested auxiliary layers
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    film: &Film,
+    layers: &[Layer],
+    precision: Precision,
+) -> io::Result<()> {
+    let channels = film_channels(film, layers)?;
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out, film.width, film.height, &channels, precision)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::film::Aov;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_to_half() {
+        assert_eq!(to_half(0.0), 0x0000);
+        assert_eq!(to_half(-0.0), 0x8000);
+        assert_eq!(to_half(1.0), 0x3c00);
+        assert_eq!(to_half(0.5), 0x3800);
+        assert_eq!(to_half(-2.0), 0xc000);
+        assert_eq!(to_half(0.1), 0x2e66);
+        assert_eq!(to_half(65504.0), 0x7bff);
+
+        // overflow and special values
+        assert_eq!(to_half(65520.0), 0x7c00);
+        assert_eq!(to_half(f::INFINITY), 0x7c00);
+        assert_eq!(to_half(f::NEG_INFINITY), 0xfc00);
+        assert_eq!(to_half(f::NAN) & 0x7c00, 0x7c00);
+        assert_ne!(to_half(f::NAN) & 0x3ff, 0);
+
+        // subnormals, including rounding up to the smallest normal number
+        assert_eq!(to_half(2.0_f32.powi(-24)), 0x0001);
+        assert_eq!(to_half(2.0_f32.powi(-25)), 0x0000);
+        assert_eq!(to_half(2.0_f32.powi(-14) * 0.9999), 0x0400);
+        assert_eq!(to_half(1e-10), 0x0000);
+
+        // ties round to even
+        assert_eq!(to_half(1.0 + 2.0_f32.powi(-11)), 0x3c00);
+        assert_eq!(to_half(1.0 + 3.0 * 2.0_f32.powi(-11)), 0x3c02);
+    }
+
+    /// Read a little endian integer of `N` bytes from the start of a slice
+    fn read<const N: usize>(data: &[u8], at: usize) -> [u8; N] {
+        data[at..at + N].try_into().unwrap()
+    }
+
+    /// Read a null terminated string, returning it along with the position after it
+    fn read_string(data: &[u8], at: usize) -> (String, usize) {
+        let end = at + data[at..].iter().position(|&b| b == 0).unwrap();
+        (String::from_utf8(data[at..end].to_vec()).unwrap(), end + 1)
+    }
+
+    /// Parse the header of an EXR file, returning the attributes and the position after it
+    fn read_header(data: &[u8]) -> (Vec<(String, String, Vec<u8>)>, usize) {
+        assert_eq!(data[0..4], MAGIC);
+        assert_eq!(u32::from_le_bytes(read(data, 4)), 2);
+        let mut at = 8;
+        let mut attributes = Vec::new();
+
+        loop {
+            let (name, next) = read_string(data, at);
+            if name.is_empty() {
+                return (attributes, next);
+            }
+            let (ty, next) = read_string(data, next);
+            let size = i32::from_le_bytes(read(data, next)) as usize;
+            attributes.push((name, ty, data[next + 4..next + 4 + size].to_vec()));
+            at = next + 4 + size;
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let channels = vec![
+            Channel::new("R", (0..6).map(|i| i as f)),
+            Channel::new("A", (0..6).map(|i| -(i as f))),
+        ];
+
+        for &precision in &[Precision::Half, Precision::Float] {
+            let mut data = Vec::new();
+            write(&mut data, 3, 2, &channels, precision).unwrap();
+            let (attributes, end) = read_header(&data);
+            let names: Vec<&str> = attributes.iter().map(|(n, _, _)| n.as_str()).collect();
+            assert_eq!(
+                names,
+                vec![
+                    "channels",
+                    "compression",
+                    "dataWindow",
+                    "displayWindow",
+                    "lineOrder",
+                    "pixelAspectRatio",
+                    "screenWindowCenter",
+                    "screenWindowWidth",
+                ]
+            );
+
+            // the channels are sorted by name
+            let list = &attributes[0].2;
+            assert_eq!(list[0..2], *b"A\0");
+            assert_eq!(i32::from_le_bytes(read(list, 2)), precision.pixel_type());
+            assert_eq!(list[18..20], *b"R\0");
+            assert_eq!(list.len(), 2 * 18 + 1);
+
+            let window = &attributes[2].2;
+            assert_eq!(i32::from_le_bytes(read(window, 8)), 2);
+            assert_eq!(i32::from_le_bytes(read(window, 12)), 1);
+
+            // check the second scanline, which holds the A channel and then the R channel
+            let offset = u64::from_le_bytes(read(&data, end + 8)) as usize;
+            assert_eq!(i32::from_le_bytes(read(&data, offset)), 1);
+            let size = i32::from_le_bytes(read(&data, offset + 4)) as usize;
+            let line = &data[offset + 8..offset + 8 + size];
+            let expected = [-3.0, -4.0, -5.0, 3.0, 4.0, 5.0];
+
+            for (i, &value) in expected.iter().enumerate() {
+                match precision {
+                    Precision::Half => {
+                        assert_eq!(u16::from_le_bytes(read(line, 2 * i)), to_half(value))
+                    }
+                    Precision::Float => assert_eq!(f::from_le_bytes(read(line, 4 * i)), value),
+                }
+            }
+            assert_eq!(offset + 8 + size, data.len());
+        }
+
+        // empty images and repeated channel names can't be stored
+        assert!(write(&mut Vec::new(), 0, 2, &[], Precision::Half).is_err());
+        assert!(write(&mut Vec::new(), 3, 0, &[], Precision::Half).is_err());
+        let repeated = vec![
+            channels[0].clone(),
+            channels[1].clone(),
+            channels[0].clone(),
+        ];
+        assert!(write(&mut Vec::new(), 3, 2, &repeated, Precision::Half).is_err());
+    }
+
+    #[test]
+    fn test_film_channels() {
+        let mut film = Film::new(2, 1);
+        film.add_sample(1, 0, &Color3f::new(1.0, 2.0, 3.0), 1.0);
+
+        let channels = film_channels(&film, &[]).unwrap();
+        let names: Vec<&str> = channels.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["R", "G", "B"]);
+        assert_eq!(channels[2].values, vec![0.0, 3.0]);
+
+        // layers can't be written without AOVs
+        assert!(film_channels(&film, &[Layer::Depth]).is_err());
+
+        let aov = Aov {
+            normal: Vector3f::new(0.0, 1.0, 0.0),
+            ..Aov::default()
+        };
+        film.aovs = Some(vec![Aov::default(), aov]);
+        let channels = film_channels(&film, &[Layer::Normal, Layer::Depth]).unwrap();
+        let names: Vec<&str> = channels.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["R", "G", "B", "normal.X", "normal.Y", "normal.Z", "depth.Z"]
+        );
+        assert_eq!(channels[4].values, vec![0.0, 1.0]);
+        assert_eq!(channels[6].values[0], f::INFINITY);
+
+        // repeated layers are only included once
+        let channels = film_channels(&film, &[Layer::Depth, Layer::Depth]).unwrap();
+        let names: Vec<&str> = channels.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["R", "G", "B", "depth.Z"]);
+    }
+}
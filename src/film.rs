@@ -0,0 +1,283 @@
+//! The film records the radiance that arrives at each pixel during a render
+//!
+//! The film stores linear, high dynamic range values, so nothing is lost while rendering. Values
+//! are only converted to a displayable, low dynamic range format when the image is saved.
+
+use crate::filter::Filter;
+use crate::tonemap::ToneMap;
+use crate::typedefs::*;
+
+/// The samples that have been recorded for a single pixel
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct Pixel {
+    /// The weighted sum of the radiance of every sample
+    pub color_sum: Color3f,
+
+    /// The sum of the weights of every sample
+    pub weight_sum: f,
+}
+
+impl Pixel {
+    /// Record a sample with the given radiance and weight
+    pub fn add_sample(&mut self, color: &Color3f, weight: f) {
+        self.color_sum += color * weight;
+        self.weight_sum += weight;
+    }
+
+    /// Add the samples that were recorded in another pixel to this one
+    pub fn merge(&mut self, other: &Pixel) {
+        self.color_sum += other.color_sum;
+        self.weight_sum += other.weight_sum;
+    }
+
+    /// The weighted average of the samples, or black if there aren't any
+    pub fn color(&self) -> Color3f {
+        if self.weight_sum == 0.0 {
+            Color3f::zeros()
+        } else {
+            self.color_sum / self.weight_sum
+        }
+    }
+}
+
+impl Default for Pixel {
+    fn default() -> Self {
+        Self {
+            color_sum: Color3f::zeros(),
+            weight_sum: 0.0,
+        }
+    }
+}
+
+/// Auxiliary information about the surfaces that are visible through a pixel, which is useful for
+/// compositing and denoising
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct Aov {
+    /// The distance from the camera to the closest surface that is visible through the pixel, or
+    /// infinity if there isn't one
+    pub depth: f,
+
+    /// The average shading normal of the visible surfaces
+    pub normal: Vector3f,
+
+    /// The average albedo of the visible surfaces
+    pub albedo: Color3f,
+}
+
+impl Default for Aov {
+    /// The values for a pixel that only sees the background
+    fn default() -> Self {
+        Self {
+            depth: f::INFINITY,
+            normal: Vector3f::zeros(),
+            albedo: Color3f::zeros(),
+        }
+    }
+}
+
+/// Counts of the samples that were taken for a render, and the problems that were found with them
+#[derive(Clone, Debug, Default, PartialEq, Eq, Copy)]
+pub struct SampleStats {
+    /// The number of samples that were taken
+    pub samples: u64,
+
+    /// The number of samples with a NaN or infinite component
+    pub non_finite: u64,
+
+    /// The number of non-finite samples that were dropped rather than recorded
+    pub dropped: u64,
+
+    /// The number of samples with a negative component, which was clamped to zero
+    pub negative: u64,
+
+    /// The number of samples whose luminance was clamped to the maximum
+    pub clamped: u64,
+}
+
+impl SampleStats {
+    /// Whether any of the samples had a problem or were modified
+    pub fn has_problems(&self) -> bool {
+        self.non_finite + self.negative + self.clamped > 0
+    }
+}
+
+impl std::ops::Add for SampleStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            samples: self.samples + other.samples,
+            non_finite: self.non_finite + other.non_finite,
+            dropped: self.dropped + other.dropped,
+            negative: self.negative + other.negative,
+            clamped: self.clamped + other.clamped,
+        }
+    }
+}
+
+/// A grid of pixels that samples are recorded into.
+///
+/// Pixels are stored in row-major order, starting from the top left corner of the image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Film {
+    /// The width of the image, in pixels
+    pub width: usize,
+
+    /// The height of the image, in pixels
+    pub height: usize,
+
+    /// The samples for each pixel
+    pub pixels: Vec<Pixel>,
+
+    /// The auxiliary information for each pixel, if it was rendered
+    pub aovs: Option<Vec<Aov>>,
+
+    /// Counts of the samples that were recorded, and of the ones that had to be fixed or dropped
+    pub stats: SampleStats,
+}
+
+impl Film {
+    /// Create a film with the given resolution that has no samples
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Pixel::default(); width * height],
+            aovs: None,
+            stats: SampleStats::default(),
+        }
+    }
+
+    /// The pixel in column `x` and row `y`, where the top row is row 0
+    pub fn pixel(&self, x: usize, y: usize) -> &Pixel {
+        &self.pixels[y * self.width + x]
+    }
+
+    /// Record a sample for the pixel in column `x` and row `y`
+    pub fn add_sample(&mut self, x: usize, y: usize, color: &Color3f, weight: f) {
+        self.pixels[y * self.width + x].add_sample(color, weight);
+    }
+
+    /// Record a sample at a continuous position on the film, where `(0, 0)` is the top left corner
+    /// of the image and `(width, height)` is the bottom right corner. The sample is added to every
+    /// pixel whose center is within the radius of the filter, weighted by the filter. Pixels that
+    /// would be outside of the film are skipped.
+    pub fn splat(&mut self, x: f, y: f, color: &Color3f, filter: &dyn Filter) {
+        let radius = filter.radius();
+        // the range of pixels whose centers are within the radius along one axis
+        let range = |p: f, size: usize| {
+            let first = (p - 0.5 - radius).ceil().max(0.0) as usize;
+            let last = (p - 0.5 + radius).floor().min(size as f - 1.0);
+            (first, last)
+        };
+        let (x0, x1) = range(x, self.width);
+        let (y0, y1) = range(y, self.height);
+
+        if x1 < 0.0 || y1 < 0.0 {
+            return;
+        }
+
+        for py in y0..=y1 as usize {
+            let wy = filter.weight_1d(y - (py as f + 0.5));
+
+            for px in x0..=x1 as usize {
+                let weight = filter.weight_1d(x - (px as f + 0.5)) * wy;
+
+                if weight != 0.0 {
+                    self.add_sample(px, py, color, weight);
+                }
+            }
+        }
+    }
+
+    /// The linear radiance of the pixel in column `x` and row `y`
+    pub fn get(&self, x: usize, y: usize) -> Color3f {
+        self.pixel(x, y).color()
+    }
+
+    /// The linear radiance of every pixel, in the same order as `pixels`
+    pub fn colors(&self) -> Vec<Color3f> {
+        self.pixels.iter().map(Pixel::color).collect()
+    }
+
+    /// Convert the image to 8 bit sRGB values that are ready to be displayed or saved, using
+    /// `tone_map` to bring the radiance into the range that can be displayed
+    pub fn to_rgb8(&self, tone_map: &ToneMap) -> Vec<u8> {
+        self.colors()
+            .iter()
+            .flat_map(|color| tone_map.to_srgb8(color).to_vec())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::filter::{BoxFilter, TentFilter};
+
+    #[test]
+    fn test_samples() {
+        let mut film = Film::new(3, 2);
+        assert_eq!(film.pixels.len(), 6);
+        assert_eq!(film.get(2, 1), Color3f::zeros());
+
+        film.add_sample(2, 1, &Color3f::new(1.0, 2.0, 3.0), 1.0);
+        film.add_sample(2, 1, &Color3f::new(4.0, 8.0, 12.0), 0.5);
+        assert_eq!(film.pixel(2, 1).weight_sum, 1.5);
+        assert_eq!(film.get(2, 1), Color3f::new(2.0, 4.0, 6.0));
+        assert_eq!(film.pixels[5].color(), film.get(2, 1));
+        assert_eq!(film.get(0, 0), Color3f::zeros());
+    }
+
+    #[test]
+    fn test_splat() {
+        let white = Color3f::new(1.0, 1.0, 1.0);
+
+        // a half pixel box only covers the pixel that the sample is in, even on its edge
+        let mut film = Film::new(3, 3);
+        let box_filter = BoxFilter { radius: 0.5 };
+        film.splat(1.0, 1.0, &white, &box_filter);
+        film.splat(1.99, 1.5, &white, &box_filter);
+        assert_eq!(film.pixel(1, 1).weight_sum, 2.0);
+        assert_eq!(film.pixels.iter().map(|p| p.weight_sum).sum::<f>(), 2.0);
+
+        // a tent spreads the sample into the neighboring pixels
+        let mut film = Film::new(3, 3);
+        let tent = TentFilter { radius: 1.0 };
+        film.splat(1.5, 1.0, &white, &tent);
+        assert_eq!(film.pixel(1, 1).weight_sum, 0.5);
+        assert_eq!(film.pixel(1, 0).weight_sum, 0.5);
+        assert_eq!(film.pixel(0, 1).weight_sum, 0.0);
+        assert_eq!(film.get(1, 0), white);
+
+        // samples near the edges only reach the pixels that exist
+        film.splat(0.0, 0.0, &white, &tent);
+        film.splat(-5.0, 7.0, &white, &tent);
+        assert_eq!(film.pixel(0, 0).weight_sum, 0.25);
+        assert_eq!(film.pixels.iter().map(|p| p.weight_sum).sum::<f>(), 1.25);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = Pixel::default();
+        a.add_sample(&Color3f::new(1.0, 0.0, 0.0), 1.0);
+        let mut b = Pixel::default();
+        b.add_sample(&Color3f::new(0.0, 0.0, 3.0), 2.0);
+        a.merge(&b);
+        assert_eq!(a.weight_sum, 3.0);
+        assert_eq!(a.color_sum, Color3f::new(1.0, 0.0, 6.0));
+    }
+
+    #[test]
+    fn test_high_dynamic_range() {
+        // values far outside of [0, 1] are kept until the image is converted
+        let mut film = Film::new(2, 1);
+        film.add_sample(0, 0, &Color3f::new(0.0, 0.25, 1.0), 1.0);
+        film.add_sample(1, 0, &Color3f::new(-1.0, 400.0, f::NAN), 1.0);
+        assert_eq!(film.get(1, 0).y, 400.0);
+        assert_eq!(
+            film.to_rgb8(&ToneMap::default()),
+            vec![0, 137, 255, 0, 255, 0]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Reconstruction filters, which decide how much each sample contributes to the pixels around it
+//!
+//! Every sample is splatted into each pixel whose center is within the filter's radius of the
+//! sample, weighted by the filter. The value of a pixel is the weighted average of the samples
+//! that were splatted into it. All of the filters here are separable, so a filter's weight for an
+//! offset is the product of its weights for the horizontal and vertical offsets.
+
+use crate::typedefs::*;
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// A pixel reconstruction filter
+pub trait Filter {
+    /// The distance from the center of the filter, in pixels, beyond which its weight is zero
+    fn radius(&self) -> f;
+
+    /// The weight of the filter at a horizontal or vertical offset `x` from its center, in pixels
+    fn weight_1d(&self, x: f) -> f;
+
+    /// The weight of a sample that's offset by `(x, y)` pixels from the center of a pixel
+    fn weight(&self, x: f, y: f) -> f {
+        self.weight_1d(x) * self.weight_1d(y)
+    }
+}
+
+/// An owned reference to a filter trait object that can be shared between threads
+pub type FilterRef = Box<dyn Filter + Send + Sync>;
+
+/// Every sample contributes equally to the pixels within the radius. A box with a radius of half
+/// a pixel only contributes to the pixel that the sample is in, which is a plain average.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct BoxFilter {
+    pub radius: f,
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f {
+        self.radius
+    }
+
+    fn weight_1d(&self, x: f) -> f {
+        // the interval is half open, so that a sample on the edge between two pixels only
+        // contributes to one of them
+        if x >= -self.radius && x < self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The weight falls off linearly from the center of the filter
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct TentFilter {
+    pub radius: f,
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f {
+        self.radius
+    }
+
+    fn weight_1d(&self, x: f) -> f {
+        (1.0 - x.abs() / self.radius).max(0.0)
+    }
+}
+
+/// A Gaussian, shifted down so that it reaches zero at the radius
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct GaussianFilter {
+    pub radius: f,
+
+    /// The falloff of the Gaussian, where larger values make it narrower
+    pub alpha: f,
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f {
+        self.radius
+    }
+
+    fn weight_1d(&self, x: f) -> f {
+        let gaussian = |x: f| (-self.alpha * x * x).exp();
+        (gaussian(x) - gaussian(self.radius)).max(0.0)
+    }
+}
+
+/// The cubic filter from Mitchell and Netravali, "Reconstruction Filters in Computer Graphics".
+/// It has small negative lobes, which keep edges sharper than the positive filters do.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct MitchellFilter {
+    pub radius: f,
+
+    /// The parameters of the family of cubics. Mitchell and Netravali recommend B = C = 1/3.
+    pub b: f,
+    pub c: f,
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f {
+        self.radius
+    }
+
+    fn weight_1d(&self, x: f) -> f {
+        let (b, c) = (self.b, self.c);
+        // the cubic is defined over [-2, 2]
+        let x = (2.0 * x / self.radius).abs();
+
+        if x >= 2.0 {
+            0.0
+        } else if x >= 1.0 {
+            ((-b - 6.0 * c) * x.powi(3)
+                + (6.0 * b + 30.0 * c) * x.powi(2)
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                + (6.0 - 2.0 * b))
+                / 6.0
+        }
+    }
+}
+
+/// A sinc function windowed by a wider sinc that reaches zero at the radius. This is the
+/// sharpest of the filters, but its negative lobes can cause ringing around bright edges.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct LanczosFilter {
+    pub radius: f,
+}
+
+/// The normalized sinc function, `sin(pi x) / (pi x)`
+fn sinc(x: f) -> f {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f {
+        self.radius
+    }
+
+    fn weight_1d(&self, x: f) -> f {
+        if x.abs() >= self.radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+/// The filters that are built into the crate, which can be selected by name
+#[derive(Clone, Debug, Default, PartialEq, Eq, Copy)]
+pub enum FilterKind {
+    /// A box filter (see `BoxFilter`), which averages the samples in each pixel by default
+    #[default]
+    Box,
+
+    /// A tent filter (see `TentFilter`)
+    Tent,
+
+    /// A Gaussian filter (see `GaussianFilter`)
+    Gaussian,
+
+    /// The Mitchell-Netravali filter (see `MitchellFilter`)
+    Mitchell,
+
+    /// A Lanczos windowed sinc filter (see `LanczosFilter`)
+    Lanczos,
+}
+
+impl FilterKind {
+    /// The radius of the filter, when one isn't given
+    pub fn default_radius(&self) -> f {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+
+    /// Create a filter of this kind, with its default radius unless `radius` is given
+    pub fn build(&self, radius: Option<f>) -> FilterRef {
+        let radius = radius.unwrap_or_else(|| self.default_radius());
+
+        match self {
+            FilterKind::Box => Box::new(BoxFilter { radius }),
+            FilterKind::Tent => Box::new(TentFilter { radius }),
+            FilterKind::Gaussian => Box::new(GaussianFilter { radius, alpha: 2.0 }),
+            FilterKind::Mitchell => Box::new(MitchellFilter {
+                radius,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            FilterKind::Lanczos => Box::new(LanczosFilter { radius }),
+        }
+    }
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(FilterKind::Box),
+            "tent" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian),
+            "mitchell" => Ok(FilterKind::Mitchell),
+            "lanczos" => Ok(FilterKind::Lanczos),
+            _ => Err(format!("unknown filter \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterKind::Box => write!(f, "box"),
+            FilterKind::Tent => write!(f, "tent"),
+            FilterKind::Gaussian => write!(f, "gaussian"),
+            FilterKind::Mitchell => write!(f, "mitchell"),
+            FilterKind::Lanczos => write!(f, "lanczos"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KINDS: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    #[test]
+    fn test_names() {
+        for kind in &KINDS {
+            assert_eq!(kind.to_string().parse::<FilterKind>(), Ok(*kind));
+        }
+        assert!("sinc".parse::<FilterKind>().is_err());
+    }
+
+    #[test]
+    fn test_shape() {
+        for kind in &KINDS {
+            let filter = kind.build(None);
+            let r = filter.radius();
+            assert_eq!(r, kind.default_radius());
+
+            // every filter peaks in the middle, is symmetric, and is zero outside of its radius
+            let peak = filter.weight_1d(0.0);
+            assert!(peak > 0.0, "{}", kind);
+            assert_eq!(filter.weight(0.0, 0.0), peak * peak);
+
+            for i in 1..20 {
+                let x = r * i as f / 20.0;
+                assert!(filter.weight_1d(x) <= peak, "{}", kind);
+                assert!(
+                    (filter.weight_1d(x) - filter.weight_1d(-x)).abs() < 1e-6,
+                    "{}",
+                    kind
+                );
+            }
+            assert_eq!(filter.weight_1d(r + 0.01), 0.0, "{}", kind);
+            assert_eq!(filter.weight(r + 0.01, 0.0), 0.0, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        let box_filter = BoxFilter { radius: 0.5 };
+        assert_eq!(box_filter.weight(-0.5, 0.2), 1.0);
+        assert_eq!(box_filter.weight(0.5, 0.2), 0.0);
+
+        let tent = TentFilter { radius: 2.0 };
+        assert_eq!(tent.weight(1.0, 0.0), 0.5);
+        assert_eq!(tent.weight(1.0, 1.0), 0.25);
+
+        // the Mitchell filter integrates to one and is negative near its edge
+        let mitchell = FilterKind::Mitchell.build(Some(2.0));
+        let integral: f = (-200..200)
+            .map(|i| mitchell.weight_1d((i as f + 0.5) / 100.0) / 100.0)
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-3, "{}", integral);
+        assert!(mitchell.weight_1d(1.5) < 0.0);
+
+        // the Lanczos filter is zero at every whole number of pixels from its center
+        let lanczos = LanczosFilter { radius: 3.0 };
+        assert_eq!(lanczos.weight_1d(0.0), 1.0);
+        assert!(lanczos.weight_1d(1.0).abs() < 1e-6);
+        assert!(lanczos.weight_1d(2.0).abs() < 1e-6);
+        assert!(lanczos.weight_1d(1.5) < 0.0);
+    }
+}
@@ -0,0 +1,429 @@
+//! A Radiance HDR (RGBE) encoder and decoder
+//!
+//! Each pixel is stored as an 8 bit mantissa for each color channel, along with an 8 bit exponent
+//! that all three channels share. Files are written with the run length encoding that Radiance
+//! and every other HDR reader supports. The decoder also understands flat scanlines, the older
+//! run length encoding, and images that are stored bottom-up or right-to-left.
+
+use crate::raster::Raster;
+use crate::typedefs::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// The shortest run of repeated bytes that is worth encoding as a run
+const MIN_RUN: usize = 3;
+
+/// Slightly less than the largest value that the shared exponent can represent, which is
+/// `255 / 256 * 2^127`
+const MAX_VALUE: f = 1.69e38;
+
+/// The widths of the scanlines that can use the run length encoding
+const RLE_WIDTHS: std::ops::RangeInclusive<usize> = 8..=0x7fff;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Convert a linear color to the shared exponent representation. Negative and NaN components
+/// are stored as zero, and infinite components as the largest representable value.
+pub fn to_rgbe(color: &Color3f) -> [u8; 4] {
+    let c = color.map(|e| if e > 0.0 { e.min(MAX_VALUE) } else { 0.0 });
+    let v = c.x.max(c.y).max(c.z);
+
+    if v < 1e-32 {
+        return [0; 4];
+    }
+    // `v` is a normal float, so its exponent can be read directly from its bits. This is the
+    // exponent `e` for which `v = m * 2^e` with `m` in [0.5, 1).
+    let exponent = ((v.to_bits() >> 23) & 0xff) as i32 - 126;
+    let scale = 256.0 / (2.0 as f).powi(exponent);
+    [
+        (c.x * scale) as u8,
+        (c.y * scale) as u8,
+        (c.z * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+/// Convert a shared exponent color back to a linear color. Each mantissa is mapped to the middle
+/// of the range of values that round down to it.
+pub fn from_rgbe(rgbe: [u8; 4]) -> Color3f {
+    if rgbe[3] == 0 {
+        return Color3f::zeros();
+    }
+    let scale = (2.0 as f).powi(rgbe[3] as i32 - (128 + 8));
+    Color3f::new(
+        (rgbe[0] as f + 0.5) * scale,
+        (rgbe[1] as f + 0.5) * scale,
+        (rgbe[2] as f + 0.5) * scale,
+    )
+}
+
+/// Run length encode one component of a scanline
+fn write_rle<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    let run_length = |start: usize| {
+        data[start..]
+            .iter()
+            .take(127)
+            .take_while(|&&b| b == data[start])
+            .count()
+    };
+    let mut i = 0;
+
+    while i < data.len() {
+        let run = run_length(i);
+
+        if run >= MIN_RUN {
+            out.write_all(&[128 + run as u8, data[i]])?;
+            i += run;
+            continue;
+        }
+        // copy bytes literally until the next run that's worth encoding
+        let start = i;
+
+        while i < data.len() && i - start < 128 && run_length(i) < MIN_RUN {
+            i += 1;
+        }
+        out.write_all(&[(i - start) as u8])?;
+        out.write_all(&data[start..i])?;
+    }
+    Ok(())
+}
+
+/// Write an image as a Radiance HDR file. The top row of the image is stored first.
+pub fn write<W: Write>(out: &mut W, image: &Raster) -> io::Result<()> {
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n")?;
+    writeln!(out, "-Y {} +X {}", image.height, image.width)?;
+
+    for row in image.pixels.chunks(image.width.max(1)) {
+        let scanline: Vec<[u8; 4]> = row.iter().map(to_rgbe).collect();
+
+        if !RLE_WIDTHS.contains(&image.width) {
+            for pixel in &scanline {
+                out.write_all(pixel)?;
+            }
+            continue;
+        }
+        out.write_all(&[2, 2, (image.width >> 8) as u8, image.width as u8])?;
+
+        // each component is encoded separately, since they're much more likely to have runs
+        for component in 0..4 {
+            let data: Vec<u8> = scanline.iter().map(|p| p[component]).collect();
+            write_rle(out, &data)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a line of the header, without the trailing newline
+fn read_line<R: BufRead>(input: &mut R) -> io::Result<String> {
+    let mut line = Vec::new();
+    input.read_until(b'\n', &mut line)?;
+
+    if line.last() != Some(&b'\n') {
+        return Err(invalid("the HDR header ended unexpectedly"));
+    }
+    line.pop();
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Read a scanline that was stored with the run length encoding
+fn read_rle_scanline<R: Read>(input: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    for component in 0..4 {
+        let mut x = 0;
+
+        while x < scanline.len() {
+            let mut byte = [0; 1];
+            input.read_exact(&mut byte)?;
+            let (count, run) = if byte[0] > 128 {
+                ((byte[0] - 128) as usize, true)
+            } else {
+                (byte[0] as usize, false)
+            };
+
+            if count == 0 || x + count > scanline.len() {
+                return Err(invalid("a run in the HDR data doesn't fit in its scanline"));
+            }
+            if run {
+                input.read_exact(&mut byte)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[component] = byte[0];
+                }
+            } else {
+                let mut bytes = vec![0; count];
+                input.read_exact(&mut bytes)?;
+                for (pixel, b) in scanline[x..x + count].iter_mut().zip(bytes) {
+                    pixel[component] = b;
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(())
+}
+
+/// Read a scanline that was stored without the run length encoding, or with the original one,
+/// where a pixel of `(1, 1, 1, n)` repeats the previous pixel. Consecutive repeats are combined,
+/// with each one contributing 8 more significant bits to the count. The first pixel of the
+/// scanline has already been read.
+fn read_flat_scanline<R: Read>(
+    input: &mut R,
+    first: [u8; 4],
+    scanline: &mut [[u8; 4]],
+) -> io::Result<()> {
+    let mut pixel = first;
+    let mut x = 0;
+    let mut shift = 0;
+
+    loop {
+        if pixel[..3] == [1, 1, 1] {
+            if x == 0 || shift > 16 {
+                return Err(invalid("an HDR scanline has an invalid repeat"));
+            }
+            let count = (pixel[3] as usize) << shift;
+
+            if x + count > scanline.len() {
+                return Err(invalid("a run in the HDR data doesn't fit in its scanline"));
+            }
+            let previous = scanline[x - 1];
+            for p in &mut scanline[x..x + count] {
+                *p = previous;
+            }
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x] = pixel;
+            x += 1;
+            shift = 0;
+        }
+
+        if x == scanline.len() {
+            return Ok(());
+        }
+        input.read_exact(&mut pixel)?;
+    }
+}
+
+/// Read a Radiance HDR file. Pixel values are divided by any exposure that was applied to the
+/// file, so that they are back in the units that they were rendered in.
+pub fn read<R: BufRead>(input: &mut R) -> io::Result<Raster> {
+    if !read_line(input)?.starts_with("#?") {
+        return Err(invalid("the file isn't a Radiance HDR file"));
+    }
+    let mut exposure = 1.0;
+
+    loop {
+        let line = read_line(input)?;
+
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format.trim() != "32-bit_rle_rgbe" {
+                return Err(invalid("only RGB HDR files are supported"));
+            }
+        } else if let Some(value) = line.strip_prefix("EXPOSURE=") {
+            exposure *= value
+                .trim()
+                .parse::<f>()
+                .map_err(|_| invalid("the HDR file has an invalid exposure"))?;
+        }
+    }
+    let resolution = read_line(input)?;
+    let tokens: Vec<&str> = resolution.split_whitespace().collect();
+    let size = |token: &str| {
+        token
+            .parse::<usize>()
+            .map_err(|_| invalid("the HDR file has an invalid resolution"))
+    };
+
+    let (top_down, left_to_right, width, height) = match tokens.as_slice() {
+        [y, height, x, width] if y.ends_with('Y') && x.ends_with('X') => (
+            y.starts_with('-'),
+            x.starts_with('+'),
+            size(width)?,
+            size(height)?,
+        ),
+        _ => return Err(invalid("the HDR file has an unsupported orientation")),
+    };
+    if !Raster::is_loadable(width, height) {
+        return Err(invalid("the HDR file's resolution is too large"));
+    }
+
+    // the image is read a scanline at a time, so that a file that's shorter than its header says
+    // fails before the memory for the whole image is allocated
+    let mut scanline = vec![[0; 4]; width];
+    let mut pixels = Vec::new();
+
+    for _ in 0..height {
+        if width == 0 {
+            break;
+        }
+        let mut first = [0; 4];
+        input.read_exact(&mut first)?;
+        let is_rle = first[..2] == [2, 2]
+            && first[2] & 0x80 == 0
+            && RLE_WIDTHS.contains(&width)
+            && ((first[2] as usize) << 8 | first[3] as usize) == width;
+
+        if is_rle {
+            read_rle_scanline(input, &mut scanline)?;
+        } else {
+            read_flat_scanline(input, first, &mut scanline)?;
+        }
+        let colors = scanline.iter().map(|&rgbe| from_rgbe(rgbe) / exposure);
+
+        if left_to_right {
+            pixels.extend(colors);
+        } else {
+            pixels.extend(colors.rev());
+        }
+    }
+
+    if !top_down {
+        pixels = pixels
+            .chunks(width.max(1))
+            .rev()
+            .flatten()
+            .cloned()
+            .collect();
+    }
+    Ok(Raster {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Save an image to a Radiance HDR file
+pub fn save<P: AsRef<Path>>(path: P, image: &Raster) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out, image)?;
+    out.flush()
+}
+
+/// Load an image from a Radiance HDR file
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Raster> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An image with smooth gradients, runs of a single color, and a very bright pixel
+    fn test_image(width: usize, height: usize) -> Raster {
+        let mut image = Raster::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let color = if x >= width / 2 {
+                    Color3f::new(0.25, 0.5, 1.0)
+                } else {
+                    Color3f::new(x as f * 0.1, y as f * 3.0, 0.001)
+                };
+                image.set(x, y, color);
+            }
+        }
+        image.set(0, 0, Color3f::new(1000.0, 20.0, 0.0));
+        image
+    }
+
+    /// The relative error that the shared exponent allows for the brightest component
+    fn close(a: &Color3f, b: &Color3f) -> bool {
+        (a - b).abs().max() <= a.abs().max() / 128.0
+    }
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(to_rgbe(&Color3f::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(&Color3f::zeros()), [0; 4]);
+        assert_eq!(to_rgbe(&Color3f::new(-1.0, f::NAN, 0.0)), [0; 4]);
+        assert_eq!(to_rgbe(&Color3f::new(f::INFINITY, 0.0, 0.0))[3], 255);
+        assert_eq!(from_rgbe([0; 4]), Color3f::zeros());
+        assert_eq!(
+            from_rgbe([128, 64, 0, 129]),
+            Color3f::new(128.5, 64.5, 0.5) / 128.0
+        );
+
+        for &c in &[
+            Color3f::new(1.0, 2.0, 3.0),
+            Color3f::new(1e-5, 0.3, 0.3),
+            Color3f::new(5e4, 1.0, 7e3),
+        ] {
+            let rgbe = to_rgbe(&c);
+            assert!(close(&c, &from_rgbe(rgbe)), "{:?}", c);
+
+            // decoding and encoding again is lossless
+            assert_eq!(to_rgbe(&from_rgbe(rgbe)), rgbe);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // the narrow image is too small for the run length encoding
+        for &(width, height) in &[(3, 2), (40, 5), (300, 1)] {
+            let image = test_image(width, height);
+            let mut data = Vec::new();
+            write(&mut data, &image).unwrap();
+
+            let decoded = read(&mut data.as_slice()).unwrap();
+            assert_eq!((decoded.width, decoded.height), (width, height));
+            for (a, b) in image.pixels.iter().zip(&decoded.pixels) {
+                assert!(close(a, b), "{:?} {:?}", a, b);
+            }
+
+            // decoded files are written back out exactly
+            let mut again = Vec::new();
+            write(&mut again, &decoded).unwrap();
+            assert_eq!(data, again);
+        }
+
+        // the runs of the same color compress well
+        let mut data = Vec::new();
+        write(&mut data, &test_image(300, 1)).unwrap();
+        assert!(data.len() < 800, "{}", data.len());
+    }
+
+    #[test]
+    fn test_read_flat() {
+        // a bottom-up file with the old run length encoding and an exposure
+        let mut data = b"#?RGBE\nEXPOSURE=2.0\n\n+Y 2 +X 3\n".to_vec();
+        data.extend_from_slice(&[128, 0, 0, 129, 1, 1, 1, 2]);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 128, 0, 129, 0, 0, 128, 129]);
+        let image = read(&mut data.as_slice()).unwrap();
+
+        let red = from_rgbe([128, 0, 0, 129]) / 2.0;
+        assert_eq!(image.pixels[3..], [red, red, red]);
+        assert_eq!(image.get(0, 0), Color3f::zeros());
+        assert_eq!(image.get(2, 0), from_rgbe([0, 0, 128, 129]) / 2.0);
+    }
+
+    #[test]
+    fn test_read_mirrored() {
+        let mut data = b"#?RADIANCE\n\n-Y 1 -X 2\n".to_vec();
+        data.extend_from_slice(&[128, 0, 0, 129, 0, 128, 0, 129]);
+        let image = read(&mut data.as_slice()).unwrap();
+        assert!(image.get(0, 0).y > 0.0 && image.get(1, 0).x > 0.0);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let files: &[&[u8]] = &[
+            b"P6\n",
+            b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0",
+            b"#?RADIANCE\n\n+X 1 -Y 1\n\0\0\0\0",
+            b"#?RADIANCE\n\n-Y 1 +X 1\n",
+            b"#?RADIANCE\n\n-Y 1 +X 2\n\x01\x01\x01\x01",
+            b"#?RADIANCE\n\n-Y 1 +X 8\n\x02\x02\x00\x08\x89\x00",
+            b"#?RADIANCE\n\n-Y 999999999 +X 999999999\n",
+            b"#?RADIANCE\n\n-Y 0 +X 18446744073709551615\n",
+            b"#?RADIANCE\n\n-Y 1000 +X 1000\n\x01\x01\x01\x80",
+        ];
+
+        for file in files {
+            assert!(read(&mut &file[..]).is_err());
+        }
+    }
+}
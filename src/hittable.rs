@@ -1,15 +1,85 @@
+use crate::aabb::AABB;
 use crate::material::BSDF;
-use crate::na::{RealField, Vector3};
+use crate::na::{RealField, Vector2, Vector3};
 use crate::ray::Ray;
-use log::{info, warn};
+use log::warn;
 
 /// A struct that is returned by a hit query that indicates whether some object has been hit by a
 /// ray, and relevant location information if it has.
 #[derive(Clone, Debug, PartialEq, Copy)]
 pub struct HitRecord<N: RealField + Sync> {
+    /// The ray parameter at which the hit occurred
     pub t: N,
+
+    /// The point in space where the hit occurred
+    pub p: Vector3<N>,
+
+    /// The unit normal of the actual geometry at the hit point, which faces the side of the
+    /// surface that the ray came from
+    pub normal: Vector3<N>,
+
+    /// The unit normal that should be used for shading. This is the same as `normal` unless the
+    /// primitive supplies smoothed normals, such as a mesh with per-vertex normals.
+    pub shading_normal: Vector3<N>,
+
+    /// The surface parameterization of the hit point, for texture lookups
+    pub uv: Vector2<N>,
+
+    /// Whether the ray hit the outside of the surface. The outside of a sphere faces away from
+    /// its center, and the outside of a triangle is the side that its vertices wind
+    /// counterclockwise around. Materials that care about which side was hit, like dielectrics,
+    /// use this since the normals always face the ray.
+    pub front_face: bool,
+}
+
+impl<N: RealField + Sync> HitRecord<N> {
+    /// Flip the normals of a hit whose normals face the outside of the surface so that they face
+    /// the side that a ray traveling in `direction` came from, and record which side that was
+    pub fn face_forward(mut self, direction: &Vector3<N>) -> Self {
+        self.front_face = direction.dot(&self.normal) < N::zero();
+
+        if !self.front_face {
+            self.normal = -self.normal;
+            self.shading_normal = -self.shading_normal;
+        }
+        self
+    }
+}
+
+/// A point that was sampled on the surface of an object, such as a point on an area light
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct SurfaceSample<N: RealField> {
+    /// The point on the surface
     pub p: Vector3<N>,
+
+    /// The unit normal of the surface at the point
     pub normal: Vector3<N>,
+
+    /// The probability density of sampling the point, with respect to the solid angle that it
+    /// covers as seen from the reference point
+    pub pdf: N,
+}
+
+/// Convert the density of a point on a surface with respect to area into a density with respect
+/// to solid angle, as seen from `reference`. Returns zero if the surface is seen edge on.
+pub fn area_to_solid_angle<N: RealField>(
+    pdf: N,
+    reference: &Vector3<N>,
+    p: &Vector3<N>,
+    normal: &Vector3<N>,
+) -> N {
+    let offset = p - reference;
+    let distance_squared = offset.norm_squared();
+
+    if distance_squared == N::zero() {
+        return N::zero();
+    }
+    let cos = normal.dot(&offset).abs() / distance_squared.sqrt();
+
+    if cos == N::zero() {
+        return N::zero();
+    }
+    pdf * distance_squared / cos
 }
 
 /// Any object/struct that implements `Hittable` is something that can be hit by a ray and
@@ -21,11 +91,49 @@ pub trait Hittable {
     /// Whether the object was hit. If so, it will be indicated in the hit record along with other
     /// relevant info. If there is a hit, then there will be a hit record. If not, a `None`
     /// will be returned.
-    fn hit(&self, ray: &Ray<Self::NumType>) -> Option<HitRecord<Self::NumType>>;
+    ///
+    /// Only intersections whose ray parameter lies within `[t_min, t_max]` count as hits. If an
+    /// object is intersected more than once along the ray, the closest intersection within that
+    /// range must be returned.
+    fn hit(
+        &self,
+        ray: &Ray<Self::NumType>,
+        t_min: Self::NumType,
+        t_max: Self::NumType,
+    ) -> Option<HitRecord<Self::NumType>>;
+
+    /// The axis-aligned box that fully encloses the object, which is used by acceleration
+    /// structures to skip objects that a ray cannot hit. Objects that are unbounded (such as
+    /// infinite planes) should return `None`.
+    fn bounding_box(&self) -> Option<AABB<Self::NumType>>;
+
+    /// Sample a point on the surface of the object that is visible from `reference`, where `u` is
+    /// a uniformly distributed point in the unit square. This is what lets an object be used as an
+    /// area light. Objects that can't be sampled return `None`, which is what the default
+    /// implementation does.
+    fn sample(
+        &self,
+        _reference: &Vector3<Self::NumType>,
+        _u: &Vector2<Self::NumType>,
+    ) -> Option<SurfaceSample<Self::NumType>> {
+        None
+    }
+
+    /// The density with respect to solid angle that `sample` has for the first point of the
+    /// object that is hit by a ray from `reference` in `direction`. This is zero if the ray
+    /// misses the object, or if the object can't be sampled, which is what the default
+    /// implementation returns.
+    fn pdf(
+        &self,
+        _reference: &Vector3<Self::NumType>,
+        _direction: &Vector3<Self::NumType>,
+    ) -> Self::NumType {
+        num::zero()
+    }
 }
 
 /// A parallel reference to a hittable object
-type HittableRef<N> = Box<dyn Hittable<NumType = N> + Sync>;
+pub type HittableRef<N> = Box<dyn Hittable<NumType = N> + Sync>;
 
 /// An owned reference to a BSDF trait object that is also `Sync`
 pub type BSDFRef<N> = Box<dyn BSDF<N> + Sync>;
@@ -54,36 +162,82 @@ pub fn any_hit<'a, N: RealField + Sync>(
     t_min: Option<N>,
     t_max: Option<N>,
 ) -> Option<(HitRecord<N>, &'a ObjRef<N>)> {
-    if list.len() < 1 {
+    if list.is_empty() {
         warn!("The list of objects was empty. Unless your scene is empty, this should not happen");
         return None;
     }
-    let mut closest_hit: Option<HitRecord<N>> = None;
-    let mut hit_obj: Option<&ObjRef<N>> = None;
+    let t_min = t_min.unwrap_or_else(|| -N::max_value());
+    let mut closest_t = t_max.unwrap_or_else(N::max_value);
+    let mut closest: Option<(HitRecord<N>, &ObjRef<N>)> = None;
 
-    // Iterate through each object, looking for a hit. If we have a closer hit, update the hit
-    // object.
+    // Iterate through each object, looking for a hit. The closest hit so far is used as the upper
+    // bound for the next query, so any hit that is returned is closer than the current one.
     for obj_ref in list {
-        if let Some(hit_record) = obj_ref.hittable.hit(ray) {
-            // There are two cases here: either we didn't have a hit before, and now we do, or we
-            // already have a hit, and the new hit is closer than that.
-            if let Some(curr_closest_hit) = closest_hit {
-                if (hit_record.t < curr_closest_hit.t)
-                    && (t_min.is_none() || hit_record.t >= t_min.unwrap())
-                    && (t_max.is_none() || hit_record.t <= t_max.unwrap())
-                {
-                    closest_hit = Some(curr_closest_hit);
-                    hit_obj = Some(obj_ref);
-                }
-            } else {
-                closest_hit = Some(hit_record);
-                hit_obj = Some(obj_ref);
-            }
+        if let Some(hit_record) = obj_ref.hittable.hit(ray, t_min, closest_t) {
+            closest_t = hit_record.t;
+            closest = Some((hit_record, obj_ref));
         }
     }
+    closest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::diffuse::Diffuse;
+    use crate::primitives::sphere::Sphere;
+    use crate::typedefs::*;
+
+    fn sphere(z: f) -> ObjRef<f> {
+        ObjRef {
+            hittable: Box::new(Sphere {
+                center: Vector3f::new(0.0, 0.0, z),
+                radius: 0.5,
+            }),
+            material: Box::new(Diffuse {
+                albedo: Vector3f::new(0.5, 0.5, 0.5),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_any_hit_closest() {
+        // the first object in the list is not the closest one
+        let list = vec![sphere(-5.0), sphere(-2.0), sphere(-8.0)];
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 0.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        let (hr, _) = any_hit(&list, &ray, Some(0.001), None).unwrap();
+        assert_eq!(hr.t, 1.5);
+    }
+
+    #[test]
+    fn test_any_hit_respects_range() {
+        let list = vec![sphere(2.0), sphere(-5.0)];
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 0.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+
+        // the sphere behind the origin must never be returned, even though it comes first
+        let (hr, _) = any_hit(&list, &ray, Some(0.001), None).unwrap();
+        assert_eq!(hr.t, 4.5);
+
+        // nothing is close enough
+        assert!(any_hit(&list, &ray, Some(0.001), Some(4.0)).is_none());
+    }
 
-    if let (Some(hit), Some(obj)) = (closest_hit, hit_obj) {
-        return Some((hit, obj));
+    #[test]
+    fn test_any_hit_from_surface() {
+        // a ray leaving the surface of a sphere from the inside should hit the far side rather
+        // than the point it started from
+        let list = vec![sphere(0.0)];
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, -0.5),
+            &Vector3f::new(0.0, 0.0, 1.0),
+        );
+        let (hr, _) = any_hit(&list, &ray, Some(0.001), None).unwrap();
+        assert_eq!(hr.t, 1.0);
     }
-    None
 }
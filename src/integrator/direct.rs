@@ -0,0 +1,339 @@
+use super::{russian_roulette, Integrator, EPSILON};
+use crate::accelerator::Accelerator;
+use crate::na::{RealField, Vector2, Vector3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::typedefs::*;
+
+/// A path tracer that samples the lights directly at every bounce (next event estimation)
+///
+/// At each surface that the path hits, a point is sampled on one of the lights, and its light is
+/// added if a shadow ray shows that nothing is in the way. The path then continues in the
+/// direction that the material scatters it. Light that the path finds by hitting an emissive
+/// object is only counted when it couldn't have been found by sampling the lights at the previous
+/// vertex, so that it isn't counted twice.
+///
+/// This converges much faster than `PathTracer` for scenes that are lit by small lights, which
+/// paths rarely hit by chance.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct DirectPathTracer {
+    /// The number of bounces after which paths can be ended by Russian roulette
+    pub min_depth: u,
+
+    /// The maximum number of bounces for each path
+    pub depth_limit: u,
+}
+
+impl<N: RealField> Integrator<N> for DirectPathTracer {
+    fn radiance(&self, scene: &Scene<N>, ray: &Ray<N>, sampler: &mut dyn Sampler) -> Vector3<N> {
+        let epsilon = N::from_f32(EPSILON).unwrap();
+        let mut radiance = Vector3::zeros();
+        let mut throughput = Vector3::repeat(N::one());
+        let mut ray = *ray;
+        // whether the lights were sampled at the vertex that the ray leaves from
+        let mut sampled_light = false;
+
+        for depth in 0.. {
+            let (hr, obj) = match scene.objects.any_hit(&ray, Some(epsilon), None) {
+                Some(hit) => hit,
+                None => {
+                    let mut escaped = scene.background.radiance(&ray.direction);
+
+                    if !sampled_light {
+                        escaped += scene.escaped_light(&ray.direction);
+                    }
+                    radiance += throughput.component_mul(&escaped);
+                    break;
+                }
+            };
+            let bsdf = &obj.material;
+
+            if !sampled_light || scene.light_pdf(obj, &ray.origin, &ray.direction) == N::zero() {
+                radiance += throughput.component_mul(&bsdf.emitted(&ray, &hr));
+            }
+
+            if depth >= self.depth_limit {
+                break;
+            }
+
+            // the light sample always uses the same dimensions, so that the dimensions of later
+            // bounces don't depend on the materials along the path
+            let u_light = N::from_f32(sampler.get_1d()).unwrap();
+            let u: Vector2<N> = sampler.get_2d().map(|x| N::from_f32(x).unwrap());
+
+            // delta materials only reflect light from a single direction, which a light sample
+            // can't find
+            if !bsdf.is_delta() {
+                if let Some(ls) = scene.sample_light(&hr.p, u_light, &u) {
+                    if ls.pdf > N::zero() && scene.unoccluded(&hr.p, &ls.direction, ls.distance) {
+                        let cos_theta = ls.direction.dot(&hr.shading_normal).abs();
+                        let f = bsdf.eval(&ray, &hr, &ls.direction);
+                        radiance += throughput.component_mul(&f).component_mul(&ls.radiance)
+                            * (cos_theta / ls.pdf);
+                    }
+                }
+            }
+
+            match bsdf.scatter(&ray, &hr, sampler) {
+                Some(record) => {
+                    throughput.component_mul_assign(&record.attenuated);
+                    ray = record.out_scattered;
+                    sampled_light = !bsdf.is_delta();
+                }
+                // the light was absorbed by the surface
+                None => break,
+            }
+
+            if depth >= self.min_depth {
+                let u = N::from_f32(sampler.get_1d()).unwrap();
+                match russian_roulette(&throughput, u) {
+                    Some(probability) => throughput /= probability,
+                    None => break,
+                }
+            }
+        }
+        radiance
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::background::Background;
+    use crate::hittable::{BSDFRef, ObjRef, ObjVec};
+    use crate::integrator::PathTracer;
+    use crate::light::Light;
+    use crate::material::diffuse::Diffuse;
+    use crate::material::emissive::Emissive;
+    use crate::material::mirror::Mirror;
+    use crate::primitives::sphere::Sphere;
+    use crate::primitives::triangle::Triangle;
+    use crate::sampler::Independent;
+    use std::f32::consts::PI;
+
+    /// An upward facing floor at y = 0 with the given material, and any other objects, lit by a
+    /// small spherical light above the origin in an otherwise black scene
+    pub fn lit_floor(material: BSDFRef<f>, mut objects: ObjVec<f>) -> Scene<f> {
+        objects.push(ObjRef {
+            hittable: Box::new(Triangle {
+                vertices: [
+                    Vector3f::new(-100.0, 0.0, 100.0),
+                    Vector3f::new(100.0, 0.0, 100.0),
+                    Vector3f::new(0.0, 0.0, -100.0),
+                ],
+            }),
+            material,
+        });
+        objects.push(ObjRef {
+            hittable: Box::new(Sphere {
+                center: Vector3f::new(0.0, 2.0, 0.0),
+                radius: 0.5,
+            }),
+            material: Box::new(Emissive {
+                emission: Color3f::new(4.0, 4.0, 4.0),
+            }),
+        });
+        Scene::new(objects, Background::Constant(Color3f::zeros()))
+    }
+
+    /// An upward facing diffuse floor at y = 0
+    pub fn floor() -> ObjRef<f> {
+        ObjRef {
+            hittable: Box::new(Triangle {
+                vertices: [
+                    Vector3f::new(-100.0, 0.0, 100.0),
+                    Vector3f::new(100.0, 0.0, 100.0),
+                    Vector3f::new(0.0, 0.0, -100.0),
+                ],
+            }),
+            material: diffuse(),
+        }
+    }
+
+    pub fn diffuse() -> BSDFRef<f> {
+        Box::new(Diffuse {
+            albedo: Color3f::new(0.5, 0.5, 0.5),
+        })
+    }
+
+    /// The mean radiance of `count` samples along `ray`
+    pub fn mean(
+        integrator: &dyn Integrator<f>,
+        scene: &Scene<f>,
+        ray: &Ray<f>,
+        count: usize,
+    ) -> Color3f {
+        let mut sampler = Independent::new(3);
+        let mut total = Color3f::zeros();
+
+        for i in 0..count {
+            sampler.start_sample((0, 0), i);
+            total += integrator.radiance(scene, ray, &mut sampler);
+        }
+        total / count as f
+    }
+
+    #[test]
+    fn test_direct_lighting() {
+        let scene = lit_floor(diffuse(), vec![]);
+        assert_eq!(scene.lights.len(), 1);
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+
+        // the irradiance from a sphere of radius r with radiance L, straight above a point at a
+        // distance h from its center, is pi * L * (r / h)^2, which a diffuse surface reflects
+        // with a factor of albedo / pi
+        let expected = 0.5 * 4.0 * (0.5 / 2.0) * (0.5 / 2.0);
+        let integrator = DirectPathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+        let radiance = mean(&integrator, &scene, &ray, 1000);
+        assert!(
+            (radiance.x - expected).abs() < 0.02 * expected,
+            "{}",
+            radiance
+        );
+
+        // the path tracer converges to the same value, with much more noise
+        let integrator = PathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+        let radiance = mean(&integrator, &scene, &ray, 20000);
+        assert!(
+            (radiance.x - expected).abs() < 0.1 * expected,
+            "{}",
+            radiance
+        );
+
+        // the light is still visible directly
+        let up = Ray::new(&Vector3f::new(0.0, 1.0, 0.0), &Vector3f::new(0.0, 1.0, 0.0));
+        let integrator = DirectPathTracer {
+            min_depth: 0,
+            depth_limit: 0,
+        };
+        let radiance = mean(&integrator, &scene, &up, 1);
+        assert_eq!(radiance, Color3f::new(4.0, 4.0, 4.0));
+    }
+
+    #[test]
+    fn test_back_face() {
+        // a floor that's wound the other way is hit from behind, and is lit just the same
+        let objects = vec![
+            ObjRef {
+                hittable: Box::new(Triangle {
+                    vertices: [
+                        Vector3f::new(100.0, 0.0, 100.0),
+                        Vector3f::new(-100.0, 0.0, 100.0),
+                        Vector3f::new(0.0, 0.0, -100.0),
+                    ],
+                }),
+                material: diffuse(),
+            },
+            ObjRef {
+                hittable: Box::new(Sphere {
+                    center: Vector3f::new(0.0, 2.0, 0.0),
+                    radius: 0.5,
+                }),
+                material: Box::new(Emissive {
+                    emission: Color3f::new(4.0, 4.0, 4.0),
+                }),
+            },
+        ];
+        let scene = Scene::new(objects, Background::Constant(Color3f::zeros()));
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let expected = 0.5 * 4.0 * (0.5 / 2.0) * (0.5 / 2.0);
+        let integrator = DirectPathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+        let radiance = mean(&integrator, &scene, &ray, 1000);
+        assert!(
+            (radiance.x - expected).abs() < 0.02 * expected,
+            "{}",
+            radiance
+        );
+    }
+
+    #[test]
+    fn test_shadow() {
+        // a diffuse triangle between the floor and the light blocks all of its light
+        let blocker = ObjRef {
+            hittable: Box::new(Triangle {
+                vertices: [
+                    Vector3f::new(-10.0, 1.0, 10.0),
+                    Vector3f::new(10.0, 1.0, 10.0),
+                    Vector3f::new(0.0, 1.0, -10.0),
+                ],
+            }),
+            material: diffuse(),
+        };
+        let scene = lit_floor(diffuse(), vec![blocker]);
+
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.5, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let integrator = DirectPathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+        let radiance = mean(&integrator, &scene, &ray, 100);
+        assert_eq!(radiance, Color3f::zeros());
+    }
+
+    #[test]
+    fn test_sun() {
+        // the sun is found both by light samples and by paths that escape the scene, but it's
+        // only counted once
+        let sun = Light::Directional {
+            direction: Vector3f::new(0.0, -1.0, 0.0),
+            irradiance: Color3f::new(1.0, 1.0, 1.0),
+            cos_max: 0.9,
+        };
+        let scene = Scene::new(vec![floor()], Background::Constant(Color3f::zeros()))
+            .with_lights(vec![sun]);
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let integrator = DirectPathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+        let expected = 0.5 / PI;
+        let radiance = mean(&integrator, &scene, &ray, 1000);
+        assert!(
+            (radiance.x - expected).abs() < 0.02 * expected,
+            "{}",
+            radiance
+        );
+    }
+
+    #[test]
+    fn test_mirror() {
+        // a mirror can't use light samples, so the light is found by the reflected ray
+        let mirror = Box::new(Mirror {
+            albedo: Color3f::new(0.5, 0.5, 0.5),
+            fuzziness: 0.0,
+        });
+        let scene = lit_floor(mirror, vec![]);
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let integrator = DirectPathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+        let radiance = mean(&integrator, &scene, &ray, 10);
+        assert_eq!(radiance, Color3f::new(2.0, 2.0, 2.0));
+    }
+}
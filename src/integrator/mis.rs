@@ -0,0 +1,276 @@
+use super::{russian_roulette, Integrator, EPSILON};
+use crate::accelerator::Accelerator;
+use crate::na::{RealField, Vector2, Vector3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::typedefs::*;
+
+/// A path tracer that combines light sampling and BSDF sampling with multiple importance
+/// sampling
+///
+/// At each surface that the path hits, light arrives from both a sampled point on a light and
+/// from the direction that the BSDF scatters the path in. Each of those samples is weighted by
+/// the power heuristic, based on how likely either strategy was to pick its direction. This keeps
+/// the low noise of light sampling for small lights, without the noise that it has for large
+/// lights on glossy surfaces, where sampling the BSDF works better.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct MISPathTracer {
+    /// The number of bounces after which paths can be ended by Russian roulette
+    pub min_depth: u,
+
+    /// The maximum number of bounces for each path
+    pub depth_limit: u,
+}
+
+/// The weight of a sample from a strategy with density `pdf`, when another strategy could have
+/// also sampled it with density `other_pdf`. This is Veach's power heuristic, with an exponent of
+/// two.
+pub fn power_heuristic<N: RealField>(pdf: N, other_pdf: N) -> N {
+    if pdf > N::max_value() {
+        return N::one();
+    }
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+
+    if a + b == N::zero() {
+        return N::zero();
+    }
+    a / (a + b)
+}
+
+impl<N: RealField> Integrator<N> for MISPathTracer {
+    fn radiance(&self, scene: &Scene<N>, ray: &Ray<N>, sampler: &mut dyn Sampler) -> Vector3<N> {
+        let epsilon = N::from_f32(EPSILON).unwrap();
+        let mut radiance = Vector3::zeros();
+        let mut throughput = Vector3::repeat(N::one());
+        let mut ray = *ray;
+        // the density of the BSDF sample that the ray came from, or `None` if the lights
+        // couldn't have been sampled at the vertex that the ray leaves from
+        let mut bsdf_pdf: Option<N> = None;
+
+        for depth in 0.. {
+            let (hr, obj) = match scene.objects.any_hit(&ray, Some(epsilon), None) {
+                Some(hit) => hit,
+                None => {
+                    let weight = match bsdf_pdf {
+                        Some(pdf) => power_heuristic(pdf, scene.escaped_light_pdf(&ray.direction)),
+                        None => N::one(),
+                    };
+                    let escaped = scene.background.radiance(&ray.direction)
+                        + scene.escaped_light(&ray.direction) * weight;
+                    radiance += throughput.component_mul(&escaped);
+                    break;
+                }
+            };
+            let bsdf = &obj.material;
+            let emitted = bsdf.emitted(&ray, &hr);
+            let weight = match bsdf_pdf {
+                Some(pdf) => {
+                    power_heuristic(pdf, scene.light_pdf(obj, &ray.origin, &ray.direction))
+                }
+                None => N::one(),
+            };
+            radiance += throughput.component_mul(&emitted) * weight;
+
+            if depth >= self.depth_limit {
+                break;
+            }
+
+            // the light sample always uses the same dimensions, so that the dimensions of later
+            // bounces don't depend on the materials along the path
+            let u_light = N::from_f32(sampler.get_1d()).unwrap();
+            let u: Vector2<N> = sampler.get_2d().map(|x| N::from_f32(x).unwrap());
+
+            if !bsdf.is_delta() {
+                if let Some(ls) = scene.sample_light(&hr.p, u_light, &u) {
+                    if ls.pdf > N::zero() && scene.unoccluded(&hr.p, &ls.direction, ls.distance) {
+                        let cos_theta = ls.direction.dot(&hr.shading_normal).abs();
+                        let f = bsdf.eval(&ray, &hr, &ls.direction);
+                        // delta lights can't be found by sampling the BSDF
+                        let weight = if ls.delta {
+                            N::one()
+                        } else {
+                            power_heuristic(ls.pdf, bsdf.pdf(&ray, &hr, &ls.direction))
+                        };
+                        radiance += throughput.component_mul(&f).component_mul(&ls.radiance)
+                            * (cos_theta * weight / ls.pdf);
+                    }
+                }
+            }
+
+            match bsdf.sample(&ray, &hr, sampler) {
+                Some(sample) => {
+                    throughput.component_mul_assign(&sample.record.attenuated);
+                    ray = sample.record.out_scattered;
+                    ray.direction = ray.direction.normalize();
+                    bsdf_pdf = if bsdf.is_delta() {
+                        None
+                    } else {
+                        Some(sample.pdf)
+                    };
+                }
+                // the light was absorbed by the surface
+                None => break,
+            }
+
+            if depth >= self.min_depth {
+                let u = N::from_f32(sampler.get_1d()).unwrap();
+                match russian_roulette(&throughput, u) {
+                    Some(probability) => throughput /= probability,
+                    None => break,
+                }
+            }
+        }
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::background::Background;
+    use crate::environment::EnvironmentMap;
+    use crate::hittable::{ObjRef, ObjVec};
+    use crate::integrator::direct::test::{diffuse, floor, lit_floor, mean};
+    use crate::light::Light;
+    use crate::material::emissive::Emissive;
+    use crate::material::mirror::Mirror;
+    use crate::primitives::sphere::Sphere;
+    use crate::raster::Raster;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_power_heuristic() {
+        assert_eq!(power_heuristic(1.0, 1.0), 0.5);
+        assert_eq!(power_heuristic(3.0, 1.0), 0.9);
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+        assert_eq!(power_heuristic(f::INFINITY, 1.0), 1.0);
+        assert!(
+            (power_heuristic::<f>(2.0, 5.0) + power_heuristic::<f>(5.0, 2.0) - 1.0).abs() < 1e-6
+        );
+    }
+
+    #[test]
+    fn test_direct_lighting() {
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let integrator = MISPathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+
+        // the light is small, which light sampling handles well
+        let scene = lit_floor(diffuse(), vec![]);
+        let expected = 0.5 * 4.0 * (0.5 / 2.0) * (0.5 / 2.0);
+        let radiance = mean(&integrator, &scene, &ray, 1000);
+        assert!(
+            (radiance.x - expected).abs() < 0.02 * expected,
+            "{}",
+            radiance
+        );
+
+        // the light covers most of the hemisphere, which BSDF sampling handles well
+        let light = ObjRef {
+            hittable: Box::new(Sphere {
+                center: Vector3f::new(0.0, 3.0, 0.0),
+                radius: 2.5,
+            }),
+            material: Box::new(Emissive {
+                emission: Color3f::new(4.0, 4.0, 4.0),
+            }),
+        };
+        let objects: ObjVec<f> = vec![floor(), light];
+        let scene = Scene::new(objects, Background::Constant(Color3f::zeros()));
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.25, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let expected = 0.5 * 4.0 * (2.5 / 3.0) * (2.5 / 3.0);
+        let radiance = mean(&integrator, &scene, &ray, 1000);
+        assert!(
+            (radiance.x - expected).abs() < 0.02 * expected,
+            "{}",
+            radiance
+        );
+    }
+
+    #[test]
+    fn test_analytic_lights() {
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let integrator = MISPathTracer {
+            min_depth: 1,
+            depth_limit: 1,
+        };
+        // the floor reflects albedo / pi of an irradiance of 1 from each light
+        let expected = 0.5 / PI;
+        let mut sky = Raster::new(8, 4);
+        sky.pixels = vec![Color3f::new(1.0, 1.0, 1.0) / PI; 8 * 4];
+        let lights = [
+            Light::Point {
+                position: Vector3f::new(0.0, 2.0, 0.0),
+                intensity: Color3f::new(4.0, 4.0, 4.0),
+            },
+            Light::Spot {
+                position: Vector3f::new(0.0, 2.0, 0.0),
+                direction: Vector3f::new(0.0, -1.0, 0.0),
+                intensity: Color3f::new(4.0, 4.0, 4.0),
+                cos_angle: 0.5,
+                cos_falloff: 0.8,
+            },
+            Light::Directional {
+                direction: Vector3f::new(0.0, -1.0, 0.0),
+                irradiance: Color3f::new(1.0, 1.0, 1.0),
+                cos_max: 1.0,
+            },
+            Light::Directional {
+                direction: Vector3f::new(0.0, -1.0, 0.0),
+                irradiance: Color3f::new(1.0, 1.0, 1.0),
+                cos_max: 0.9,
+            },
+            Light::Environment(Box::new(EnvironmentMap::new(sky, 0.0, 1.0))),
+        ];
+
+        for light in &lights {
+            let objects: ObjVec<f> = vec![floor()];
+            let scene = Scene::new(objects, Background::Constant(Color3f::zeros()))
+                .with_lights(vec![light.clone()]);
+            let radiance = mean(&integrator, &scene, &ray, 1000);
+            assert!(
+                (radiance.x - expected).abs() < 0.02 * expected,
+                "{:?} {}",
+                light,
+                radiance
+            );
+        }
+    }
+
+    #[test]
+    fn test_mirror() {
+        // a mirror can't use light samples, so all of the light is found by the reflected ray
+        let mirror = Box::new(Mirror {
+            albedo: Color3f::new(0.5, 0.5, 0.5),
+            fuzziness: 0.0,
+        });
+        let scene = lit_floor(mirror, vec![]);
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let radiance = mean(
+            &MISPathTracer {
+                min_depth: 1,
+                depth_limit: 1,
+            },
+            &scene,
+            &ray,
+            10,
+        );
+        assert_eq!(radiance, Color3f::new(2.0, 2.0, 2.0));
+    }
+}
@@ -0,0 +1,145 @@
+//! The integrator module defines a trait for light transport algorithms, which compute the
+//! radiance that arrives along a ray, along with implementations of those algorithms.
+
+use crate::na::{RealField, Vector3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::typedefs::*;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod direct;
+pub mod mis;
+pub mod path;
+
+pub use direct::DirectPathTracer;
+pub use mis::MISPathTracer;
+pub use path::PathTracer;
+
+/// The minimum distance along a ray that an intersection can be at. This keeps rays that leave a
+/// surface from hitting that same surface due to floating point error.
+pub const EPSILON: f = 0.001;
+
+/// Decide whether a path continues with Russian roulette, where `throughput` is the fraction of
+/// the light at the end of the path that reaches the camera, and `u` is a uniformly distributed
+/// value.
+///
+/// The path continues with a probability of the largest component of its throughput, so paths
+/// that can't carry much light are likely to be ended. Returns the probability if the path
+/// continues, which the throughput has to be divided by to keep the estimate unbiased, or `None`
+/// if the path ends.
+pub fn russian_roulette<N: RealField>(throughput: &Vector3<N>, u: N) -> Option<N> {
+    let probability = throughput
+        .x
+        .max(throughput.y)
+        .max(throughput.z)
+        .min(N::one());
+
+    if u < probability {
+        Some(probability)
+    } else {
+        None
+    }
+}
+
+/// The `Integrator` trait should be implemented for any algorithm that solves the rendering
+/// equation.
+pub trait Integrator<N: RealField> {
+    /// Calculate the radiance that arrives at the origin of `ray`, travelling in the opposite
+    /// direction of the ray. `sampler` provides the values for the random choices of the
+    /// estimate, and has already been started on the sample that the ray belongs to.
+    fn radiance(&self, scene: &Scene<N>, ray: &Ray<N>, sampler: &mut dyn Sampler) -> Vector3<N>;
+}
+
+/// An owned reference to an integrator trait object that is also `Sync`
+pub type IntegratorRef<N> = Box<dyn Integrator<N> + Sync>;
+
+/// The integrators that are built into the crate, which can be selected by name
+#[derive(Clone, Debug, Default, PartialEq, Copy)]
+pub enum IntegratorKind {
+    /// The path tracer from "Ray Tracing in One Weekend" (see `PathTracer`)
+    #[default]
+    Path,
+
+    /// A path tracer that samples the lights at every bounce (see `DirectPathTracer`)
+    Direct,
+
+    /// A path tracer that combines light and BSDF sampling (see `MISPathTracer`)
+    Mis,
+}
+
+impl IntegratorKind {
+    /// Create an integrator of this kind, which follows paths for at most `depth_limit` bounces,
+    /// and uses Russian roulette after `min_depth` bounces
+    pub fn build<N: RealField>(&self, min_depth: u, depth_limit: u) -> IntegratorRef<N> {
+        match self {
+            IntegratorKind::Path => Box::new(PathTracer {
+                min_depth,
+                depth_limit,
+            }),
+            IntegratorKind::Direct => Box::new(DirectPathTracer {
+                min_depth,
+                depth_limit,
+            }),
+            IntegratorKind::Mis => Box::new(MISPathTracer {
+                min_depth,
+                depth_limit,
+            }),
+        }
+    }
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(IntegratorKind::Path),
+            "direct" => Ok(IntegratorKind::Direct),
+            "mis" => Ok(IntegratorKind::Mis),
+            _ => Err(format!("unknown integrator \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for IntegratorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntegratorKind::Path => write!(f, "path"),
+            IntegratorKind::Direct => write!(f, "direct"),
+            IntegratorKind::Mis => write!(f, "mis"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!("path".parse(), Ok(IntegratorKind::Path));
+        assert_eq!(IntegratorKind::Path.to_string(), "path");
+        assert_eq!("direct".parse(), Ok(IntegratorKind::Direct));
+        assert_eq!(IntegratorKind::Direct.to_string(), "direct");
+        assert_eq!("mis".parse(), Ok(IntegratorKind::Mis));
+        assert_eq!(IntegratorKind::Mis.to_string(), "mis");
+        assert!("nope".parse::<IntegratorKind>().is_err());
+    }
+
+    #[test]
+    fn test_russian_roulette() {
+        // bright paths always continue
+        let bright = Color3f::new(0.5, 2.0, 0.0);
+        assert_eq!(russian_roulette(&bright, 0.99), Some(1.0));
+
+        // dim paths continue with the probability of their largest component
+        let dim = Color3f::new(0.1, 0.25, 0.0);
+        assert_eq!(russian_roulette(&dim, 0.2), Some(0.25));
+        assert_eq!(russian_roulette(&dim, 0.3), None);
+
+        // paths that can't carry any light always end
+        assert_eq!(russian_roulette(&Color3f::zeros(), 0.0), None);
+    }
+}
@@ -0,0 +1,220 @@
+use super::{russian_roulette, Integrator, EPSILON};
+use crate::accelerator::Accelerator;
+use crate::na::{RealField, Vector3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::typedefs::*;
+
+/// A path tracer, as described in "Ray Tracing in One Weekend"
+///
+/// Paths are extended by recursively scattering rays off of the surfaces that they hit, until
+/// they escape the scene, get absorbed, or reach the depth limit. Any light that is emitted by the
+/// surfaces along the path is added to the result. After the minimum depth, paths are also ended
+/// at random by Russian roulette.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct PathTracer {
+    /// The number of bounces after which paths can be ended by Russian roulette
+    pub min_depth: u,
+
+    /// The maximum number of bounces for each path
+    pub depth_limit: u,
+}
+
+impl PathTracer {
+    /// The radiance along a ray that is `depth` bounces into the path, where `throughput` is the
+    /// fraction of that radiance which reaches the camera
+    fn trace<N: RealField>(
+        &self,
+        scene: &Scene<N>,
+        ray: &Ray<N>,
+        depth: u,
+        throughput: &Vector3<N>,
+        sampler: &mut dyn Sampler,
+    ) -> Vector3<N> {
+        let epsilon = N::from_f32(EPSILON).unwrap();
+        let (hr, obj) = match scene.objects.any_hit(ray, Some(epsilon), None) {
+            Some(hit) => hit,
+            None => {
+                return scene.background.radiance(&ray.direction)
+                    + scene.escaped_light(&ray.direction)
+            }
+        };
+        let bsdf = &obj.material;
+        let emitted = bsdf.emitted(ray, &hr);
+
+        if depth >= self.depth_limit {
+            return emitted;
+        }
+
+        match bsdf.scatter(ray, &hr, sampler) {
+            Some(record) => {
+                let mut attenuated = record.attenuated;
+
+                if depth >= self.min_depth {
+                    let throughput = throughput.component_mul(&attenuated);
+                    let u = N::from_f32(sampler.get_1d()).unwrap();
+                    match russian_roulette(&throughput, u) {
+                        Some(probability) => attenuated /= probability,
+                        None => return emitted,
+                    }
+                }
+                let throughput = throughput.component_mul(&attenuated);
+                let incoming = self.trace(
+                    scene,
+                    &record.out_scattered,
+                    depth + 1,
+                    &throughput,
+                    sampler,
+                );
+                emitted + incoming.component_mul(&attenuated)
+            }
+            // the light was absorbed by the surface
+            None => emitted,
+        }
+    }
+}
+
+impl<N: RealField> Integrator<N> for PathTracer {
+    fn radiance(&self, scene: &Scene<N>, ray: &Ray<N>, sampler: &mut dyn Sampler) -> Vector3<N> {
+        self.trace(scene, ray, 0, &Vector3::repeat(N::one()), sampler)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::background::Background;
+    use crate::hittable::{ObjRef, ObjVec};
+    use crate::material::diffuse::Diffuse;
+    use crate::material::emissive::Emissive;
+    use crate::primitives::sphere::Sphere;
+    use crate::primitives::triangle::Triangle;
+    use crate::sampler::Independent;
+
+    /// A large upward facing triangle at y = 0 under a white sky
+    fn floor_scene() -> Scene<f> {
+        let objects: ObjVec<f> = vec![ObjRef {
+            hittable: Box::new(Triangle {
+                vertices: [
+                    Vector3f::new(-100.0, 0.0, 100.0),
+                    Vector3f::new(100.0, 0.0, 100.0),
+                    Vector3f::new(0.0, 0.0, -100.0),
+                ],
+            }),
+            material: Box::new(Diffuse {
+                albedo: Color3f::new(0.5, 0.25, 0.0),
+            }),
+        }];
+        Scene::new(objects, Background::Constant(Color3f::new(1.0, 1.0, 1.0)))
+    }
+
+    #[test]
+    fn test_background() {
+        let scene = floor_scene();
+        let integrator = PathTracer {
+            min_depth: 5,
+            depth_limit: 5,
+        };
+        let ray = Ray::new(&Vector3f::new(0.0, 1.0, 0.0), &Vector3f::new(0.0, 1.0, 0.0));
+        let radiance = integrator.radiance(&scene, &ray, &mut Independent::new(0));
+        assert_eq!(radiance, Color3f::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_single_bounce() {
+        // every ray that bounces off of the floor escapes to the sky
+        let scene = floor_scene();
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let integrator = PathTracer {
+            min_depth: 5,
+            depth_limit: 5,
+        };
+
+        let mut sampler = Independent::new(0);
+
+        for i in 0..10 {
+            sampler.start_sample((0, 0), i);
+            let radiance = integrator.radiance(&scene, &ray, &mut sampler);
+            assert_eq!(radiance, Color3f::new(0.5, 0.25, 0.0));
+        }
+
+        // without any bounces, the floor is black
+        let integrator = PathTracer {
+            min_depth: 0,
+            depth_limit: 0,
+        };
+        let radiance = integrator.radiance(&scene, &ray, &mut Independent::new(0));
+        assert_eq!(radiance, Color3f::zeros());
+    }
+
+    #[test]
+    fn test_russian_roulette() {
+        // the throughput after the floor is at most 0.5, so half of the paths are ended there, and
+        // the rest carry twice as much light
+        let scene = floor_scene();
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let integrator = PathTracer {
+            min_depth: 0,
+            depth_limit: 5,
+        };
+        let mut sampler = Independent::new(0);
+        let trials = 10000;
+        let mut ended = 0;
+        let mut mean = Color3f::zeros();
+
+        for i in 0..trials {
+            sampler.start_sample((0, 0), i);
+            let radiance = integrator.radiance(&scene, &ray, &mut sampler);
+
+            if radiance == Color3f::zeros() {
+                ended += 1;
+            } else {
+                assert_eq!(radiance, Color3f::new(1.0, 0.5, 0.0));
+            }
+            mean += radiance / trials as f;
+        }
+
+        // the estimate is still unbiased
+        assert!((ended as f / trials as f - 0.5).abs() < 0.02, "{}", ended);
+        assert!(
+            (mean - Color3f::new(0.5, 0.25, 0.0)).norm() < 0.02,
+            "{}",
+            mean
+        );
+    }
+
+    #[test]
+    fn test_emission() {
+        let objects: ObjVec<f> = vec![ObjRef {
+            hittable: Box::new(Sphere {
+                center: Vector3f::new(0.0, 0.0, -2.0),
+                radius: 1.0,
+            }),
+            material: Box::new(Emissive {
+                emission: Color3f::new(2.0, 3.0, 4.0),
+            }),
+        }];
+        let scene = Scene::new(objects, Background::Constant(Color3f::zeros()));
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 0.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+
+        // lights are visible even when there are no bounces left
+        for &depth_limit in &[0, 5] {
+            let integrator = PathTracer {
+                min_depth: depth_limit,
+                depth_limit,
+            };
+            let radiance = integrator.radiance(&scene, &ray, &mut Independent::new(0));
+            assert_eq!(radiance, Color3f::new(2.0, 3.0, 4.0));
+        }
+    }
+}
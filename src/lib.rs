@@ -1,11 +1,28 @@
 extern crate nalgebra as na;
 
+pub mod aabb;
+pub mod accelerator;
+pub mod background;
 pub mod camera;
 pub mod common;
+pub mod distribution;
+pub mod environment;
+pub mod exr;
+pub mod film;
+pub mod filter;
+pub mod hdr;
 pub mod hittable;
+pub mod integrator;
+pub mod light;
 pub mod material;
+pub mod obj;
+pub mod pfm;
 pub mod primitives;
+pub mod raster;
 pub mod ray;
+pub mod renderer;
 pub mod sample;
+pub mod sampler;
 pub mod scene;
+pub mod tonemap;
 pub mod typedefs;
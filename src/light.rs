@@ -0,0 +1,437 @@
+//! Lights are the parts of a scene that can be sampled directly, rather than only being found by
+//! rays that happen to hit them. Sampling lights explicitly (next event estimation) is what makes
+//! small light sources practical to render.
+//!
+//! Besides objects with emissive materials, there are analytic lights that aren't part of the
+//! geometry. Point, spot and directional lights without an angular size can't be hit by rays at
+//! all, so they only contribute light through light sampling.
+
+use crate::common::coordinate_system;
+use crate::environment::EnvironmentMap;
+use crate::hittable::{HitRecord, ObjVec};
+use crate::na::{RealField, Vector2, Vector3};
+use crate::ray::Ray;
+use crate::sample::{uniform_cone, uniform_cone_pdf};
+
+/// A direction towards a light that was sampled from some reference point, along with the light
+/// that arrives from it
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct LightSample<N: RealField> {
+    /// The unit direction from the reference point to the light
+    pub direction: Vector3<N>,
+
+    /// The distance from the reference point to the sampled point on the light, which is as far
+    /// as a shadow ray needs to check for anything in the way
+    pub distance: N,
+
+    /// The radiance that the light emits towards the reference point
+    pub radiance: Vector3<N>,
+
+    /// The probability density of sampling the direction, with respect to solid angle. For delta
+    /// lights, this is the probability of picking the light's only direction.
+    pub pdf: N,
+
+    /// Whether the light only arrives from this one direction, like the light from a point
+    /// light, so that it can't be found in any way other than sampling the light
+    pub delta: bool,
+}
+
+/// A light in a scene
+#[derive(Clone, Debug, PartialEq)]
+pub enum Light<N: RealField> {
+    /// An object with an emissive material. This is the index of the object in the list of
+    /// objects that the scene was built with, which must be able to sample its surface.
+    Area { object: usize },
+
+    /// A point that emits the same intensity in every direction
+    Point {
+        position: Vector3<N>,
+
+        /// The radiant intensity, which is the power per unit of solid angle
+        intensity: Vector3<N>,
+    },
+
+    /// A point that emits light in a cone, which fades out towards the edge of the cone
+    Spot {
+        position: Vector3<N>,
+
+        /// The unit direction of the center of the cone
+        direction: Vector3<N>,
+
+        /// The radiant intensity along the center of the cone
+        intensity: Vector3<N>,
+
+        /// The cosine of the angle between the center of the cone and its edge
+        cos_angle: N,
+
+        /// The cosine of the angle at which the light starts to fade out. The light fades out
+        /// smoothly between this angle and the edge of the cone.
+        cos_falloff: N,
+    },
+
+    /// A light that is infinitely far away, such as the sun, which arrives from the same
+    /// direction everywhere in the scene
+    Directional {
+        /// The unit direction that the light travels in
+        direction: Vector3<N>,
+
+        /// The irradiance on a surface that faces the light
+        irradiance: Vector3<N>,
+
+        /// The cosine of the angular radius of the light. The light is a uniformly bright disk
+        /// in the sky, unless this is 1, in which case all of the light comes from one direction.
+        cos_max: N,
+    },
+
+    /// An image that surrounds the scene, which lights it from every direction
+    Environment(Box<EnvironmentMap<N>>),
+}
+
+/// A smooth step from 0 where `x` is `start`, to 1 where it's `end`
+fn smooth_step<N: RealField>(x: N, start: N, end: N) -> N {
+    if start == end {
+        return if x < start { N::zero() } else { N::one() };
+    }
+    let t = num::clamp((x - start) / (end - start), N::zero(), N::one());
+    t * t * (N::from_u32(3).unwrap() - (t + t))
+}
+
+/// The distance to a light that is infinitely far away
+fn infinity<N: RealField>() -> N {
+    N::from_f32(f32::INFINITY).unwrap()
+}
+
+impl<N: RealField> Light<N> {
+    /// Sample a direction from `reference` towards the light, where `u` is a uniformly
+    /// distributed point in the unit square. `objects` is the list of objects that the light
+    /// refers to. Returns `None` if no light can reach the reference point from the sample.
+    pub fn sample(
+        &self,
+        objects: &ObjVec<N>,
+        reference: &Vector3<N>,
+        u: &Vector2<N>,
+    ) -> Option<LightSample<N>> {
+        match *self {
+            Light::Area { object } => {
+                let obj = &objects[object];
+                let sample = obj.hittable.sample(reference, u)?;
+                let offset = sample.p - reference;
+                let distance = offset.norm();
+
+                if sample.pdf <= N::zero() || distance == N::zero() {
+                    return None;
+                }
+                let direction = offset / distance;
+
+                // the emitted radiance is found as though a ray from the reference point had hit
+                // the sampled point
+                let hit_record = HitRecord {
+                    t: distance,
+                    p: sample.p,
+                    normal: sample.normal,
+                    shading_normal: sample.normal,
+                    uv: Vector2::zeros(),
+                    front_face: true,
+                }
+                .face_forward(&direction);
+                let ray = Ray {
+                    origin: *reference,
+                    direction,
+                };
+
+                Some(LightSample {
+                    direction,
+                    distance,
+                    radiance: obj.material.emitted(&ray, &hit_record),
+                    pdf: sample.pdf,
+                    delta: false,
+                })
+            }
+            Light::Point {
+                position,
+                intensity,
+            } => Self::sample_point(&position, intensity, reference),
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                cos_angle,
+                cos_falloff,
+            } => {
+                let mut sample = Self::sample_point(&position, intensity, reference)?;
+                let cos_theta = -sample.direction.dot(&direction);
+                let falloff = smooth_step(cos_theta, cos_angle, cos_falloff);
+
+                if falloff <= N::zero() {
+                    return None;
+                }
+                sample.radiance *= falloff;
+                Some(sample)
+            }
+            Light::Directional {
+                direction,
+                irradiance,
+                cos_max,
+            } => {
+                if cos_max >= N::one() {
+                    return Some(LightSample {
+                        direction: -direction,
+                        distance: infinity(),
+                        radiance: irradiance,
+                        pdf: N::one(),
+                        delta: true,
+                    });
+                }
+                let local = uniform_cone(u, cos_max);
+                let (s, t) = coordinate_system(&-direction);
+                Some(LightSample {
+                    direction: (s * local.x + t * local.y - direction * local.z).normalize(),
+                    distance: infinity(),
+                    radiance: self.emitted(&-direction),
+                    pdf: uniform_cone_pdf(cos_max),
+                    delta: false,
+                })
+            }
+            Light::Environment(ref map) => {
+                let (direction, radiance, pdf) = map.sample(u)?;
+                Some(LightSample {
+                    direction,
+                    distance: infinity(),
+                    radiance,
+                    pdf,
+                    delta: false,
+                })
+            }
+        }
+    }
+
+    /// The light from a point that emits `intensity` towards `reference`
+    fn sample_point(
+        position: &Vector3<N>,
+        intensity: Vector3<N>,
+        reference: &Vector3<N>,
+    ) -> Option<LightSample<N>> {
+        let offset = position - reference;
+        let distance = offset.norm();
+
+        if distance == N::zero() {
+            return None;
+        }
+        Some(LightSample {
+            direction: offset / distance,
+            distance,
+            radiance: intensity / (distance * distance),
+            pdf: N::one(),
+            delta: true,
+        })
+    }
+
+    /// The density that `sample` has for `direction`, as seen from `reference`. Delta lights
+    /// have a density of zero, since there's no chance of an arbitrary direction being theirs.
+    pub fn pdf(&self, objects: &ObjVec<N>, reference: &Vector3<N>, direction: &Vector3<N>) -> N {
+        match *self {
+            Light::Area { object } => objects[object].hittable.pdf(reference, direction),
+            Light::Directional { cos_max, .. } if cos_max < N::one() => {
+                if self.emitted(direction) == Vector3::zeros() {
+                    return N::zero();
+                }
+                uniform_cone_pdf(cos_max)
+            }
+            Light::Environment(ref map) => map.pdf(direction),
+            _ => N::zero(),
+        }
+    }
+
+    /// Whether the light is infinitely far away, so that it's only reached by rays that escape
+    /// the scene
+    pub fn is_infinite(&self) -> bool {
+        matches!(self, Light::Directional { .. } | Light::Environment(_))
+    }
+
+    /// The radiance that arrives from the light along a ray that escapes the scene in
+    /// `direction`. This is zero for lights that aren't infinitely far away, and for delta
+    /// lights.
+    pub fn emitted(&self, direction: &Vector3<N>) -> Vector3<N> {
+        match *self {
+            Light::Directional {
+                direction: light_direction,
+                irradiance,
+                cos_max,
+            } if cos_max < N::one() => {
+                if -direction.normalize().dot(&light_direction) < cos_max {
+                    return Vector3::zeros();
+                }
+                // the irradiance from a uniformly bright disk is its radiance times the projected
+                // solid angle that it covers, which is pi * sin^2 of its angular radius
+                irradiance / (N::pi() * (N::one() - cos_max * cos_max))
+            }
+            Light::Environment(ref map) => map.radiance(direction),
+            _ => Vector3::zeros(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittable::ObjRef;
+    use crate::material::emissive::Emissive;
+    use crate::primitives::triangle::Triangle;
+    use crate::sampler::{Independent, Sampler};
+    use crate::typedefs::*;
+
+    #[test]
+    fn test_area_light() {
+        // a 2x2 triangle on the plane z = -2, with an area of 2
+        let objects: ObjVec<f> = vec![ObjRef {
+            hittable: Box::new(Triangle {
+                vertices: [
+                    Vector3f::new(-1.0, -1.0, -2.0),
+                    Vector3f::new(1.0, -1.0, -2.0),
+                    Vector3f::new(-1.0, 1.0, -2.0),
+                ],
+            }),
+            material: Box::new(Emissive {
+                emission: Color3f::new(1.0, 2.0, 3.0),
+            }),
+        }];
+        let light = Light::Area { object: 0 };
+        let reference = Vector3f::zeros();
+        let mut sampler = Independent::new(0);
+        let trials = 10000;
+        let mut solid_angle = 0.0;
+
+        for i in 0..trials {
+            sampler.start_sample((0, 0), i);
+            let sample = light
+                .sample(&objects, &reference, &sampler.get_2d())
+                .unwrap();
+            let p = reference + sample.direction * sample.distance;
+            assert!((p.z + 2.0).abs() < 1e-5);
+            assert!(p.x + p.y <= 1e-5);
+            assert_eq!(sample.radiance, Color3f::new(1.0, 2.0, 3.0));
+
+            let pdf = light.pdf(&objects, &reference, &sample.direction);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-3 * pdf,
+                "{} {}",
+                pdf,
+                sample.pdf
+            );
+            solid_angle += 1.0 / sample.pdf / trials as f;
+        }
+
+        // the solid angle of the triangle is half that of the square around it, whose solid angle
+        // is 4 * asin(sin(a) * sin(b)) for the angles a and b that it spans along each axis
+        let half = (0.5 as f).atan().sin();
+        let expected = 2.0 * (half * half).asin();
+        assert!(
+            (solid_angle - expected).abs() < 0.01 * expected,
+            "{}",
+            solid_angle
+        );
+
+        // directions that miss the light can't be sampled
+        let away = Vector3f::new(0.0, 0.0, 1.0);
+        assert_eq!(light.pdf(&objects, &reference, &away), 0.0);
+    }
+
+    #[test]
+    fn test_point_light() {
+        let light = Light::Point {
+            position: Vector3f::new(0.0, 2.0, 0.0),
+            intensity: Color3f::new(4.0, 8.0, 12.0),
+        };
+        let sample = light
+            .sample(&Vec::new(), &Vector3f::zeros(), &Vector2f::zeros())
+            .unwrap();
+
+        // the light falls off with the square of the distance
+        assert_eq!(sample.direction, Vector3f::new(0.0, 1.0, 0.0));
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.radiance, Color3f::new(1.0, 2.0, 3.0));
+        assert_eq!(sample.pdf, 1.0);
+        assert!(sample.delta);
+        assert_eq!(
+            light.pdf(&Vec::new(), &Vector3f::zeros(), &sample.direction),
+            0.0
+        );
+        assert_eq!(light.emitted(&sample.direction), Color3f::zeros());
+    }
+
+    #[test]
+    fn test_spot_light() {
+        // a cone of 60 degrees, which fades out from 30 degrees
+        let light = Light::Spot {
+            position: Vector3f::zeros(),
+            direction: Vector3f::new(0.0, -1.0, 0.0),
+            intensity: Color3f::new(1.0, 1.0, 1.0),
+            cos_angle: (60.0 as f).to_radians().cos(),
+            cos_falloff: (30.0 as f).to_radians().cos(),
+        };
+        let radiance = |angle: f| {
+            let angle = angle.to_radians();
+            let reference = Vector3f::new(angle.sin(), -angle.cos(), 0.0);
+            light
+                .sample(&Vec::new(), &reference, &Vector2f::zeros())
+                .map(|sample| sample.radiance.x)
+        };
+
+        assert_eq!(radiance(0.0), Some(1.0));
+        assert_eq!(radiance(29.0), Some(1.0));
+        let middle = radiance(45.0).unwrap();
+        assert!(middle > 0.0 && middle < 1.0, "{}", middle);
+        assert!(radiance(55.0).unwrap() < middle);
+        assert_eq!(radiance(61.0), None);
+        assert_eq!(radiance(120.0), None);
+    }
+
+    #[test]
+    fn test_directional_light() {
+        let direction = Vector3f::new(1.0, -1.0, 0.0).normalize();
+        let irradiance = Color3f::new(2.0, 2.0, 2.0);
+        let sun = Light::Directional {
+            direction,
+            irradiance,
+            cos_max: 1.0,
+        };
+        let sample = sun
+            .sample(&Vec::new(), &Vector3f::zeros(), &Vector2f::zeros())
+            .unwrap();
+        assert_eq!(sample.direction, -direction);
+        assert_eq!(sample.distance, f::INFINITY);
+        assert_eq!(sample.radiance, irradiance);
+        assert!(sample.delta);
+        assert!(sun.is_infinite());
+
+        // a sun with an angular size has the same irradiance on a surface that faces it
+        let sun = Light::Directional {
+            direction,
+            irradiance,
+            cos_max: (10.0 as f).to_radians().cos(),
+        };
+        let mut sampler = Independent::new(0);
+        let trials = 10000;
+        let mut estimate = Color3f::zeros();
+
+        for i in 0..trials {
+            sampler.start_sample((0, 0), i);
+            let sample = sun
+                .sample(&Vec::new(), &Vector3f::zeros(), &sampler.get_2d())
+                .unwrap();
+            assert!(!sample.delta);
+            assert_eq!(sample.radiance, sun.emitted(&sample.direction));
+
+            let pdf = sun.pdf(&Vec::new(), &Vector3f::zeros(), &sample.direction);
+            assert!((pdf - sample.pdf).abs() < 1e-3 * pdf);
+            let cos_theta = -sample.direction.dot(&direction);
+            estimate += sample.radiance * cos_theta / sample.pdf / trials as f;
+        }
+        assert!((estimate - irradiance).norm() < 1e-2, "{}", estimate);
+
+        // directions outside of the disk don't see the sun
+        let away = Vector3f::new(0.0, 1.0, 0.0);
+        assert_eq!(sun.emitted(&away), Color3f::zeros());
+        assert_eq!(sun.pdf(&Vec::new(), &Vector3f::zeros(), &away), 0.0);
+    }
+}
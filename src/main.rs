@@ -1,64 +1,25 @@
 extern crate nalgebra as na;
 
-use crate::na::Vector3;
-use clap::{load_yaml, value_t, App};
+use clap::{load_yaml, value_t_or_exit, values_t_or_exit, App, ArgMatches};
 use indicatif::{ProgressBar, ProgressStyle};
-use log::{error, info, warn};
-use pretty_env_logger;
-use rand::prelude::*;
-use rayon::iter::IntoParallelIterator;
-use rayon::prelude::*;
+use log::{info, warn};
+use std::path::Path;
+use std::process;
 use std::time::Instant;
-use std::vec::Vec;
-use trtlib::camera::pinhole::Pinhole;
-use trtlib::camera::Camera;
-use trtlib::hittable::{any_hit, ObjVec};
-use trtlib::scene;
+use trtlib::background::Background;
+use trtlib::camera::Pinhole;
+use trtlib::exr::{self, Layer, Precision};
+use trtlib::filter::FilterKind;
+use trtlib::hdr;
+use trtlib::integrator::IntegratorKind;
+use trtlib::pfm;
+use trtlib::raster::Raster;
+use trtlib::renderer::{NonFinite, Renderer, RendererBuilder};
+use trtlib::sampler::SamplerKind;
+use trtlib::scene::{self, CameraDescription, RenderSettings, Scene, SceneDescription};
+use trtlib::tonemap::{Operator, ToneMap};
 use trtlib::typedefs::*;
 
-/// Calculate the background color that corresponds to an outgoing camera ray. Creates a blend of
-/// blue and white.
-///
-/// `r` is the outgoing ray from the camera to the world `objects` is a list of tuple(geometric
-/// primitives, materials) that are in the scene `depth` is the recursion depth for global
-/// illumination `depth_limit` is the recursion depth limit for global illumination
-fn color(r: &Ray3f, primitives: &ObjVec<f>, depth: u, depth_limit: u) -> Color3f {
-    let possible_hit_record = any_hit(&primitives, r, Some(0.001), None);
-
-    if let Some(hit_record) = possible_hit_record {
-        let hr = hit_record.0;
-        let obj = hit_record.1;
-        let bsdf = &obj.material;
-
-        // if depth is less than depth limit, then global illumination
-        if depth < depth_limit {
-            let bsdf_record = bsdf.scatter(r, &hr);
-            let attenuation: Vector3f = bsdf_record.attenuated;
-            let scattered_ray: Ray3f = bsdf_record.out_scattered;
-            // FIXME I think that the scattered rays are getting messed up somehow and hitting the
-            // depth limit. Maybe they're going inside the sphere? In any case, whenever an object
-            // is hit, this method is returning (0,0,0). The background works fine which leads me
-            // to believe that there is an issue with the outgoing ray
-            let tmp_color = color(&scattered_ray, primitives, depth + 1, depth_limit)
-                .component_mul(&attenuation);
-            info!(
-                "current color: {}, {}, {}",
-                tmp_color.x, tmp_color.y, tmp_color.z
-            );
-            return tmp_color;
-        } else {
-            // TODO(afnan) change back to 0, 0, 0
-            return Color3f::new(0.0, 0.0, 0.0);
-        }
-    } else {
-        let unit_dir = r.direction.normalize();
-        let t = 0.5 * (unit_dir.y + 1.0);
-
-        // linearly interpolate a color based on the angle of the ray
-        return (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.7, 1.0);
-    }
-}
-
 /// Creates a progress bar with the style we want for this app
 fn create_progress_bar(size: u64) -> ProgressBar {
     let style = ProgressStyle::default_bar()
@@ -69,93 +30,133 @@ fn create_progress_bar(size: u64) -> ProgressBar {
     pb
 }
 
-/// Render the scene, given a configuration. This method takes care of the bulk of the core code to
-/// generate the scene as well as parallelize the render.
+/// How the rendered image is saved, which is chosen based on the extension of the output file
+enum OutputFormat {
+    /// An 8 bit image, in any of the formats that the image crate supports, which is tone mapped
+    Ldr(ToneMap),
+
+    /// A high dynamic range EXR file, with the given auxiliary layers
+    Exr {
+        layers: Vec<Layer>,
+        precision: Precision,
+    },
+
+    /// A high dynamic range Radiance HDR file
+    Hdr,
+
+    /// A lossless, high dynamic range PFM file
+    Pfm,
+}
+
+/// Render the scene, showing the progress of the render in the terminal, and save the result.
 ///
 /// Params:
-/// - nx: the width of the image
-/// - ny: the height of hte image
-/// - ns: the antialiasing factor for each pixel
+/// - renderer: the renderer, with everything except for progress reporting configured
+/// - nx, ny: the width and height of the image
 /// - out: the relative output filename for the rendered picture
+/// - format: the format to save the picture in
 fn render_scene(
-    primitives: &ObjVec<f>,
-    nx: usize,
-    ny: usize,
-    ns: usize,
+    renderer: RendererBuilder,
+    (nx, ny): (usize, usize),
     out: &str,
+    format: &OutputFormat,
 ) -> std::io::Result<()> {
-    let camera = Pinhole::default();
-
-    // recursion limit
-    let depth_limit = 50;
-    info!("Using a depth limit of {}", depth_limit);
-
     println!("Rendering scene...");
-    let mut buffer: Vec<[u8; 3]> = Vec::with_capacity(nx * ny);
+    let renderer = match format {
+        OutputFormat::Exr { layers, .. } => renderer.aovs(!layers.is_empty()),
+        _ => renderer,
+    };
 
     // initialize progress bar so we can track progress from the CLI
     let pb = create_progress_bar((nx * ny) as u64);
-
-    (0..(nx * ny))
-        .into_par_iter()
-        .map(|idx| {
-            pb.inc(1);
-            let j = ny - (idx / nx);
-            let i = idx % nx;
-            let mut rng = rand::thread_rng();
-
-            // accumulate colors via AA
-            let mut col = Color3f::new(0.0, 0.0, 0.0);
-
-            for _ in 0..ns {
-                let u = (i as f + rng.gen::<f>()) / (nx as f);
-                let v = (j as f + rng.gen::<f>()) / (ny as f);
-                let r = camera.get_ray(u, v);
-                col += color(&r, &primitives, 0, depth_limit);
-            }
-
-            // average out the color values
-            col /= ns as f32;
-            col.apply(|e| e.sqrt());
-
-            // these assertions ensure that the values are above 0 and we aren't losing any
-            // information (the values should never get below 0 anyways)
-            assert!(col.x >= 0.0 && col.x <= 1.0);
-            assert!(col.y >= 0.0 && col.y <= 1.0);
-            assert!(col.z >= 0.0 && col.z <= 1.0);
-
-            // writing colors as u16 instead of u8 because this allows us to sanity check whether
-            // colors would wrap/be invalid
-            let ir = (col.x * 255.99) as u16;
-            let ig = (col.y * 255.99) as u16;
-            let ib = (col.z * 255.99) as u16;
-
-            // write to file with some sanity checking
-            // We use black as the default value if there is some incorrect value
-            if ir > 256 || ig > 256 || ib > 256 {
-                error!(
-                    "ERROR: generated invalid color value ({}, {}, {})",
-                    ir, ig, ib
-                );
-                return [0 as u8; 3];
-            }
-            [ir as u8, ig as u8, ib as u8]
-        })
-        .collect_into_vec(&mut buffer);
+    let pb_handle = pb.clone();
+    let film = renderer
+        .progress(move |done, _| pb_handle.set_position(done as u64))
+        .build()
+        .render();
     pb.finish();
 
+    let stats = film.stats;
+    if stats.has_problems() {
+        println!(
+            "Out of {} samples, {} were NaN or infinite ({} dropped), {} were negative and {} were \
+             clamped to the maximum luminance",
+            stats.samples, stats.non_finite, stats.dropped, stats.negative, stats.clamped
+        );
+    }
+
     println!("Writing buffer to file");
     let start_time = Instant::now();
-
-    // flatten the image buffer so it can be saved using the image crate Note that this is a
-    // performance issue as it doubles the memory necessary
-    let image_buffer: Vec<u8> = buffer.iter().flat_map(|n| n.iter().cloned()).collect();
-    image::save_buffer(out, &image_buffer, nx as u32, ny as u32, image::RGB(8))?;
+    match format {
+        OutputFormat::Exr { layers, precision } => exr::save(out, &film, layers, *precision)?,
+        OutputFormat::Hdr => hdr::save(out, &Raster::from(&film))?,
+        OutputFormat::Pfm => pfm::save(out, &Raster::from(&film))?,
+        OutputFormat::Ldr(tone_map) => image::save_buffer(
+            out,
+            &film.to_rgb8(tone_map),
+            nx as u32,
+            ny as u32,
+            image::RGB(8),
+        )?,
+    }
     let elapsed = start_time.elapsed().as_secs();
     println!("File took {} seconds to write to disk\n", elapsed);
     Ok(())
 }
 
+/// Apply the camera options from the command line to the scene's camera. If any of them are
+/// present, the camera becomes a look-at camera, and the options that weren't given keep the
+/// scene's values (or the defaults, if the scene used a different type of camera).
+fn camera_from_args(matches: &ArgMatches, camera: CameraDescription) -> CameraDescription {
+    let options = ["eye", "target", "up", "fov"];
+
+    if !options.iter().any(|&name| matches.is_present(name)) {
+        return camera;
+    }
+    let mut camera = match camera {
+        CameraDescription::LookAt { .. } => camera,
+        _ => CameraDescription::default(),
+    };
+
+    if let CameraDescription::LookAt {
+        eye,
+        target,
+        up,
+        vfov,
+        ..
+    } = &mut camera
+    {
+        let vector = |name| {
+            let v = values_t_or_exit!(matches, name, f);
+            Vector3f::new(v[0], v[1], v[2])
+        };
+
+        if matches.is_present("eye") {
+            *eye = vector("eye");
+        }
+        if matches.is_present("target") {
+            *target = vector("target");
+        }
+        if matches.is_present("up") {
+            *up = vector("up");
+        }
+        if matches.is_present("fov") {
+            *vfov = value_t_or_exit!(matches, "fov", f);
+
+            if *vfov <= 0.0 || *vfov >= 180.0 {
+                eprintln!("error: the field of view must be between 0 and 180 degrees");
+                process::exit(1);
+            }
+        }
+
+        if let Err(message) = Pinhole::check_look_at(eye, target, up) {
+            eprintln!("error: {}", message);
+            process::exit(1);
+        }
+    }
+    camera
+}
+
 fn main() -> std::io::Result<()> {
     pretty_env_logger::init();
     // load the args from a yaml file
@@ -163,14 +164,204 @@ fn main() -> std::io::Result<()> {
     info!("Parsing command line parameters");
     let matches = App::from_yaml(yaml).get_matches();
 
-    // Check for args or get default values
-    // The default values should be relatively cheap
-    let width = value_t!(matches.value_of("width"), usize).unwrap_or(200);
-    let height = value_t!(matches.value_of("height"), usize).unwrap_or(100);
-    let aa = value_t!(matches.value_of("aa"), usize).unwrap_or(50);
+    let description = match matches.value_of("scene") {
+        Some(path) => {
+            info!("Loading scene from {}", path);
+            scene::load(path).unwrap_or_else(|e| {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            })
+        }
+        None => SceneDescription {
+            objects: scene::test_scene(),
+            camera: CameraDescription::default(),
+            background: Background::default(),
+            lights: Vec::new(),
+            settings: RenderSettings::default(),
+        },
+    };
+    let settings = description.settings;
+
+    // Check for args or get default values. Command line arguments take precedence over the
+    // settings in the scene file. The default values should be relatively cheap
+    let width = if matches.is_present("width") {
+        Some(value_t_or_exit!(matches, "width", usize))
+    } else {
+        None
+    }
+    .or(settings.width)
+    .unwrap_or(200);
+    let height = if matches.is_present("height") {
+        Some(value_t_or_exit!(matches, "height", usize))
+    } else {
+        None
+    }
+    .or(settings.height)
+    .unwrap_or(100);
+    if width == 0 || height == 0 {
+        eprintln!("error: the image must be at least one pixel wide and high");
+        process::exit(1);
+    }
+    let aa = if matches.is_present("aa") {
+        Some(value_t_or_exit!(matches, "aa", usize))
+    } else {
+        None
+    }
+    .or(settings.samples)
+    .unwrap_or(50);
+    if aa == 0 {
+        eprintln!("error: each pixel needs at least one sample");
+        process::exit(1);
+    }
+    let depth_limit = if matches.is_present("depth") {
+        Some(value_t_or_exit!(matches, "depth", u))
+    } else {
+        None
+    }
+    .or(settings.depth)
+    .unwrap_or(50);
+    let min_depth = if matches.is_present("min_depth") {
+        Some(value_t_or_exit!(matches, "min_depth", u))
+    } else {
+        None
+    }
+    .or(settings.min_depth)
+    .unwrap_or(3);
     let output_fname: &str = matches.value_of("out").unwrap_or("render.png");
-    let scene = scene::test_scene();
+    let layers = if matches.is_present("layers") {
+        values_t_or_exit!(matches, "layers", Layer)
+    } else {
+        Vec::new()
+    };
+    let extension = Path::new(output_fname)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    let operator = if matches.is_present("tonemap") {
+        Some(value_t_or_exit!(matches, "tonemap", Operator))
+    } else {
+        None
+    }
+    .or(settings.tone_map)
+    .unwrap_or_default();
+    let exposure = if matches.is_present("exposure") {
+        Some(value_t_or_exit!(matches, "exposure", f))
+    } else {
+        None
+    }
+    .or(settings.exposure)
+    .unwrap_or(0.0);
+    let white_point = if matches.is_present("white_point") {
+        Some(value_t_or_exit!(matches, "white_point", f))
+    } else {
+        None
+    }
+    .or(settings.white_point)
+    .unwrap_or_else(|| ToneMap::default().white_point);
+    let tone_map = ToneMap {
+        operator,
+        exposure,
+        white_point,
+    };
+    if tone_map.white_point <= 0.0 {
+        eprintln!("error: the white point must be positive");
+        process::exit(1);
+    }
+    let format = match extension.as_deref() {
+        Some("exr") => OutputFormat::Exr {
+            layers,
+            precision: value_t_or_exit!(matches, "precision", Precision),
+        },
+        other => {
+            if !layers.is_empty() {
+                warn!("Auxiliary layers can only be saved in EXR files, so they will be skipped");
+            }
+            match other {
+                Some("hdr") => OutputFormat::Hdr,
+                Some("pfm") => OutputFormat::Pfm,
+                _ => OutputFormat::Ldr(tone_map),
+            }
+        }
+    };
+    let tone_map_options = ["tonemap", "exposure", "white_point"];
+    let is_ldr = matches!(format, OutputFormat::Ldr(_));
+
+    if !is_ldr
+        && tone_map_options
+            .iter()
+            .any(|&name| matches.is_present(name))
+    {
+        warn!("Tone mapping only applies to 8 bit images, so it will be skipped");
+    }
+    let seed = if matches.is_present("seed") {
+        Some(value_t_or_exit!(matches, "seed", u64))
+    } else {
+        None
+    }
+    .or(settings.seed)
+    .unwrap_or_default();
+    let sampler = if matches.is_present("sampler") {
+        Some(value_t_or_exit!(matches, "sampler", SamplerKind))
+    } else {
+        None
+    }
+    .or(settings.sampler)
+    .unwrap_or_default();
+    let filter = if matches.is_present("filter") {
+        Some(value_t_or_exit!(matches, "filter", FilterKind))
+    } else {
+        None
+    }
+    .or(settings.filter)
+    .unwrap_or_default();
+    let filter_radius = if matches.is_present("filter_radius") {
+        Some(value_t_or_exit!(matches, "filter_radius", f))
+    } else {
+        None
+    }
+    .or(settings.filter_radius);
+    if filter_radius.is_some_and(|radius| radius <= 0.0) {
+        eprintln!("error: the filter radius must be positive");
+        process::exit(1);
+    }
+    let non_finite = if matches.is_present("non_finite") {
+        Some(value_t_or_exit!(matches, "non_finite", NonFinite))
+    } else {
+        None
+    }
+    .or(settings.non_finite)
+    .unwrap_or_default();
+    let max_luminance = if matches.is_present("max_luminance") {
+        Some(value_t_or_exit!(matches, "max_luminance", f))
+    } else {
+        None
+    }
+    .or(settings.max_luminance);
+    if max_luminance.is_some_and(|max| max <= 0.0) {
+        eprintln!("error: the maximum luminance must be positive");
+        process::exit(1);
+    }
+    let camera = camera_from_args(&matches, description.camera).build((width, height));
+    let integrator = value_t_or_exit!(matches, "integrator", IntegratorKind);
+    info!(
+        "Using a depth limit of {}, with Russian roulette after {} bounces",
+        depth_limit, min_depth
+    );
+    info!("Using the {} sampler with seed {}", sampler, seed);
+    let scene =
+        Scene::new(description.objects, description.background).with_lights(description.lights);
 
     info!("Preparing to render scene");
-    render_scene(&scene, width, height, aa, output_fname)
+    let renderer = Renderer::builder(scene, camera)
+        .resolution(width, height)
+        .samples(aa)
+        .depth(depth_limit)
+        .min_depth(min_depth)
+        .integrator(integrator)
+        .sampler(sampler)
+        .seed(seed)
+        .filter(filter)
+        .filter_radius(filter_radius)
+        .non_finite(non_finite)
+        .max_luminance(max_luminance);
+    render_scene(renderer, (width, height), output_fname, &format)
 }
@@ -0,0 +1,170 @@
+use super::{BSDFRecord, BSDF};
+use crate::common::{fresnel_dielectric, mirror, refract};
+use crate::hittable::HitRecord;
+use crate::na::{Matrix, RealField, Vector3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use num::FromPrimitive;
+use std::fmt::Debug;
+
+/// A smooth dielectric material, such as glass or water, which both reflects and refracts light.
+///
+/// Whether an incoming ray is reflected or refracted is chosen randomly, with the probability of
+/// reflection given by the Fresnel equations. Rays that hit the front face of the surface are
+/// entering the object, and the others are leaving it.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct Dielectric<N: RealField + Copy + Debug + PartialEq> {
+    /// The index of refraction of the material, relative to the medium outside of it (such as 1.5
+    /// for glass in air)
+    pub ior: N,
+
+    /// A tint that is applied to both reflected and refracted light. White is colorless.
+    pub albedo: Vector3<N>,
+}
+
+impl<N: RealField + FromPrimitive> BSDF<N> for Dielectric<N> {
+    fn scatter(
+        &self,
+        in_ray: &Ray<N>,
+        hit_record: &HitRecord<N>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<BSDFRecord<N>> {
+        let unit_dir = in_ray.direction.normalize();
+
+        // The normal faces the ray, so only the indices of refraction depend on which side of the
+        // surface it's on
+        let normal = hit_record.shading_normal;
+        let (eta_i, eta_t) = if hit_record.front_face {
+            (N::one(), self.ior)
+        } else {
+            (self.ior, N::one())
+        };
+        let cos_i = -Matrix::dot(&unit_dir, &normal);
+        let reflectance = fresnel_dielectric(cos_i, eta_i, eta_t);
+        let u = N::from_f32(sampler.get_1d()).unwrap();
+
+        // refract returns `None` on total internal reflection, in which case the reflectance is
+        // also 1, but the check on `u` may still pass due to floating point error
+        let direction = if u < reflectance {
+            None
+        } else {
+            refract(&unit_dir, &normal, eta_i / eta_t)
+        }
+        .unwrap_or_else(|| mirror(&unit_dir, &normal));
+
+        Some(BSDFRecord {
+            out_scattered: Ray {
+                origin: hit_record.p,
+                direction,
+            },
+            attenuated: self.albedo,
+        })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord<N>) -> Vector3<N> {
+        self.albedo
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::Independent;
+    use crate::typedefs::*;
+
+    fn glass() -> Dielectric<f> {
+        Dielectric {
+            ior: 1.5,
+            albedo: Vector3f::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// A hit by `ray` on the plane y = 0, whose outside faces up
+    fn hit(ray: &Ray3f) -> HitRecord<f> {
+        HitRecord {
+            t: 1.0,
+            p: Vector3f::new(0.0, 0.0, 0.0),
+            normal: Vector3f::new(0.0, 1.0, 0.0),
+            shading_normal: Vector3f::new(0.0, 1.0, 0.0),
+            uv: Vector2f::new(0.0, 0.0),
+            front_face: true,
+        }
+        .face_forward(&ray.direction)
+    }
+
+    #[test]
+    fn test_entering() {
+        // at normal incidence about 4% of the light is reflected
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        let trials = 20000;
+        let mut reflected = 0;
+        let mut sampler = Independent::new(0);
+
+        for i in 0..trials {
+            sampler.start_sample((0, 0), i);
+            let record = glass().scatter(&ray, &hit(&ray), &mut sampler).unwrap();
+            assert_eq!(record.attenuated, Vector3f::new(1.0, 1.0, 1.0));
+
+            if record.out_scattered.direction.y > 0.0 {
+                reflected += 1;
+            } else {
+                assert_eq!(record.out_scattered.direction, ray.direction);
+            }
+        }
+        let fraction = reflected as f / trials as f;
+        assert!(fraction > 0.02 && fraction < 0.06, "{}", fraction);
+    }
+
+    #[test]
+    fn test_refraction_bends_towards_normal() {
+        let ray = Ray::new(
+            &Vector3f::new(-1.0, 1.0, 0.0),
+            &Vector3f::new(1.0, -1.0, 0.0),
+        );
+
+        let mut sampler = Independent::new(0);
+
+        for i in 0..100 {
+            sampler.start_sample((0, 0), i);
+            let dir = glass()
+                .scatter(&ray, &hit(&ray), &mut sampler)
+                .unwrap()
+                .out_scattered
+                .direction;
+
+            if dir.y < 0.0 {
+                let sin_t = dir.x / dir.norm();
+                assert!((sin_t * 1.5 - (0.5 as f).sqrt()).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_total_internal_reflection() {
+        // the ray is inside the glass, traveling along the outward normal at a grazing angle
+        let ray = Ray::new(
+            &Vector3f::new(-1.0, -0.2, 0.0),
+            &Vector3f::new(1.0, 0.2, 0.0),
+        );
+
+        let mut sampler = Independent::new(0);
+
+        for i in 0..100 {
+            sampler.start_sample((0, 0), i);
+            let dir = glass()
+                .scatter(&ray, &hit(&ray), &mut sampler)
+                .unwrap()
+                .out_scattered
+                .direction;
+            assert!(dir.y < 0.0);
+            assert!((dir.x - ray.direction.normalize().x).abs() < 1e-6);
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use super::{BSDFRecord, BSDF};
+use crate::common::coordinate_system;
 use crate::hittable::HitRecord;
 use crate::na::{RealField, Vector3};
 use crate::ray::Ray;
-use crate::sample::unit_sphere;
+use crate::sample::{cosine_hemisphere, cosine_hemisphere_pdf};
+use crate::sampler::Sampler;
 use num::FromPrimitive;
 use std::fmt::Debug;
 
@@ -16,17 +18,104 @@ pub struct Diffuse<N: RealField + Copy + Debug + PartialEq> {
 impl<N: FromPrimitive + RealField> BSDF<N> for Diffuse<N> {
     // note that the incoming angle doesn't matter for a lambertian surface, which is why we ignore
     // the incoming ray
-    fn scatter(&self, _in_ray: &Ray<N>, hit_record: &HitRecord<N>) -> BSDFRecord<N> {
-        let target = hit_record.p + hit_record.normal + unit_sphere();
+    fn scatter(
+        &self,
+        _in_ray: &Ray<N>,
+        hit_record: &HitRecord<N>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<BSDFRecord<N>> {
+        // a lambertian surface scatters light with a density that's proportional to the cosine of
+        // the angle with the normal
+        let u = sampler.get_2d().map(|x| N::from_f32(x).unwrap());
+        let local = cosine_hemisphere(&u);
+        let normal = hit_record.shading_normal.normalize();
+        let (s, t) = coordinate_system(&normal);
         let scattered = Ray {
             origin: hit_record.p,
-            direction: target - hit_record.p,
+            direction: s * local.x + t * local.y + normal * local.z,
         };
         let atten = self.albedo;
 
-        BSDFRecord {
+        Some(BSDFRecord {
             out_scattered: scattered,
             attenuated: atten,
+        })
+    }
+
+    fn eval(
+        &self,
+        _in_ray: &Ray<N>,
+        hit_record: &HitRecord<N>,
+        direction: &Vector3<N>,
+    ) -> Vector3<N> {
+        // light is only scattered into the hemisphere around the normal
+        if direction.dot(&hit_record.shading_normal) <= N::zero() {
+            return Vector3::zeros();
+        }
+        self.albedo * N::frac_1_pi()
+    }
+
+    fn pdf(&self, _in_ray: &Ray<N>, hit_record: &HitRecord<N>, direction: &Vector3<N>) -> N {
+        let cos_theta = direction
+            .normalize()
+            .dot(&hit_record.shading_normal.normalize());
+        if cos_theta <= N::zero() {
+            return N::zero();
         }
+        cosine_hemisphere_pdf(cos_theta)
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord<N>) -> Vector3<N> {
+        self.albedo
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::Independent;
+    use crate::typedefs::*;
+
+    #[test]
+    fn test_scatter() {
+        let diffuse = Diffuse {
+            albedo: Color3f::new(0.5, 0.25, 1.0),
+        };
+        let normal = Vector3f::new(1.0, 2.0, 3.0).normalize();
+        let hit = HitRecord {
+            t: 1.0,
+            p: Vector3f::new(0.0, 0.0, 0.0),
+            normal,
+            shading_normal: normal,
+            uv: Vector2f::new(0.0, 0.0),
+            front_face: true,
+        };
+        let ray = Ray::new(&normal, &-normal);
+        let mut sampler = Independent::new(0);
+        let trials = 10000;
+        let mut mean_cos = 0.0;
+
+        for i in 0..trials {
+            sampler.start_sample((0, 0), i);
+            let sample = diffuse.sample(&ray, &hit, &mut sampler).unwrap();
+            let record = sample.record;
+            let direction = record.out_scattered.direction;
+            assert!((direction.norm() - 1.0).abs() < 1e-5);
+            assert!(direction.dot(&normal) >= -1e-6);
+            mean_cos += direction.dot(&normal) / trials as f;
+
+            // the scattered directions are cosine distributed, so the attenuation is the albedo
+            let f = diffuse.eval(&ray, &hit, &direction);
+            assert!((f * f::pi() - record.attenuated).norm() < 1e-5);
+            let pdf = diffuse.pdf(&ray, &hit, &direction);
+            assert!((pdf - sample.pdf).abs() < 1e-5);
+            assert!((pdf - direction.dot(&normal) / f::pi()).abs() < 1e-5);
+        }
+
+        // the mean cosine of a cosine distribution is 2/3
+        assert!((mean_cos - 2.0 / 3.0).abs() < 0.01, "{}", mean_cos);
+        assert_eq!(diffuse.eval(&ray, &hit, &-normal), Color3f::zeros());
+        assert_eq!(diffuse.pdf(&ray, &hit, &-normal), 0.0);
+        assert!(!diffuse.is_delta());
     }
 }
@@ -0,0 +1,86 @@
+use super::{BSDFRecord, BSDF};
+use crate::hittable::HitRecord;
+use crate::na::{RealField, Vector3};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use std::fmt::Debug;
+
+/// A material that emits light, which turns whatever primitive it's applied to into an area
+/// light.
+///
+/// The surface emits the same radiance in every direction, from both of its sides, and it
+/// absorbs any light that arrives at it.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct Emissive<N: RealField + Copy + Debug + PartialEq> {
+    /// The radiance that is emitted from the surface. This isn't limited to the range [0, 1],
+    /// since lights are generally much brighter than the surfaces they illuminate.
+    pub emission: Vector3<N>,
+}
+
+impl<N: RealField> BSDF<N> for Emissive<N> {
+    fn scatter(
+        &self,
+        _in_ray: &Ray<N>,
+        _hit_record: &HitRecord<N>,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<BSDFRecord<N>> {
+        None
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn emitted(&self, _in_ray: &Ray<N>, _hit_record: &HitRecord<N>) -> Vector3<N> {
+        self.emission
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::diffuse::Diffuse;
+    use crate::sampler::Independent;
+    use crate::typedefs::*;
+
+    fn hit() -> HitRecord<f> {
+        HitRecord {
+            t: 1.0,
+            p: Vector3f::new(0.0, 0.0, 0.0),
+            normal: Vector3f::new(0.0, 1.0, 0.0),
+            shading_normal: Vector3f::new(0.0, 1.0, 0.0),
+            uv: Vector2f::new(0.0, 0.0),
+            front_face: true,
+        }
+    }
+
+    #[test]
+    fn test_emission() {
+        let light = Emissive {
+            emission: Color3f::new(4.0, 4.0, 4.0),
+        };
+
+        // the light is visible from both sides and doesn't reflect anything
+        for &dy in &[-1.0, 1.0] {
+            let ray = Ray::new(&Vector3f::new(0.0, -dy, 0.0), &Vector3f::new(0.0, dy, 0.0));
+            assert!(light.is_emissive());
+            assert_eq!(light.emitted(&ray, &hit()), light.emission);
+            assert!(light
+                .scatter(&ray, &hit(), &mut Independent::new(0))
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn test_default_emission() {
+        let diffuse = Diffuse {
+            albedo: Color3f::new(0.5, 0.5, 0.5),
+        };
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            &Vector3f::new(0.0, -1.0, 0.0),
+        );
+        assert_eq!(diffuse.emitted(&ray, &hit()), Color3f::zeros());
+        assert!(!diffuse.is_emissive());
+    }
+}
@@ -1,8 +1,10 @@
-use crate::common::{mirror, random_in_unit_sphere};
+use crate::common::mirror;
 use crate::hittable::HitRecord;
 use crate::material::{BSDFRecord, BSDF};
 use crate::na::{Matrix, RealField, Vector3};
 use crate::ray::Ray;
+use crate::sample::unit_ball;
+use crate::sampler::Sampler;
 use num::{Float, FromPrimitive};
 use std::fmt::Debug;
 
@@ -26,19 +28,25 @@ where
 impl<N> BSDF<N> for Mirror<N>
 where
     N: FromPrimitive + RealField + Float,
-    rand::distributions::Standard: rand::distributions::Distribution<N>,
 {
     /// Implements the scatter function for a mirror surface. This mirror implementation takes
     /// the albedo into account and attenuates the reflection based off the albedo. The mirror
     /// reflects the incoming ray about the normal of the incoming ray.
-    fn scatter(&self, in_ray: &Ray<N>, hit_record: &HitRecord<N>) -> BSDFRecord<N> {
+    fn scatter(
+        &self,
+        in_ray: &Ray<N>,
+        hit_record: &HitRecord<N>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<BSDFRecord<N>> {
         let fuzz = num::clamp(
             self.fuzziness,
             N::from_u32(0).unwrap(),
             N::from_u32(1).unwrap(),
         );
-        let fuzz_vector = random_in_unit_sphere().map(|x| x * fuzz);
-        let reflection = mirror(&in_ray.direction, &hit_record.normal) + fuzz_vector;
+        let u = sampler.get_2d();
+        let point = Vector3::new(u.x, u.y, sampler.get_1d()).map(|x| N::from_f32(x).unwrap());
+        let fuzz_vector = unit_ball(&point).map(|x| x * fuzz);
+        let reflection = mirror(&in_ray.direction, &hit_record.shading_normal) + fuzz_vector;
         let scatter_out = Ray {
             direction: reflection,
             origin: hit_record.p,
@@ -55,6 +63,53 @@ where
                 N::from_u32(0).unwrap(),
             );
         }
-        bsdf_record
+        Some(bsdf_record)
+    }
+
+    /// Fuzzy reflections are treated as perfect ones, since they're scattered in directions
+    /// whose density isn't known
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord<N>) -> Vector3<N> {
+        self.albedo
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::Independent;
+    use crate::typedefs::*;
+
+    #[test]
+    fn test_sample() {
+        let mirror = Mirror {
+            albedo: Color3f::new(0.5, 0.5, 0.5),
+            fuzziness: 0.0,
+        };
+        let hit = HitRecord {
+            t: 1.0,
+            p: Vector3f::zeros(),
+            normal: Vector3f::new(0.0, 1.0, 0.0),
+            shading_normal: Vector3f::new(0.0, 1.0, 0.0),
+            uv: Vector2f::zeros(),
+            front_face: true,
+        };
+        let ray = Ray::new(
+            &Vector3f::new(-1.0, 1.0, 0.0),
+            &Vector3f::new(1.0, -1.0, 0.0),
+        );
+
+        // a perfect reflection has no density, and can't be found by evaluating the BSDF
+        let sample = mirror.sample(&ray, &hit, &mut Independent::new(0)).unwrap();
+        let direction = sample.record.out_scattered.direction.normalize();
+        assert!((direction - Vector3f::new(1.0, 1.0, 0.0).normalize()).norm() < 1e-6);
+        assert_eq!(sample.record.attenuated, mirror.albedo);
+        assert_eq!(sample.pdf, 0.0);
+        assert!(mirror.is_delta());
+        assert_eq!(mirror.eval(&ray, &hit, &direction), Color3f::zeros());
+        assert_eq!(mirror.pdf(&ray, &hit, &direction), 0.0);
     }
 }
@@ -1,8 +1,11 @@
 use crate::hittable::HitRecord;
 use crate::na::{RealField, Vector3};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
+pub mod dielectric;
 pub mod diffuse;
+pub mod emissive;
 pub mod mirror;
 
 /// Any struct that implements the BSDF trait should contain only parameters relevant to the
@@ -11,8 +14,85 @@ pub mod mirror;
 ///
 /// `in_ray` is the incoming ray from the camera to the surface that was struck.
 /// `hit_record` is the `HitRecord` that is associated with that hit.
+/// `sampler` provides the values for BSDFs that scatter light stochastically.
 pub trait BSDF<N: RealField + Sync> {
-    fn scatter(&self, in_ray: &Ray<N>, hit_record: &HitRecord<N>) -> BSDFRecord<N>;
+    /// Scatter an incoming ray off of the surface. Returns `None` if the light is absorbed, in
+    /// which case the path ends at this surface.
+    fn scatter(
+        &self,
+        in_ray: &Ray<N>,
+        hit_record: &HitRecord<N>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<BSDFRecord<N>>;
+
+    /// The fraction of the light arriving from `direction` that the surface scatters back along
+    /// `in_ray`, per unit of solid angle. This doesn't include the cosine of the angle between
+    /// `direction` and the normal.
+    ///
+    /// BSDFs that only scatter light in specific directions (see `is_delta`) are zero everywhere
+    /// else, since there's no chance of an arbitrary direction being one of them. That is what
+    /// the default implementation returns.
+    fn eval(
+        &self,
+        _in_ray: &Ray<N>,
+        _hit_record: &HitRecord<N>,
+        _direction: &Vector3<N>,
+    ) -> Vector3<N> {
+        Vector3::zeros()
+    }
+
+    /// The probability density that `scatter` picks `direction` with, with respect to solid
+    /// angle. Delta BSDFs (see `is_delta`) have a density of zero for any arbitrary direction,
+    /// which is what the default implementation returns.
+    fn pdf(&self, _in_ray: &Ray<N>, _hit_record: &HitRecord<N>, _direction: &Vector3<N>) -> N {
+        N::zero()
+    }
+
+    /// Scatter an incoming ray off of the surface like `scatter`, and also return the density
+    /// of the scattered direction. The density is zero for delta BSDFs.
+    ///
+    /// The default implementation finds the density of the direction from `scatter` with `pdf`.
+    fn sample(
+        &self,
+        in_ray: &Ray<N>,
+        hit_record: &HitRecord<N>,
+        sampler: &mut dyn Sampler,
+    ) -> Option<BSDFSample<N>> {
+        let record = self.scatter(in_ray, hit_record, sampler)?;
+        let pdf = if self.is_delta() {
+            N::zero()
+        } else {
+            self.pdf(in_ray, hit_record, &record.out_scattered.direction)
+        };
+        Some(BSDFSample { record, pdf })
+    }
+
+    /// Whether the BSDF only scatters light in a discrete set of directions, like a perfect
+    /// mirror. Lights can't be sampled for these surfaces, since `eval` is zero for any direction
+    /// that the light is in, so the light that they scatter has to be found by following the
+    /// directions from `scatter` instead.
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    /// Whether the surface emits light, which makes any object with this material a light that
+    /// can be sampled directly. The default implementation returns false.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// The radiance that the surface emits back along `in_ray`. Most materials don't emit any
+    /// light, which is what the default implementation returns.
+    fn emitted(&self, _in_ray: &Ray<N>, _hit_record: &HitRecord<N>) -> Vector3<N> {
+        Vector3::zeros()
+    }
+
+    /// The overall color of the surface, which is written out as an auxiliary image for
+    /// compositing and denoising. Surfaces that don't reflect any light are black, which is what
+    /// the default implementation returns.
+    fn albedo(&self, _hit_record: &HitRecord<N>) -> Vector3<N> {
+        Vector3::zeros()
+    }
 }
 
 /// The BSDF record is similar to the `HitRecord` struct. It contains information about the
@@ -25,3 +105,16 @@ pub struct BSDFRecord<N: RealField + Sync> {
     /// How much the ray should be attenuated
     pub attenuated: Vector3<N>,
 }
+
+/// A scattered ray along with the density of its direction, which is what integrators need to
+/// weigh the ray against other ways of sampling the same direction
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct BSDFSample<N: RealField + Sync> {
+    /// The scattered ray, and how much it's attenuated by. The attenuation is the value of the
+    /// BSDF times the cosine of the angle with the normal, divided by `pdf`.
+    pub record: BSDFRecord<N>,
+
+    /// The probability density of the scattered direction with respect to solid angle, which is
+    /// zero for delta BSDFs
+    pub pdf: N,
+}
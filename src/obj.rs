@@ -0,0 +1,791 @@
+//! Wavefront OBJ/MTL importer
+//!
+//! This module loads polygonal meshes from OBJ files, along with any materials declared in the
+//! MTL libraries they reference. Every face is triangulated and turned into an `ObjRef` so that
+//! the result can be appended directly to a scene's `ObjVec`.
+//!
+//! The supported subset of the format is:
+//!
+//! - `v`, `vt` and `vn` vertex data
+//! - `f` faces with any number of vertices in the `v`, `v/vt`, `v//vn` and `v/vt/vn` forms,
+//!   including negative (relative) indices
+//! - `mtllib` and `usemtl`
+//!
+//! Other statements (groups, smoothing groups, lines, etc.) are ignored. Faces that don't specify
+//! normals are shaded flat.
+
+use crate::hittable::{BSDFRef, ObjRef, ObjVec};
+use crate::material::dielectric::Dielectric;
+use crate::material::diffuse::Diffuse;
+use crate::material::emissive::Emissive;
+use crate::material::mirror::Mirror;
+use crate::primitives::mesh::TriangleMesh;
+use crate::typedefs::*;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An error that occurred while loading an OBJ or MTL file
+#[derive(Debug)]
+pub enum ObjError {
+    /// A file could not be read
+    Io { path: PathBuf, source: io::Error },
+
+    /// A file was read, but its contents were malformed
+    Parse {
+        /// The name of the file that contained the error
+        file: String,
+
+        /// The (1-indexed) line that the error occurred on
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// The properties of a material that was declared in an MTL file
+///
+/// Only the properties that map onto BSDFs in this crate are kept.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    /// The diffuse color (`Kd`)
+    pub diffuse: Color3f,
+
+    /// The specular color (`Ks`)
+    pub specular: Color3f,
+
+    /// The specular exponent (`Ns`)
+    pub shininess: f,
+
+    /// The color of light that is transmitted through the material (`Tf`)
+    pub transmission: Color3f,
+
+    /// The index of refraction (`Ni`), which must be positive
+    pub ior: f,
+
+    /// The radiance emitted by the material (`Ke`)
+    pub emission: Color3f,
+
+    /// The illumination model (`illum`)
+    pub illum: u,
+}
+
+impl Default for MtlMaterial {
+    /// The defaults that are used by most OBJ exporters
+    fn default() -> Self {
+        Self {
+            diffuse: Color3f::new(0.8, 0.8, 0.8),
+            specular: Color3f::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            transmission: Color3f::new(1.0, 1.0, 1.0),
+            ior: 1.0,
+            emission: Color3f::new(0.0, 0.0, 0.0),
+            illum: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Create the BSDF that best matches this material.
+    ///
+    /// Illumination models 3 and 5 use ray traced reflections, so they become mirrors tinted by
+    /// the specular color. The specular exponent is converted to a roughness using the usual
+    /// Phong to Beckmann mapping and used as the mirror's fuzziness. Illumination models 4, 6 and
+    /// 7 are transparent, so they become dielectrics with the optical density (`Ni`) as the index
+    /// of refraction, tinted by the transmission filter (`Tf`). Everything else is treated as a
+    /// diffuse surface. Materials with a non-zero emission become lights, regardless of their
+    /// illumination model.
+    pub fn bsdf(&self) -> BSDFRef<f> {
+        if self.emission != Color3f::zeros() {
+            return Box::new(Emissive {
+                emission: self.emission,
+            });
+        }
+
+        match self.illum {
+            3 | 5 => Box::new(Mirror {
+                albedo: self.specular,
+                fuzziness: (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt(),
+            }),
+            4 | 6 | 7 => Box::new(Dielectric {
+                ior: self.ior,
+                albedo: self.transmission,
+            }),
+            _ => Box::new(Diffuse {
+                albedo: self.diffuse,
+            }),
+        }
+    }
+}
+
+/// Load an OBJ file, along with any MTL files that it references. Material libraries are resolved
+/// relative to the directory the OBJ file is in.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ObjVec<f>, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+
+    parse(&path.display().to_string(), &source, |name| {
+        let mtl_path = dir.join(name);
+        let mtl_source = read_file(&mtl_path)?;
+        parse_mtl(&mtl_path.display().to_string(), &mtl_source)
+    })
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// A helper to make parse errors more concise to create
+fn parse_error(file: &str, line: usize, message: String) -> ObjError {
+    ObjError::Parse {
+        file: file.to_string(),
+        line,
+        message,
+    }
+}
+
+/// Parse up to `count` of the remaining tokens of a statement as floats, requiring at least `min`
+/// of them. Missing optional components are filled in with zero, and any extra trailing components
+/// (such as the optional `w` coordinate) are ignored.
+fn parse_floats<'a, I>(
+    file: &str,
+    line: usize,
+    tokens: I,
+    count: usize,
+    min: usize,
+) -> Result<Vec<f>, ObjError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut values = Vec::with_capacity(count);
+
+    for token in tokens.take(count) {
+        let value = token.parse::<f>().map_err(|_| {
+            parse_error(
+                file,
+                line,
+                format!("expected a number, found \"{}\"", token),
+            )
+        })?;
+        values.push(value);
+    }
+
+    if values.len() < min {
+        return Err(parse_error(
+            file,
+            line,
+            format!("expected {} numbers, found {}", min, values.len()),
+        ));
+    }
+    values.resize(count, 0.0);
+    Ok(values)
+}
+
+/// Parse the source of an MTL file into a map of material names to materials
+pub fn parse_mtl(file: &str, source: &str) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+
+            if name.is_empty() {
+                return Err(parse_error(file, line_no, "missing material name".into()));
+            }
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((name, MtlMaterial::default()));
+            continue;
+        }
+        let material = match current.as_mut() {
+            Some((_, material)) => material,
+            None => {
+                return Err(parse_error(
+                    file,
+                    line_no,
+                    format!("\"{}\" appears before any \"newmtl\"", keyword),
+                ))
+            }
+        };
+
+        match keyword {
+            "Kd" | "Ks" | "Ke" | "Tf" => {
+                let v = parse_floats(file, line_no, tokens, 3, 3)?;
+                let color = Color3f::new(v[0], v[1], v[2]);
+
+                match keyword {
+                    "Kd" => material.diffuse = color,
+                    "Ks" => material.specular = color,
+                    "Ke" => material.emission = color,
+                    _ => material.transmission = color,
+                }
+            }
+            "Ns" => material.shininess = parse_floats(file, line_no, tokens, 1, 1)?[0],
+            "Ni" => {
+                material.ior = match parse_floats(file, line_no, tokens, 1, 1)?[0] {
+                    ior if ior > 0.0 => ior,
+                    ior => {
+                        return Err(parse_error(
+                            file,
+                            line_no,
+                            format!("the index of refraction must be positive, found {}", ior),
+                        ))
+                    }
+                };
+            }
+            "illum" => {
+                let token = tokens.next().unwrap_or("");
+                material.illum = token.parse().map_err(|_| {
+                    parse_error(
+                        file,
+                        line_no,
+                        format!("invalid illumination model \"{}\"", token),
+                    )
+                })?;
+            }
+            _ => debug!("{}:{}: ignoring \"{}\"", file, line_no, keyword),
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+/// A single corner of a face, with each index already resolved to a 0-based offset
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// The triangles that share a material, before they are converted into a mesh
+#[derive(Default)]
+struct Group {
+    triangles: Vec<[FaceVertex; 3]>,
+}
+
+/// Resolve an OBJ index, which is 1-based if positive and relative to the end of the list if
+/// negative, into a 0-based index
+fn resolve_index(
+    file: &str,
+    line: usize,
+    token: &str,
+    len: usize,
+    kind: &str,
+) -> Result<usize, ObjError> {
+    let idx: i64 = token
+        .parse()
+        .map_err(|_| parse_error(file, line, format!("invalid {} index \"{}\"", kind, token)))?;
+    let resolved = if idx > 0 { idx - 1 } else { len as i64 + idx };
+
+    if idx == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(parse_error(
+            file,
+            line,
+            format!(
+                "{} index {} is out of range (there are {} defined)",
+                kind, idx, len
+            ),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// Parse the source of an OBJ file. `load_mtl` is called with the name of every material library
+/// that the file references.
+pub fn parse<F>(file: &str, source: &str, mut load_mtl: F) -> Result<ObjVec<f>, ObjError>
+where
+    F: FnMut(&str) -> Result<HashMap<String, MtlMaterial>, ObjError>,
+{
+    let mut positions: Vec<Point3f> = Vec::new();
+    let mut uvs: Vec<Vector2f> = Vec::new();
+    let mut normals: Vec<Vector3f> = Vec::new();
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+
+    // Faces are grouped by the material they use. `None` is for faces that were declared before
+    // any `usemtl` statement.
+    let mut groups: Vec<(Option<String>, Group)> = vec![(None, Group::default())];
+    let mut current_group = 0;
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(file, line_no, tokens, 3, 3)?;
+                positions.push(Point3f::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = parse_floats(file, line_no, tokens, 2, 1)?;
+                uvs.push(Vector2f::new(v[0], v[1]));
+            }
+            "vn" => {
+                let v = parse_floats(file, line_no, tokens, 3, 3)?;
+                let normal = Vector3f::new(v[0], v[1], v[2]);
+
+                if normal.norm_squared() == 0.0 {
+                    return Err(parse_error(
+                        file,
+                        line_no,
+                        "a vertex normal can't have a length of zero".to_string(),
+                    ));
+                }
+                normals.push(normal.normalize());
+            }
+            "f" => {
+                let mut vertices = Vec::new();
+
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let position = resolve_index(
+                        file,
+                        line_no,
+                        parts.next().unwrap_or(""),
+                        positions.len(),
+                        "vertex",
+                    )?;
+                    let uv = match parts.next() {
+                        Some(t) if !t.is_empty() => {
+                            Some(resolve_index(file, line_no, t, uvs.len(), "texture")?)
+                        }
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(t) if !t.is_empty() => {
+                            Some(resolve_index(file, line_no, t, normals.len(), "normal")?)
+                        }
+                        _ => None,
+                    };
+
+                    if parts.next().is_some() {
+                        return Err(parse_error(
+                            file,
+                            line_no,
+                            format!("malformed face vertex \"{}\"", token),
+                        ));
+                    }
+                    vertices.push(FaceVertex {
+                        position,
+                        uv,
+                        normal,
+                    });
+                }
+
+                if vertices.len() < 3 {
+                    return Err(parse_error(
+                        file,
+                        line_no,
+                        format!("a face needs at least 3 vertices, found {}", vertices.len()),
+                    ));
+                }
+
+                // Triangulate the polygon as a fan around the first vertex, which is correct for
+                // the convex polygons that modelling tools export. Triangles with no area have no
+                // normal, so they're left out.
+                let group = &mut groups[current_group].1;
+                for i in 1..(vertices.len() - 1) {
+                    let triangle = [vertices[0], vertices[i], vertices[i + 1]];
+                    let [p0, p1, p2] = [
+                        positions[triangle[0].position],
+                        positions[triangle[1].position],
+                        positions[triangle[2].position],
+                    ];
+
+                    if (p1 - p0).cross(&(p2 - p0)).norm_squared() == 0.0 {
+                        warn!("{}:{}: skipping a degenerate triangle", file, line_no);
+                        continue;
+                    }
+                    group.triangles.push(triangle);
+                }
+            }
+            "mtllib" => {
+                for name in tokens {
+                    materials.extend(load_mtl(name)?);
+                }
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+
+                if !materials.contains_key(&name) {
+                    return Err(parse_error(
+                        file,
+                        line_no,
+                        format!("material \"{}\" has not been defined", name),
+                    ));
+                }
+                current_group = match groups.iter().position(|(n, _)| n.as_ref() == Some(&name)) {
+                    Some(i) => i,
+                    None => {
+                        groups.push((Some(name), Group::default()));
+                        groups.len() - 1
+                    }
+                };
+            }
+            _ => debug!("{}:{}: ignoring \"{}\"", file, line_no, keyword),
+        }
+    }
+
+    let default_material = MtlMaterial::default();
+    let mut objects: ObjVec<f> = Vec::new();
+
+    for (name, group) in groups {
+        if group.triangles.is_empty() {
+            continue;
+        }
+        let material = name
+            .as_ref()
+            .and_then(|name| materials.get(name))
+            .unwrap_or(&default_material);
+        let mesh = build_mesh(&group, &positions, &uvs, &normals);
+
+        for triangle in mesh.into_triangles() {
+            objects.push(ObjRef {
+                hittable: Box::new(triangle),
+                material: material.bsdf(),
+            });
+        }
+    }
+    Ok(objects)
+}
+
+/// Convert a group of triangles into a mesh, de-duplicating vertices that share the same
+/// position, UV and normal indices.
+fn build_mesh(
+    group: &Group,
+    positions: &[Point3f],
+    uvs: &[Vector2f],
+    normals: &[Vector3f],
+) -> TriangleMesh<f> {
+    let has_normals = group
+        .triangles
+        .iter()
+        .any(|tri| tri.iter().any(|v| v.normal.is_some()));
+    let has_uvs = group
+        .triangles
+        .iter()
+        .any(|tri| tri.iter().any(|v| v.uv.is_some()));
+
+    let mut mesh = TriangleMesh {
+        positions: Vec::new(),
+        indices: Vec::with_capacity(group.triangles.len()),
+        normals: if has_normals { Some(Vec::new()) } else { None },
+        uvs: if has_uvs { Some(Vec::new()) } else { None },
+    };
+    let mut lookup: HashMap<FaceVertex, usize> = HashMap::new();
+
+    for tri in &group.triangles {
+        // If only some faces have normals, the faces without them get the face normal at each of
+        // their vertices. Those vertices can't be shared with other faces.
+        let face_normal = if has_normals && tri.iter().any(|v| v.normal.is_none()) {
+            let [p0, p1, p2] = [
+                positions[tri[0].position],
+                positions[tri[1].position],
+                positions[tri[2].position],
+            ];
+            Some((p1 - p0).cross(&(p2 - p0)).normalize())
+        } else {
+            None
+        };
+        let mut indices = [0; 3];
+
+        for (corner, vertex) in tri.iter().enumerate() {
+            let shared = face_normal.is_none() || vertex.normal.is_some();
+
+            if shared {
+                if let Some(&idx) = lookup.get(vertex) {
+                    indices[corner] = idx;
+                    continue;
+                }
+            }
+            let idx = mesh.positions.len();
+            mesh.positions.push(positions[vertex.position]);
+
+            if let Some(mesh_normals) = mesh.normals.as_mut() {
+                let normal = vertex.normal.map(|n| normals[n]).or(face_normal).unwrap();
+                mesh_normals.push(normal);
+            }
+            if let Some(mesh_uvs) = mesh.uvs.as_mut() {
+                mesh_uvs.push(
+                    vertex
+                        .uv
+                        .map_or_else(|| Vector2f::new(0.0, 0.0), |t| uvs[t]),
+                );
+            }
+            if shared {
+                lookup.insert(*vertex, idx);
+            }
+            indices[corner] = idx;
+        }
+        mesh.indices.push(indices);
+    }
+    mesh
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittable::any_hit;
+    use crate::ray::Ray;
+
+    fn no_mtl(name: &str) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+        panic!("unexpected material library {}", name);
+    }
+
+    fn down_ray(x: f, y: f) -> Ray3f {
+        Ray::new(&Vector3f::new(x, y, 1.0), &Vector3f::new(0.0, 0.0, -1.0))
+    }
+
+    /// Assert that parsing fails with a parse error on a particular line
+    fn assert_parse_error(result: Result<ObjVec<f>, ObjError>, expected_line: usize) {
+        match result {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, expected_line),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    const QUAD: &str = "
+# a unit quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3 4
+";
+
+    #[test]
+    fn test_triangulates_polygons() {
+        let objects = parse("quad.obj", QUAD, no_mtl).unwrap();
+        assert_eq!(objects.len(), 2);
+
+        let (hr, _) = any_hit(&objects, &down_ray(0.25, 0.75), Some(0.001), None).unwrap();
+        assert_eq!(hr.t, 1.0);
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+        assert!(any_hit(&objects, &down_ray(0.75, 0.25), Some(0.001), None).is_some());
+        assert!(any_hit(&objects, &down_ray(1.5, 0.25), Some(0.001), None).is_none());
+    }
+
+    #[test]
+    fn test_skips_degenerate_triangles() {
+        // the first face repeats a vertex and the second one is a line, so only the last face is
+        // left
+        let source = "
+v 0 0 0
+v 1 0 0
+v 2 0 0
+v 1 1 0
+f 1 1 2
+f 1 2 3
+f 1 2 4
+";
+        let objects = parse("degenerate.obj", source, no_mtl).unwrap();
+        assert_eq!(objects.len(), 1);
+
+        let (hr, _) = any_hit(&objects, &down_ray(0.9, 0.5), Some(0.001), None).unwrap();
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_negative_indices() {
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3 -2 -1
+";
+        let objects = parse("neg.obj", source, no_mtl).unwrap();
+        assert_eq!(objects.len(), 1);
+        assert!(any_hit(&objects, &down_ray(0.25, 0.25), Some(0.001), None).is_some());
+    }
+
+    #[test]
+    fn test_normals_and_uvs() {
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 1 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 2
+f 1/1/1 2/2/1 3/3/1
+f 2//1 4//1 3//1
+";
+        let objects = parse("attrs.obj", source, no_mtl).unwrap();
+        assert_eq!(objects.len(), 2);
+
+        let (hr, _) = any_hit(&objects, &down_ray(0.25, 0.5), Some(0.001), None).unwrap();
+        assert_eq!(hr.shading_normal, Vector3f::new(0.0, 0.0, 1.0));
+        assert!((hr.uv - Vector2f::new(0.25, 0.5)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn test_missing_normals() {
+        // the second face has no normals, and is wound clockwise, so it should be flat shaded
+        // with its own face normal (which is hit from behind) while the first face keeps its
+        // vertex normals
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 1 1 0
+vn 0 0 1
+f 1//1 2//1 3//1
+f 2 3 4
+";
+        let objects = parse("mixed.obj", source, no_mtl).unwrap();
+        let (hr, _) = any_hit(&objects, &down_ray(0.75, 0.75), Some(0.001), None).unwrap();
+        assert!(!hr.front_face);
+        assert_eq!(hr.shading_normal, Vector3f::new(0.0, 0.0, 1.0));
+
+        let (hr, _) = any_hit(&objects, &down_ray(0.25, 0.25), Some(0.001), None).unwrap();
+        assert!(hr.front_face);
+        assert_eq!(hr.shading_normal, Vector3f::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_materials() {
+        let source = "
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl red
+f 1 2 3
+usemtl chrome
+f 1 2 3
+";
+        let mtl = "
+newmtl red
+Kd 1 0 0
+
+newmtl chrome
+Ks 0.9 0.9 0.9
+Ns 1000
+illum 3
+
+newmtl glass
+Ni 1.5
+Tf 0.9 1 0.9
+illum 7
+
+newmtl light
+Ke 10 10 8
+";
+        let mut requested = Vec::new();
+        let objects = parse("mat.obj", source, |name| {
+            requested.push(name.to_string());
+            parse_mtl(name, mtl)
+        })
+        .unwrap();
+        assert_eq!(requested, vec!["scene.mtl".to_string()]);
+        assert_eq!(objects.len(), 2);
+
+        let materials = parse_mtl("scene.mtl", mtl).unwrap();
+        assert_eq!(materials["red"].diffuse, Color3f::new(1.0, 0.0, 0.0));
+        assert_eq!(materials["red"].illum, 2);
+        assert_eq!(materials["chrome"].specular, Color3f::new(0.9, 0.9, 0.9));
+        assert_eq!(materials["chrome"].shininess, 1000.0);
+        assert_eq!(materials["chrome"].illum, 3);
+        assert_eq!(materials["glass"].ior, 1.5);
+        assert_eq!(materials["glass"].transmission, Color3f::new(0.9, 1.0, 0.9));
+        assert_eq!(materials["light"].emission, Color3f::new(10.0, 10.0, 8.0));
+        assert_eq!(materials["red"].emission, Color3f::zeros());
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n";
+        assert_parse_error(parse("bad.obj", source, no_mtl), 4);
+
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 0 2\n";
+        assert_parse_error(parse("bad.obj", source, no_mtl), 4);
+
+        let source = "v 0 0 0\nv 1 0 0\nf 1 2\n";
+        assert_parse_error(parse("bad.obj", source, no_mtl), 3);
+
+        let source = "v 0 zero 0\n";
+        assert_parse_error(parse("bad.obj", source, no_mtl), 1);
+
+        let source = "v 0 0\n";
+        assert_parse_error(parse("bad.obj", source, no_mtl), 1);
+
+        let source = "v 0 0 0\nvn 0 0 1\nvn 0 0 0\n";
+        assert_parse_error(parse("bad.obj", source, no_mtl), 3);
+
+        let source = "v 0 0 0\nusemtl missing\n";
+        assert_parse_error(parse("bad.obj", source, no_mtl), 2);
+
+        match parse_mtl("bad.mtl", "Kd 1 1 1\n") {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("expected a parse error"),
+        }
+
+        for ior in &["0", "-1.5"] {
+            match parse_mtl("bad.mtl", &format!("newmtl glass\nNi {}\n", ior)) {
+                Err(ObjError::Parse { line, .. }) => assert_eq!(line, 2),
+                _ => panic!("expected a parse error"),
+            }
+        }
+
+        let err = parse("bad.obj", "v 0 0 0\nf 1 1 9\n", no_mtl)
+            .err()
+            .unwrap();
+        assert!(err.to_string().starts_with("bad.obj:2:"));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        match load("/this/file/does/not/exist.obj") {
+            Err(ObjError::Io { .. }) => (),
+            _ => panic!("expected an IO error"),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! A Portable Float Map (PFM) encoder and decoder
+//!
+//! PFM files store uncompressed 32 bit floats for each channel, so they are lossless. The header
+//! is a line with `PF` for color images or `Pf` for grayscale images, the width and height, and a
+//! scale factor whose sign gives the byte order of the data: negative for little endian and
+//! positive for big endian. Rows are stored from the bottom of the image to the top.
+
+use crate::raster::Raster;
+use crate::typedefs::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Write an image as a little endian, color PFM file
+pub fn write<W: Write>(out: &mut W, image: &Raster) -> io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+
+    for row in image.pixels.chunks(image.width.max(1)).rev() {
+        for color in row {
+            for component in color.iter() {
+                out.write_all(&component.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read a PFM file, in either byte order. Grayscale images are converted to color by copying
+/// their value to every channel. The magnitude of the scale factor is ignored, as it is by most
+/// other tools.
+pub fn read<R: BufRead>(input: &mut R) -> io::Result<Raster> {
+    // the header is four whitespace separated tokens, which are usually on three lines
+    let mut tokens = Vec::new();
+
+    while tokens.len() < 4 {
+        let mut line = Vec::new();
+
+        if input.read_until(b'\n', &mut line)? == 0 {
+            return Err(invalid("the PFM header ended unexpectedly"));
+        }
+        let line = String::from_utf8_lossy(&line).into_owned();
+        tokens.extend(line.split_whitespace().map(str::to_string));
+    }
+    let channels = match tokens[0].as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid("the file isn't a PFM file")),
+    };
+    let number = |token: &str| {
+        token
+            .parse::<usize>()
+            .map_err(|_| invalid("the PFM file has an invalid resolution"))
+    };
+    let width = number(&tokens[1])?;
+    let height = number(&tokens[2])?;
+    let scale = tokens[3]
+        .parse::<f>()
+        .map_err(|_| invalid("the PFM file has an invalid scale"))?;
+    let little_endian = scale < 0.0;
+
+    if !Raster::is_loadable(width, height) {
+        return Err(invalid("the PFM file's resolution is too large"));
+    }
+    let value = |b: &[u8]| {
+        let bytes = [b[0], b[1], b[2], b[3]];
+
+        if little_endian {
+            f::from_le_bytes(bytes)
+        } else {
+            f::from_be_bytes(bytes)
+        }
+    };
+
+    // the image is read a row at a time, so that a file that's shorter than its header says
+    // fails before the memory for the whole image is allocated
+    let mut row = vec![0; width * channels * 4];
+    let mut pixels = Vec::new();
+
+    for _ in 0..height {
+        input.read_exact(&mut row)?;
+        pixels.extend(row.chunks(channels * 4).map(|pixel| {
+            if channels == 3 {
+                Color3f::new(
+                    value(&pixel[0..4]),
+                    value(&pixel[4..8]),
+                    value(&pixel[8..12]),
+                )
+            } else {
+                Color3f::repeat(value(pixel))
+            }
+        }));
+    }
+
+    // the rows are stored from the bottom of the image to the top
+    let pixels = pixels
+        .chunks(width.max(1))
+        .rev()
+        .flatten()
+        .cloned()
+        .collect();
+    Ok(Raster {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Save an image to a PFM file
+pub fn save<P: AsRef<Path>>(path: P, image: &Raster) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out, image)?;
+    out.flush()
+}
+
+/// Load an image from a PFM file
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Raster> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut image = Raster::new(3, 2);
+        image.set(0, 0, Color3f::new(1.0, 2.0, 3.0));
+        image.set(2, 1, Color3f::new(-0.5, 1e20, f::INFINITY));
+        image.set(1, 1, Color3f::new(0.1, 0.2, 0.3));
+
+        let mut data = Vec::new();
+        write(&mut data, &image).unwrap();
+        assert!(data.starts_with(b"PF\n3 2\n-1.0\n"));
+        assert_eq!(data.len(), 12 + 3 * 2 * 3 * 4);
+
+        // the bottom row is stored first
+        assert_eq!(data[12..16], (0.0 as f).to_le_bytes());
+        assert_eq!(data[36..40], (-0.5 as f).to_le_bytes());
+        assert_eq!(read(&mut data.as_slice()).unwrap(), image);
+    }
+
+    #[test]
+    fn test_read_big_endian_grayscale() {
+        let mut data = b"Pf\n2\n1\n1.0\n".to_vec();
+        data.extend_from_slice(&(0.25 as f).to_be_bytes());
+        data.extend_from_slice(&(8.0 as f).to_be_bytes());
+
+        let image = read(&mut data.as_slice()).unwrap();
+        assert_eq!(
+            image.pixels,
+            vec![Color3f::repeat(0.25), Color3f::repeat(8.0)]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let files: &[&[u8]] = &[
+            b"P6\n1 1\n255\n\0\0\0",
+            b"PF\n1 1\n",
+            b"PF\n1 x\n-1.0\n",
+            b"PF\n1 1\n-1.0\n\0\0\0\0",
+            b"PF\n999999999 999999999\n-1\n",
+            b"PF\n18446744073709551615 2\n-1\n",
+            b"PF\n1000 1000\n-1\n\0\0\0\0",
+        ];
+
+        for file in files {
+            assert!(read(&mut &file[..]).is_err());
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use super::triangle::{self, bounds, intersect};
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable, SurfaceSample};
+use crate::na::{Matrix, RealField, Vector2, Vector3};
+use crate::ray::Ray;
+use std::sync::Arc;
+
+/// An indexed triangle mesh
+///
+/// Every triangle is described by three indices into the vertex arrays. Per-vertex normals and
+/// UV coordinates are optional, but if they're supplied they must have one entry per position.
+/// `new` checks this, along with the indices, for meshes that come from outside of the program.
+/// Vertex normals are interpolated across each face to produce smooth shading normals.
+///
+/// A mesh can be used directly as a `Hittable`, which tests every triangle in the mesh. For
+/// anything but tiny meshes you should use `into_triangles` instead, which produces a separate
+/// primitive for each face that can be placed into an acceleration structure while still sharing
+/// the vertex data.
+#[derive(Clone, Debug)]
+pub struct TriangleMesh<N: RealField> {
+    /// The position of each vertex
+    pub positions: Vec<Vector3<N>>,
+
+    /// The vertex indices for each triangle
+    pub indices: Vec<[usize; 3]>,
+
+    /// The (optional) normal at each vertex
+    pub normals: Option<Vec<Vector3<N>>>,
+
+    /// The (optional) UV coordinate of each vertex
+    pub uvs: Option<Vec<Vector2<N>>>,
+}
+
+/// A reference to a single triangle inside of a shared `TriangleMesh`
+#[derive(Clone, Debug)]
+pub struct MeshTriangle<N: RealField> {
+    /// The mesh that the triangle belongs to
+    pub mesh: Arc<TriangleMesh<N>>,
+
+    /// The index of the triangle in the mesh's index list
+    pub index: usize,
+}
+
+impl<N: RealField> TriangleMesh<N> {
+    /// Create a mesh, checking that every index refers to a vertex and that the normals and UV
+    /// coordinates, if there are any, have one entry per position
+    pub fn new(
+        positions: Vec<Vector3<N>>,
+        indices: Vec<[usize; 3]>,
+        normals: Option<Vec<Vector3<N>>>,
+        uvs: Option<Vec<Vector2<N>>>,
+    ) -> Result<Self, String> {
+        if let Some(i) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+            return Err(format!(
+                "vertex index {} is out of range for {} vertices",
+                i,
+                positions.len()
+            ));
+        }
+        if let Some(normals) = &normals {
+            if normals.len() != positions.len() {
+                return Err(format!(
+                    "the mesh has {} normals for {} vertices",
+                    normals.len(),
+                    positions.len()
+                ));
+            }
+        }
+        if let Some(uvs) = &uvs {
+            if uvs.len() != positions.len() {
+                return Err(format!(
+                    "the mesh has {} UV coordinates for {} vertices",
+                    uvs.len(),
+                    positions.len()
+                ));
+            }
+        }
+        Ok(Self {
+            positions,
+            indices,
+            normals,
+            uvs,
+        })
+    }
+
+    /// Split the mesh into a primitive for each of its triangles
+    pub fn into_triangles(self) -> Vec<MeshTriangle<N>> {
+        let mesh = Arc::new(self);
+        (0..mesh.indices.len())
+            .map(|index| MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                index,
+            })
+            .collect()
+    }
+
+    /// Intersect the ray with a single triangle of the mesh
+    fn hit_triangle(&self, index: usize, ray: &Ray<N>, t_min: N, t_max: N) -> Option<HitRecord<N>> {
+        let [i0, i1, i2] = self.indices[index];
+        let (p0, p1, p2) = (
+            &self.positions[i0],
+            &self.positions[i1],
+            &self.positions[i2],
+        );
+        let (t, b1, b2) = intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let b0 = N::one() - b1 - b2;
+        let mut normal = (p1 - p0).cross(&(p2 - p0)).normalize();
+
+        let shading_normal = match &self.normals {
+            Some(normals) => {
+                let n = normals[i0] * b0 + normals[i1] * b1 + normals[i2] * b2;
+                let length = n.norm();
+
+                // Vertex normals that point in opposite directions can cancel out, and then
+                // there's no direction to shade with, so use the geometric normal instead
+                if length > N::default_epsilon() {
+                    let n = n / length;
+
+                    // Keep the geometric normal on the same side of the surface as the shading
+                    // normal, since the winding order of the mesh may not agree with the normals
+                    if Matrix::dot(&n, &normal) < N::zero() {
+                        normal = -normal;
+                    }
+                    n
+                } else {
+                    normal
+                }
+            }
+            None => normal,
+        };
+        let uv = match &self.uvs {
+            Some(uvs) => uvs[i0] * b0 + uvs[i1] * b1 + uvs[i2] * b2,
+            None => Vector2::new(b1, b2),
+        };
+
+        let hr = HitRecord {
+            t,
+            p: ray.point_at_param(t),
+            normal,
+            shading_normal,
+            uv,
+            front_face: true,
+        };
+        Some(hr.face_forward(&ray.direction))
+    }
+
+    /// The positions of the vertices of a single triangle of the mesh
+    fn triangle_positions(&self, index: usize) -> [&Vector3<N>; 3] {
+        let [i0, i1, i2] = self.indices[index];
+        [
+            &self.positions[i0],
+            &self.positions[i1],
+            &self.positions[i2],
+        ]
+    }
+
+    /// The bounding box of a single triangle of the mesh
+    fn triangle_bounds(&self, index: usize) -> AABB<N> {
+        let [i0, i1, i2] = self.indices[index];
+        bounds(
+            &self.positions[i0],
+            &self.positions[i1],
+            &self.positions[i2],
+        )
+    }
+}
+
+impl<N: RealField> Hittable for TriangleMesh<N> {
+    type NumType = N;
+
+    fn hit(
+        &self,
+        ray: &Ray<Self::NumType>,
+        t_min: Self::NumType,
+        t_max: Self::NumType,
+    ) -> Option<HitRecord<Self::NumType>> {
+        let mut closest = None;
+        let mut closest_t = t_max;
+
+        for index in 0..self.indices.len() {
+            if let Some(hit_record) = self.hit_triangle(index, ray, t_min, closest_t) {
+                closest_t = hit_record.t;
+                closest = Some(hit_record);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<AABB<Self::NumType>> {
+        (0..self.indices.len())
+            .map(|index| self.triangle_bounds(index))
+            .fold(None, |acc: Option<AABB<N>>, bb| match acc {
+                Some(acc) => Some(acc.union(&bb)),
+                None => Some(bb),
+            })
+    }
+}
+
+impl<N: RealField> Hittable for MeshTriangle<N> {
+    type NumType = N;
+
+    fn hit(
+        &self,
+        ray: &Ray<Self::NumType>,
+        t_min: Self::NumType,
+        t_max: Self::NumType,
+    ) -> Option<HitRecord<Self::NumType>> {
+        self.mesh.hit_triangle(self.index, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<AABB<Self::NumType>> {
+        Some(self.mesh.triangle_bounds(self.index))
+    }
+
+    fn sample(&self, reference: &Vector3<N>, u: &Vector2<N>) -> Option<SurfaceSample<N>> {
+        triangle::sample(self.mesh.triangle_positions(self.index), reference, u)
+    }
+
+    fn pdf(&self, reference: &Vector3<N>, direction: &Vector3<N>) -> N {
+        triangle::pdf(
+            self.mesh.triangle_positions(self.index),
+            reference,
+            direction,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::typedefs::*;
+
+    /// A unit square in the xy plane made out of two triangles
+    fn quad(normals: Option<Vec<Vector3f>>) -> TriangleMesh<f> {
+        TriangleMesh::new(
+            vec![
+                Vector3f::new(0.0, 0.0, 0.0),
+                Vector3f::new(1.0, 0.0, 0.0),
+                Vector3f::new(1.0, 1.0, 0.0),
+                Vector3f::new(0.0, 1.0, 0.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+            normals,
+            Some(vec![
+                Vector2f::new(0.0, 0.0),
+                Vector2f::new(1.0, 0.0),
+                Vector2f::new(1.0, 1.0),
+                Vector2f::new(0.0, 1.0),
+            ]),
+        )
+        .unwrap()
+    }
+
+    fn down_ray(x: f, y: f) -> Ray3f {
+        Ray::new(&Vector3f::new(x, y, 1.0), &Vector3f::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn test_mesh_hit() {
+        let mesh = quad(None);
+        let hr = mesh.hit(&down_ray(0.25, 0.75), 0.001, f::MAX).unwrap();
+        assert_eq!(hr.t, 1.0);
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+        assert_eq!(hr.shading_normal, hr.normal);
+        assert!((hr.uv - Vector2f::new(0.25, 0.75)).norm() < 1e-6);
+
+        assert!(mesh.hit(&down_ray(1.5, 0.5), 0.001, f::MAX).is_none());
+    }
+
+    #[test]
+    fn test_interpolated_normals() {
+        // the normals on the left edge point left, and the normals on the right edge point right,
+        // so the shading normal should tilt smoothly across the quad
+        let left = Vector3f::new(-1.0, 0.0, 1.0).normalize();
+        let right = Vector3f::new(1.0, 0.0, 1.0).normalize();
+        let mesh = quad(Some(vec![left, right, right, left]));
+
+        let hr = mesh.hit(&down_ray(0.5, 0.25), 0.001, f::MAX).unwrap();
+        assert!((hr.shading_normal - Vector3f::new(0.0, 0.0, 1.0)).norm() < 1e-6);
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+
+        let hr = mesh.hit(&down_ray(0.9, 0.1), 0.001, f::MAX).unwrap();
+        assert!(hr.shading_normal.x > 0.0);
+        assert!((hr.shading_normal.norm() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_cancelling_normals() {
+        // the vertex normals of the first triangle cancel out at this point, so the geometric
+        // normal is used for shading
+        let x = Vector3f::new(1.0, 0.0, 0.0);
+        let mesh = quad(Some(vec![x, -x, -x, x]));
+        let hr = mesh.hit(&down_ray(0.5, 0.25), 0.001, f::MAX).unwrap();
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+        assert_eq!(hr.shading_normal, hr.normal);
+    }
+
+    #[test]
+    fn test_invalid_mesh() {
+        let positions = vec![Vector3f::zeros(); 3];
+        assert!(TriangleMesh::new(positions.clone(), vec![[0, 1, 3]], None, None).is_err());
+
+        let normals = Some(vec![Vector3f::new(0.0, 0.0, 1.0); 2]);
+        assert!(TriangleMesh::new(positions.clone(), vec![[0, 1, 2]], normals, None).is_err());
+
+        let uvs = Some(vec![Vector2f::zeros(); 4]);
+        assert!(TriangleMesh::new(positions.clone(), vec![[0, 1, 2]], None, uvs).is_err());
+
+        assert!(TriangleMesh::new(positions, vec![[0, 1, 2]], None, None).is_ok());
+    }
+
+    #[test]
+    fn test_geometric_normal_follows_shading_normal() {
+        // the vertex normals point the opposite way of the winding order, so they decide which
+        // side is the outside, and a ray from above hits the back face
+        let down = Vector3f::new(0.0, 0.0, -1.0);
+        let mesh = quad(Some(vec![down; 4]));
+        let hr = mesh.hit(&down_ray(0.5, 0.25), 0.001, f::MAX).unwrap();
+        assert!(!hr.front_face);
+        assert_eq!(hr.normal, -down);
+        assert_eq!(hr.shading_normal, -down);
+    }
+
+    #[test]
+    fn test_into_triangles() {
+        let mesh = quad(None);
+        let bounds = mesh.bounding_box().unwrap();
+        assert_eq!(bounds.min, Vector3f::new(0.0, 0.0, 0.0));
+        assert_eq!(bounds.max, Vector3f::new(1.0, 1.0, 0.0));
+
+        let triangles = mesh.clone().into_triangles();
+        assert_eq!(triangles.len(), 2);
+
+        // every point on the quad is covered by exactly one of the triangles, and agrees with the
+        // mesh as a whole
+        for &(x, y) in &[(0.75, 0.25), (0.25, 0.75)] {
+            let ray = down_ray(x, y);
+            let hits: Vec<_> = triangles
+                .iter()
+                .filter_map(|tri| tri.hit(&ray, 0.001, f::MAX))
+                .collect();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(Some(hits[0]), mesh.hit(&ray, 0.001, f::MAX));
+        }
+    }
+}
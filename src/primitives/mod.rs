@@ -1 +1,3 @@
+pub mod mesh;
 pub mod sphere;
+pub mod triangle;
@@ -1,7 +1,9 @@
-use crate::hittable::{HitRecord, Hittable};
-use crate::na::{Matrix, RealField, Vector3};
+use crate::aabb::AABB;
+use crate::common::coordinate_system;
+use crate::hittable::{area_to_solid_angle, HitRecord, Hittable, SurfaceSample};
+use crate::na::{Matrix, RealField, Vector2, Vector3};
 use crate::ray::Ray;
-use log::info;
+use crate::sample::{uniform_cone, uniform_cone_pdf, uniform_sphere};
 use num::FromPrimitive;
 
 /// Contains the relevant information for a sphere primitive
@@ -11,25 +13,256 @@ pub struct Sphere<N: RealField> {
     pub center: Vector3<N>,
 }
 
+impl<N: RealField> Sphere<N> {
+    /// Compute the spherical coordinates of a point on the unit sphere, mapped to `[0, 1]`. `u`
+    /// is the angle around the y axis and `v` is the angle from the bottom of the sphere.
+    fn uv(normal: &Vector3<N>) -> Vector2<N> {
+        let theta = num::clamp(-normal.y, -N::one(), N::one()).acos();
+        let phi = (-normal.z).atan2(normal.x) + N::pi();
+        Vector2::new(phi / N::two_pi(), theta / N::pi())
+    }
+
+    /// The cosine of the angle between the direction to the center of the sphere and the edge of
+    /// the sphere, as seen from `reference`. Returns `None` if the point is inside the sphere.
+    fn cos_max(&self, reference: &Vector3<N>) -> Option<N> {
+        let sin2_max = self.radius * self.radius / (self.center - reference).norm_squared();
+
+        if sin2_max >= N::one() {
+            return None;
+        }
+        Some((N::one() - sin2_max).sqrt())
+    }
+
+    /// The density of a point from sampling the whole sphere uniformly, with respect to area
+    fn area_pdf(&self) -> N {
+        N::one() / ((N::two_pi() + N::two_pi()) * self.radius * self.radius)
+    }
+}
+
 // This could be more generic, but even if it was, it would be generic over float primitives,
 // which would require me to implement traits over primitive types, which is not recommended
 // by Rust best practices.
 impl<N: RealField + FromPrimitive> Hittable for Sphere<N> {
     type NumType = N;
 
-    fn hit(&self, ray: &Ray<Self::NumType>) -> Option<HitRecord<Self::NumType>> {
+    fn hit(
+        &self,
+        ray: &Ray<Self::NumType>,
+        t_min: Self::NumType,
+        t_max: Self::NumType,
+    ) -> Option<HitRecord<Self::NumType>> {
         let oc = ray.origin - self.center;
         let a = Matrix::norm_squared(&ray.direction);
         let b = Matrix::dot(&oc, &ray.direction);
         let c = Matrix::norm_squared(&oc) - self.radius.powi(2);
         let discriminant = b.powi(2) - (a * c);
-        let t = (-b - discriminant.sqrt()) / a;
 
-        if discriminant >= N::from_f32(0.0).unwrap() {
-            let p = ray.point_at_param(t);
-            let normal = (p - self.center).map(|n| n / self.radius);
-            return Some(HitRecord { t, p, normal });
+        if discriminant < N::from_f32(0.0).unwrap() {
+            return None;
+        }
+        let sqrt_d = discriminant.sqrt();
+
+        // The near root is preferred, but if it's out of range (behind the origin, for example)
+        // then the far root may still be valid
+        let roots = [(-b - sqrt_d) / a, (-b + sqrt_d) / a];
+        let t = roots.iter().cloned().find(|&t| t >= t_min && t <= t_max)?;
+        let p = ray.point_at_param(t);
+        let normal = (p - self.center).map(|n| n / self.radius);
+        let hr = HitRecord {
+            t,
+            p,
+            normal,
+            shading_normal: normal,
+            uv: Self::uv(&normal),
+            front_face: true,
+        };
+        Some(hr.face_forward(&ray.direction))
+    }
+
+    fn bounding_box(&self) -> Option<AABB<Self::NumType>> {
+        let r = Vector3::new(self.radius, self.radius, self.radius);
+        Some(AABB::new(&(self.center - r), &(self.center + r)))
+    }
+
+    /// Points outside of the sphere sample the cone of directions that the sphere covers, which
+    /// only generates points on the side of the sphere that faces them. Points inside of the
+    /// sphere see all of it, so they sample its area uniformly.
+    fn sample(&self, reference: &Vector3<N>, u: &Vector2<N>) -> Option<SurfaceSample<N>> {
+        let cos_max = match self.cos_max(reference) {
+            Some(cos_max) => cos_max,
+            None => {
+                let normal = uniform_sphere(u);
+                let p = self.center + normal * self.radius;
+                let pdf = area_to_solid_angle(self.area_pdf(), reference, &p, &normal);
+                return Some(SurfaceSample { p, normal, pdf });
+            }
+        };
+        let axis = (self.center - reference).normalize();
+        let (s, t) = coordinate_system(&axis);
+        let local = uniform_cone(u, cos_max);
+        let direction = s * local.x + t * local.y + axis * local.z;
+
+        // directions at the edge of the cone may narrowly miss the sphere due to floating point
+        // error, in which case the closest point to the ray is used instead
+        let ray = Ray {
+            origin: *reference,
+            direction,
+        };
+        let p = match self.hit(&ray, N::zero(), N::max_value()) {
+            Some(hr) => hr.p,
+            None => {
+                let closest =
+                    ray.point_at_param(Matrix::dot(&(self.center - reference), &direction));
+                self.center + (closest - self.center).normalize() * self.radius
+            }
+        };
+        Some(SurfaceSample {
+            p,
+            normal: (p - self.center) / self.radius,
+            pdf: uniform_cone_pdf(cos_max),
+        })
+    }
+
+    fn pdf(&self, reference: &Vector3<N>, direction: &Vector3<N>) -> N {
+        let ray = Ray {
+            origin: *reference,
+            direction: direction.normalize(),
+        };
+        let hr = match self.hit(&ray, N::zero(), N::max_value()) {
+            Some(hr) => hr,
+            None => return N::zero(),
+        };
+
+        match self.cos_max(reference) {
+            Some(cos_max) => uniform_cone_pdf(cos_max),
+            None => area_to_solid_angle(self.area_pdf(), reference, &hr.p, &hr.normal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::{Independent, Sampler};
+    use crate::typedefs::*;
+
+    fn unit_sphere() -> Sphere<f> {
+        Sphere {
+            center: Vector3f::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_hit_near_root() {
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 5.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        let hr = unit_sphere().hit(&ray, 0.001, f::MAX).unwrap();
+        assert_eq!(hr.t, 4.0);
+        assert_eq!(hr.p, Vector3f::new(0.0, 0.0, 1.0));
+        assert!(hr.front_face);
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+        assert_eq!(hr.shading_normal, hr.normal);
+    }
+
+    #[test]
+    fn test_hit_far_root() {
+        // the ray starts inside the sphere, so the near root is behind the origin
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 0.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        let hr = unit_sphere().hit(&ray, 0.001, f::MAX).unwrap();
+        assert_eq!(hr.t, 1.0);
+
+        // the inside of the sphere was hit, so the normal faces the center
+        assert!(!hr.front_face);
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+
+        // the near root is under the epsilon
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 1.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        let hr = unit_sphere().hit(&ray, 0.001, f::MAX).unwrap();
+        assert_eq!(hr.t, 2.0);
+    }
+
+    #[test]
+    fn test_uv() {
+        let uv = Sphere::uv(&Vector3f::new(0.0, -1.0, 0.0));
+        assert_eq!(uv.y, 0.0);
+        let uv = Sphere::uv(&Vector3f::new(0.0, 1.0, 0.0));
+        assert_eq!(uv.y, 1.0);
+        let uv = Sphere::uv(&Vector3f::new(-1.0, 0.0, 0.0));
+        assert_eq!(uv, Vector2f::new(0.0, 0.5));
+    }
+
+    #[test]
+    fn test_miss() {
+        // the sphere is entirely behind the ray
+        let ray = Ray::new(&Vector3f::new(0.0, 0.0, 5.0), &Vector3f::new(0.0, 0.0, 1.0));
+        assert!(unit_sphere().hit(&ray, 0.001, f::MAX).is_none());
+
+        // the sphere is beyond the maximum distance
+        let ray = Ray::new(
+            &Vector3f::new(0.0, 0.0, 5.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        assert!(unit_sphere().hit(&ray, 0.001, 3.0).is_none());
+
+        // the ray passes beside the sphere
+        let ray = Ray::new(
+            &Vector3f::new(2.0, 0.0, 5.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        assert!(unit_sphere().hit(&ray, 0.001, f::MAX).is_none());
+    }
+
+    #[test]
+    fn test_sample() {
+        let sphere = unit_sphere();
+        let mut sampler = Independent::new(0);
+        let trials = 10000;
+
+        // from outside, the samples cover the cone of directions that the sphere subtends, and
+        // from inside, they cover every direction
+        let cone = 2.0 * f::pi() * (1.0 - (1.0 - 1.0 / 9.0 as f).sqrt());
+        let cases = [
+            (Vector3f::new(0.0, 0.0, 3.0), cone),
+            (Vector3f::new(0.0, 0.5, 0.0), 4.0 * f::pi()),
+        ];
+
+        for (reference, expected) in &cases {
+            let mut solid_angle = 0.0;
+
+            for i in 0..trials {
+                sampler.start_sample((0, 0), i);
+                let sample = sphere.sample(reference, &sampler.get_2d()).unwrap();
+                assert!((sample.p.norm() - 1.0).abs() < 1e-4, "{}", sample.p);
+                assert!((sample.normal - sample.p).norm() < 1e-4);
+
+                let pdf = sphere.pdf(reference, &(sample.p - reference));
+                assert!(
+                    (pdf - sample.pdf).abs() < 1e-2 * pdf,
+                    "{} {}",
+                    pdf,
+                    sample.pdf
+                );
+                solid_angle += 1.0 / sample.pdf / trials as f;
+            }
+            assert!(
+                (solid_angle - expected).abs() < 0.02 * expected,
+                "{} {}",
+                solid_angle,
+                expected
+            );
         }
-        None
+
+        // directions that miss the sphere can't be sampled
+        let reference = Vector3f::new(0.0, 0.0, 3.0);
+        assert_eq!(sphere.pdf(&reference, &Vector3f::new(0.0, 1.0, 0.0)), 0.0);
     }
 }
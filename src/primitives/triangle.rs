@@ -0,0 +1,229 @@
+use crate::aabb::AABB;
+use crate::hittable::{area_to_solid_angle, HitRecord, Hittable, SurfaceSample};
+use crate::na::{Matrix, RealField, Vector2, Vector3};
+use crate::ray::Ray;
+use crate::sample::{uniform_triangle, uniform_triangle_pdf};
+
+/// A single triangle, defined by its three vertices
+///
+/// The front face of the triangle is the side from which the vertices appear in counter-clockwise
+/// order. Triangles can be hit from either side, and the normals of a hit face the side that the
+/// ray came from, with `HitRecord::front_face` telling which one it was.
+#[derive(Clone, Debug, Copy)]
+pub struct Triangle<N: RealField> {
+    pub vertices: [Vector3<N>; 3],
+}
+
+/// Intersect a ray with the triangle formed by `p0`, `p1`, and `p2` using the Möller–Trumbore
+/// algorithm.
+///
+/// Returns the ray parameter of the hit along with the barycentric coordinates of the hit point
+/// with respect to `p1` and `p2`. The barycentric coordinate for `p0` is `1 - b1 - b2`.
+pub(crate) fn intersect<N: RealField>(
+    p0: &Vector3<N>,
+    p1: &Vector3<N>,
+    p2: &Vector3<N>,
+    ray: &Ray<N>,
+    t_min: N,
+    t_max: N,
+) -> Option<(N, N, N)> {
+    let e1 = p1 - p0;
+    let e2 = p2 - p0;
+    let pvec = ray.direction.cross(&e2);
+    let det = Matrix::dot(&e1, &pvec);
+
+    // the ray is parallel to the plane of the triangle
+    if det == N::zero() {
+        return None;
+    }
+    let inv_det = N::one() / det;
+    let tvec = ray.origin - p0;
+    let b1 = Matrix::dot(&tvec, &pvec) * inv_det;
+
+    if b1 < N::zero() || b1 > N::one() {
+        return None;
+    }
+    let qvec = tvec.cross(&e1);
+    let b2 = Matrix::dot(&ray.direction, &qvec) * inv_det;
+
+    if b2 < N::zero() || b1 + b2 > N::one() {
+        return None;
+    }
+    let t = Matrix::dot(&e2, &qvec) * inv_det;
+
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
+
+/// The unit normal and the area of the triangle formed by `p0`, `p1`, and `p2`
+fn normal_and_area<N: RealField>(
+    p0: &Vector3<N>,
+    p1: &Vector3<N>,
+    p2: &Vector3<N>,
+) -> (Vector3<N>, N) {
+    let cross = (p1 - p0).cross(&(p2 - p0));
+    let double_area = cross.norm();
+    (cross / double_area, double_area / (N::one() + N::one()))
+}
+
+/// Sample a point uniformly on the triangle formed by `p0`, `p1`, and `p2` (see
+/// `Hittable::sample`). Returns `None` if the triangle is degenerate or seen edge on.
+pub(crate) fn sample<N: RealField>(
+    [p0, p1, p2]: [&Vector3<N>; 3],
+    reference: &Vector3<N>,
+    u: &Vector2<N>,
+) -> Option<SurfaceSample<N>> {
+    let (normal, area) = normal_and_area(p0, p1, p2);
+
+    if area == N::zero() {
+        return None;
+    }
+    let b = uniform_triangle(u);
+    let p = p0 * b.x + p1 * b.y + p2 * (N::one() - b.x - b.y);
+    let pdf = area_to_solid_angle(uniform_triangle_pdf(area), reference, &p, &normal);
+
+    if pdf == N::zero() {
+        return None;
+    }
+    Some(SurfaceSample { p, normal, pdf })
+}
+
+/// The density of `sample` for the point of the triangle formed by `p0`, `p1`, and `p2` that a
+/// ray from `reference` in `direction` hits (see `Hittable::pdf`)
+pub(crate) fn pdf<N: RealField>(
+    [p0, p1, p2]: [&Vector3<N>; 3],
+    reference: &Vector3<N>,
+    direction: &Vector3<N>,
+) -> N {
+    let ray = Ray {
+        origin: *reference,
+        direction: *direction,
+    };
+
+    match intersect(p0, p1, p2, &ray, N::zero(), N::max_value()) {
+        Some((t, _, _)) => {
+            let (normal, area) = normal_and_area(p0, p1, p2);
+            let p = ray.point_at_param(t);
+            area_to_solid_angle(uniform_triangle_pdf(area), reference, &p, &normal)
+        }
+        None => N::zero(),
+    }
+}
+
+/// The bounding box of three points
+pub(crate) fn bounds<N: RealField>(p0: &Vector3<N>, p1: &Vector3<N>, p2: &Vector3<N>) -> AABB<N> {
+    AABB::new(p0, p1).union_point(p2)
+}
+
+impl<N: RealField> Hittable for Triangle<N> {
+    type NumType = N;
+
+    fn hit(
+        &self,
+        ray: &Ray<Self::NumType>,
+        t_min: Self::NumType,
+        t_max: Self::NumType,
+    ) -> Option<HitRecord<Self::NumType>> {
+        let [p0, p1, p2] = &self.vertices;
+        let (t, b1, b2) = intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let normal = (p1 - p0).cross(&(p2 - p0)).normalize();
+
+        let hr = HitRecord {
+            t,
+            p: ray.point_at_param(t),
+            normal,
+            shading_normal: normal,
+            uv: Vector2::new(b1, b2),
+            front_face: true,
+        };
+        Some(hr.face_forward(&ray.direction))
+    }
+
+    fn bounding_box(&self) -> Option<AABB<Self::NumType>> {
+        let [p0, p1, p2] = &self.vertices;
+        Some(bounds(p0, p1, p2))
+    }
+
+    fn sample(&self, reference: &Vector3<N>, u: &Vector2<N>) -> Option<SurfaceSample<N>> {
+        let [p0, p1, p2] = &self.vertices;
+        sample([p0, p1, p2], reference, u)
+    }
+
+    fn pdf(&self, reference: &Vector3<N>, direction: &Vector3<N>) -> N {
+        let [p0, p1, p2] = &self.vertices;
+        pdf([p0, p1, p2], reference, direction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::typedefs::*;
+
+    fn triangle() -> Triangle<f> {
+        Triangle {
+            vertices: [
+                Vector3f::new(0.0, 0.0, 0.0),
+                Vector3f::new(1.0, 0.0, 0.0),
+                Vector3f::new(0.0, 1.0, 0.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_hit() {
+        let ray = Ray::new(
+            &Vector3f::new(0.25, 0.25, 1.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        let hr = triangle().hit(&ray, 0.001, f::MAX).unwrap();
+        assert_eq!(hr.t, 1.0);
+        assert_eq!(hr.p, Vector3f::new(0.25, 0.25, 0.0));
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, 1.0));
+        assert_eq!(hr.uv, Vector2f::new(0.25, 0.25));
+
+        // triangles can be hit from behind as well, and the normals are flipped to face the ray
+        let ray = Ray::new(
+            &Vector3f::new(0.25, 0.25, -1.0),
+            &Vector3f::new(0.0, 0.0, 1.0),
+        );
+        let hr = triangle().hit(&ray, 0.001, f::MAX).unwrap();
+        assert!(!hr.front_face);
+        assert_eq!(hr.normal, Vector3f::new(0.0, 0.0, -1.0));
+        assert_eq!(hr.shading_normal, hr.normal);
+    }
+
+    #[test]
+    fn test_miss() {
+        // outside of the edges
+        let ray = Ray::new(
+            &Vector3f::new(0.75, 0.75, 1.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        assert!(triangle().hit(&ray, 0.001, f::MAX).is_none());
+
+        // parallel to the triangle
+        let ray = Ray::new(
+            &Vector3f::new(-1.0, 0.25, 0.0),
+            &Vector3f::new(1.0, 0.0, 0.0),
+        );
+        assert!(triangle().hit(&ray, 0.001, f::MAX).is_none());
+
+        // out of range
+        let ray = Ray::new(
+            &Vector3f::new(0.25, 0.25, 1.0),
+            &Vector3f::new(0.0, 0.0, -1.0),
+        );
+        assert!(triangle().hit(&ray, 0.001, 0.5).is_none());
+        assert!(triangle().hit(&ray, 1.5, f::MAX).is_none());
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let bb = triangle().bounding_box().unwrap();
+        assert_eq!(bb.min, Vector3f::new(0.0, 0.0, 0.0));
+        assert_eq!(bb.max, Vector3f::new(1.0, 1.0, 0.0));
+    }
+}
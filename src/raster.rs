@@ -0,0 +1,87 @@
+//! A plain grid of linear RGB values
+//!
+//! Rasters are what the high dynamic range image codecs read and write, so the same type is used
+//! for saving renders and for loading environment maps and reference images.
+
+use crate::film::Film;
+use crate::typedefs::*;
+
+/// The largest number of pixels that an image file can have, which keeps a corrupt header from
+/// asking for far more memory than any real image needs
+pub const MAX_PIXELS: usize = 1 << 27;
+
+/// An image with a linear RGB color for each pixel.
+///
+/// Pixels are stored in row-major order, starting from the top left corner of the image, which
+/// is the same layout that `Film` uses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Raster {
+    /// The width of the image, in pixels
+    pub width: usize,
+
+    /// The height of the image, in pixels
+    pub height: usize,
+
+    /// The color of each pixel
+    pub pixels: Vec<Color3f>,
+}
+
+impl Raster {
+    /// Create a black image with the given resolution
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color3f::zeros(); width * height],
+        }
+    }
+
+    /// Whether an image with the given resolution is small enough to be loaded from a file. Each
+    /// dimension has to be within the limit on its own as well, since a scanline is allocated
+    /// even when the other dimension is zero.
+    pub fn is_loadable(width: usize, height: usize) -> bool {
+        width <= MAX_PIXELS
+            && height <= MAX_PIXELS
+            && width.checked_mul(height).is_some_and(|n| n <= MAX_PIXELS)
+    }
+
+    /// The color of the pixel in column `x` and row `y`, where the top row is row 0
+    pub fn get(&self, x: usize, y: usize) -> Color3f {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Set the color of the pixel in column `x` and row `y`
+    pub fn set(&mut self, x: usize, y: usize, color: Color3f) {
+        self.pixels[y * self.width + x] = color;
+    }
+}
+
+impl From<&Film> for Raster {
+    /// The average radiance of every pixel of the film
+    fn from(film: &Film) -> Self {
+        Self {
+            width: film.width,
+            height: film.height,
+            pixels: film.colors(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_film() {
+        let mut film = Film::new(2, 2);
+        film.add_sample(1, 0, &Color3f::new(2.0, 4.0, 6.0), 2.0);
+        let raster = Raster::from(&film);
+        assert_eq!((raster.width, raster.height), (2, 2));
+        assert_eq!(raster.get(1, 0), Color3f::new(2.0, 4.0, 6.0));
+        assert_eq!(raster.get(0, 1), Color3f::zeros());
+
+        let mut copy = Raster::new(2, 2);
+        copy.set(1, 0, Color3f::new(2.0, 4.0, 6.0));
+        assert_eq!(copy, raster);
+    }
+}
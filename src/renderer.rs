@@ -0,0 +1,645 @@
+//! The renderer turns a scene into an image
+//!
+//! A `Renderer` is configured with a `RendererBuilder`, which is created with
+//! `Renderer::builder`. Every setting other than the scene and the camera has a default.
+//!
+//! ```no_run
+//! use trtlib::background::Background;
+//! use trtlib::camera::Pinhole;
+//! use trtlib::renderer::Renderer;
+//! use trtlib::scene::{self, Scene};
+//!
+//! let scene = Scene::new(scene::default_scene(), Background::default());
+//! let renderer = Renderer::builder(scene, Box::new(Pinhole::default()))
+//!     .resolution(400, 200)
+//!     .samples(16)
+//!     .progress(|done, total| println!("{}/{} pixels", done, total))
+//!     .build();
+//! let image = renderer.render();
+//! ```
+
+use crate::accelerator::Accelerator;
+use crate::camera::CameraRef;
+use crate::film::{Aov, Film, SampleStats};
+use crate::filter::{FilterKind, FilterRef};
+use crate::integrator::{IntegratorKind, IntegratorRef, EPSILON};
+use crate::ray::Ray;
+use crate::sampler::{Sampler, SamplerKind};
+use crate::scene::Scene;
+use crate::tonemap::luminance;
+use crate::typedefs::*;
+use log::debug;
+use rayon::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A function that is called as the render progresses, with the number of pixels that have been
+/// rendered so far and the total number of pixels in the image. It may be called from any of the
+/// threads that are rendering.
+pub type ProgressCallback = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+/// What to do with a sample whose radiance has a NaN or infinite component. These samples are
+/// usually caused by numerical problems in a material or a primitive, and a single one would
+/// otherwise ruin the pixel that it's recorded in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Copy)]
+pub enum NonFinite {
+    /// Discard the sample, so the pixel is the average of the remaining samples
+    #[default]
+    Drop,
+
+    /// Record the sample with its non-finite components set to zero
+    Zero,
+}
+
+impl FromStr for NonFinite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(NonFinite::Drop),
+            "zero" => Ok(NonFinite::Zero),
+            _ => Err(format!(
+                "unknown way to handle non-finite samples \"{}\"",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NonFinite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NonFinite::Drop => write!(f, "drop"),
+            NonFinite::Zero => write!(f, "zero"),
+        }
+    }
+}
+
+/// The number of rows of the image that are rendered together by a single thread
+const BAND_HEIGHT: usize = 16;
+
+/// Renders images of a scene with a fixed set of settings
+pub struct Renderer {
+    scene: Scene<f>,
+    camera: CameraRef<f>,
+    integrator: IntegratorRef<f>,
+    filter: FilterRef,
+    sampler: SamplerKind,
+    width: usize,
+    height: usize,
+    samples: usize,
+    seed: u64,
+    aovs: bool,
+    non_finite: NonFinite,
+    max_luminance: Option<f>,
+    progress: Option<ProgressCallback>,
+}
+
+/// Configures a `Renderer`
+pub struct RendererBuilder {
+    scene: Scene<f>,
+    camera: CameraRef<f>,
+    integrator: IntegratorKind,
+    filter: FilterKind,
+    filter_radius: Option<f>,
+    sampler: SamplerKind,
+    width: usize,
+    height: usize,
+    samples: usize,
+    depth: u,
+    min_depth: u,
+    seed: u64,
+    aovs: bool,
+    non_finite: NonFinite,
+    max_luminance: Option<f>,
+    progress: Option<ProgressCallback>,
+}
+
+impl RendererBuilder {
+    /// Set the width and height of the image, in pixels, which must both be at least one. The
+    /// default is 200x100.
+    pub fn resolution(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Set the number of samples to take for each pixel, which must be at least one. The default
+    /// is 50.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Set the maximum number of bounces for each path. The default is 50.
+    pub fn depth(mut self, depth: u) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set the number of bounces after which paths can be ended early by Russian roulette, which
+    /// is more likely the less light a path can still carry. The default is 3.
+    pub fn min_depth(mut self, min_depth: u) -> Self {
+        self.min_depth = min_depth;
+        self
+    }
+
+    /// Set the light transport algorithm. The default is a path tracer.
+    pub fn integrator(mut self, integrator: IntegratorKind) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Set the filter that spreads each sample over the pixels around it. The default is a box
+    /// filter that only covers one pixel, which averages the samples in each pixel.
+    pub fn filter(mut self, filter: FilterKind) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Set the radius of the filter in pixels, or use the filter's default radius if it's `None`
+    pub fn filter_radius(mut self, radius: Option<f>) -> Self {
+        self.filter_radius = radius;
+        self
+    }
+
+    /// Set the sampler that generates the random choices for each sample, such as its position
+    /// in the pixel. The default is an independent sampler, which makes every choice uniformly at
+    /// random.
+    pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Set the seed for the random numbers that are used while rendering. Rendering the same
+    /// scene with the same settings and seed produces the same image. The default seed is 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set whether auxiliary information (depth, normals and albedo) is rendered for each pixel
+    /// along with its color. The default is to only render the color.
+    pub fn aovs(mut self, aovs: bool) -> Self {
+        self.aovs = aovs;
+        self
+    }
+
+    /// Set what happens to samples with a NaN or infinite radiance. The default is to drop them.
+    pub fn non_finite(mut self, non_finite: NonFinite) -> Self {
+        self.non_finite = non_finite;
+        self
+    }
+
+    /// Set the largest luminance that a sample can have. Brighter samples are scaled down to it,
+    /// which suppresses fireflies at the cost of some energy. The default is to not clamp samples.
+    pub fn max_luminance(mut self, max_luminance: Option<f>) -> Self {
+        self.max_luminance = max_luminance;
+        self
+    }
+
+    /// Set a function that is called to report the progress of the render
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Create the renderer with the settings that have been configured
+    ///
+    /// # Panics
+    ///
+    /// Panics if the image is empty or the number of samples is zero.
+    pub fn build(self) -> Renderer {
+        assert!(
+            self.width > 0 && self.height > 0,
+            "the image must be at least one pixel wide and high"
+        );
+        assert!(self.samples > 0, "each pixel needs at least one sample");
+        Renderer {
+            scene: self.scene,
+            camera: self.camera,
+            integrator: self.integrator.build(self.min_depth, self.depth),
+            filter: self.filter.build(self.filter_radius),
+            sampler: self.sampler,
+            width: self.width,
+            height: self.height,
+            samples: self.samples,
+            seed: self.seed,
+            aovs: self.aovs,
+            non_finite: self.non_finite,
+            max_luminance: self.max_luminance,
+            progress: self.progress,
+        }
+    }
+}
+
+impl Renderer {
+    /// Start configuring a renderer for a scene, which is viewed through `camera`
+    pub fn builder(scene: Scene<f>, camera: CameraRef<f>) -> RendererBuilder {
+        RendererBuilder {
+            scene,
+            camera,
+            integrator: IntegratorKind::default(),
+            filter: FilterKind::default(),
+            filter_radius: None,
+            sampler: SamplerKind::default(),
+            width: 200,
+            height: 100,
+            samples: 50,
+            depth: 50,
+            min_depth: 3,
+            seed: 0,
+            aovs: false,
+            non_finite: NonFinite::default(),
+            max_luminance: None,
+            progress: None,
+        }
+    }
+
+    /// Render the scene, recording every sample in a film. The rows of the image are rendered in
+    /// parallel. If AOVs are enabled, they're rendered after the color of every pixel is.
+    pub fn render(&self) -> Film {
+        let (width, height) = (self.width, self.height);
+        let mut film = Film::new(width, height);
+        let total = width * height;
+        let completed = AtomicUsize::new(0);
+
+        // every band is rendered into its own film, which has extra rows so that it can hold every
+        // sample that the filter spreads past the band's edges
+        let margin = (self.filter.radius() + 0.5).ceil() as usize;
+        let bands: Vec<(usize, Film)> = (0..height.div_ceil(BAND_HEIGHT))
+            .into_par_iter()
+            .map(|band| {
+                let (start, end) = (band * BAND_HEIGHT, ((band + 1) * BAND_HEIGHT).min(height));
+                let top = start.saturating_sub(margin);
+                let mut film = Film::new(width, (end + margin).min(height) - top);
+                let mut sampler = self.sampler.build(self.samples, self.seed);
+
+                for y in start..end {
+                    for x in 0..width {
+                        self.render_pixel(x, y, top, sampler.as_mut(), &mut film);
+                    }
+
+                    if let Some(progress) = &self.progress {
+                        let done = completed.fetch_add(width, Ordering::Relaxed) + width;
+                        progress(done, total);
+                    }
+                }
+                (top, film)
+            })
+            .collect();
+
+        // the bands overlap, so they're added to the image in order, which makes the result
+        // independent of how the work was split between threads
+        for (top, band) in bands {
+            let pixels = &mut film.pixels[top * width..(top + band.height) * width];
+
+            for (pixel, sample) in pixels.iter_mut().zip(&band.pixels) {
+                pixel.merge(sample);
+            }
+            film.stats = film.stats + band.stats;
+        }
+
+        if self.aovs {
+            let mut aovs = vec![Aov::default(); total];
+            aovs.par_chunks_mut(width.max(1))
+                .enumerate()
+                .for_each(|(y, row)| {
+                    // the AOVs use different values from the color, so that they're independent
+                    let mut sampler = self.sampler.build(self.samples, !self.seed);
+
+                    for (x, aov) in row.iter_mut().enumerate() {
+                        *aov = self.render_aov(x, y, sampler.as_mut());
+                    }
+                });
+            film.aovs = Some(aovs);
+        }
+        film
+    }
+
+    /// A random position in the pixel in column `x` and row `y`, in the continuous coordinates
+    /// of the film
+    fn sample_position(&self, x: usize, y: usize, sampler: &mut dyn Sampler) -> (f, f) {
+        let offset = sampler.get_2d();
+        (x as f + offset.x, y as f + offset.y)
+    }
+
+    /// The camera ray through a position on the film, where `(0, 0)` is the top left corner
+    fn camera_ray(&self, (x, y): (f, f), sampler: &mut dyn Sampler) -> Ray<f> {
+        // the camera's v coordinate increases from the bottom of the image
+        let u = x / self.width as f;
+        let v = 1.0 - y / self.height as f;
+        self.camera.get_ray(u, v, sampler)
+    }
+
+    /// Take the samples for the pixel in column `x` and row `y`, counting rows from the top, and
+    /// splat them into `film`, whose first row is row `top` of the image
+    fn render_pixel(
+        &self,
+        x: usize,
+        y: usize,
+        top: usize,
+        sampler: &mut dyn Sampler,
+        film: &mut Film,
+    ) {
+        for i in 0..self.samples {
+            sampler.start_sample((x, y), i);
+            let position = self.sample_position(x, y, sampler);
+            let ray = self.camera_ray(position, sampler);
+            let radiance = self.integrator.radiance(&self.scene, &ray, sampler);
+            film.stats.samples += 1;
+
+            if let Some(radiance) = self.check_sample(radiance, (x, y, i), &mut film.stats) {
+                let (fx, fy) = position;
+                film.splat(fx, fy - top as f, &radiance, self.filter.as_ref());
+            }
+        }
+    }
+
+    /// Fix the problems with the radiance of a sample before it's recorded, counting each of them
+    /// in `stats`. Returns `None` if the sample should be dropped. The position of the sample
+    /// (its column, row and index) is logged for every problem, to help track down its cause.
+    fn check_sample(
+        &self,
+        mut radiance: Color3f,
+        (x, y, i): (usize, usize, usize),
+        stats: &mut SampleStats,
+    ) -> Option<Color3f> {
+        if radiance.iter().any(|e| !e.is_finite()) {
+            debug!("Sample {} of pixel ({}, {}) is {:?}", i, x, y, radiance);
+            stats.non_finite += 1;
+
+            match self.non_finite {
+                NonFinite::Drop => {
+                    stats.dropped += 1;
+                    return None;
+                }
+                NonFinite::Zero => {
+                    radiance = radiance.map(|e| if e.is_finite() { e } else { 0.0 });
+                }
+            }
+        }
+
+        if radiance.iter().any(|&e| e < 0.0) {
+            debug!(
+                "Sample {} of pixel ({}, {}) is negative: {:?}",
+                i, x, y, radiance
+            );
+            stats.negative += 1;
+            radiance = radiance.map(|e| e.max(0.0));
+        }
+
+        if let Some(max) = self.max_luminance {
+            let l = luminance(&radiance);
+
+            if l > max {
+                stats.clamped += 1;
+                radiance *= max / l;
+            }
+        }
+        Some(radiance)
+    }
+
+    /// Find the auxiliary information for the pixel in column `x` and row `y`, using the surfaces
+    /// that the camera rays for the pixel hit first
+    fn render_aov(&self, x: usize, y: usize, sampler: &mut dyn Sampler) -> Aov {
+        let mut aov = Aov::default();
+
+        for i in 0..self.samples {
+            sampler.start_sample((x, y), i);
+            let position = self.sample_position(x, y, sampler);
+            let ray = self.camera_ray(position, sampler);
+
+            if let Some((hr, obj)) = self.scene.objects.any_hit(&ray, Some(EPSILON), None) {
+                aov.depth = aov.depth.min(hr.t * ray.direction.norm());
+                aov.normal += hr.shading_normal;
+                aov.albedo += obj.material.albedo(&hr);
+            }
+        }
+        aov.normal /= self.samples as f;
+        aov.albedo /= self.samples as f;
+        aov
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::background::Background;
+    use crate::camera::Pinhole;
+    use crate::integrator::Integrator;
+    use crate::sampler::test::KINDS;
+    use crate::scene;
+    use std::sync::Mutex;
+
+    fn renderer() -> RendererBuilder {
+        let scene = Scene::new(scene::test_scene(), Background::default());
+        Renderer::builder(scene, Box::new(Pinhole::default()))
+            .resolution(8, 4)
+            .samples(4)
+    }
+
+    #[test]
+    fn test_render() {
+        let image = renderer().build().render();
+        assert_eq!(image.width, 8);
+        assert_eq!(image.height, 4);
+        assert_eq!(image.pixels.len(), 32);
+        assert!(image.pixels.iter().all(|p| p.weight_sum == 4.0));
+
+        // the corners see the sky, which is brighter at the top, and the sphere is in the middle
+        let top = image.get(0, 0);
+        let bottom = image.get(0, 3);
+        assert!(top.x < bottom.x && top.z == 1.0 && bottom.z == 1.0);
+        assert!(image.get(4, 2).z < 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one sample")]
+    fn test_zero_samples() {
+        renderer().samples(0).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one pixel")]
+    fn test_empty_image() {
+        renderer().resolution(0, 4).build();
+    }
+
+    #[test]
+    fn test_filter() {
+        // the image is tall enough to be split into several bands
+        let render = |filter| renderer().resolution(6, 40).filter(filter).build().render();
+        let mitchell = render(FilterKind::Mitchell);
+        assert_eq!(mitchell.stats.samples, 6 * 40 * 4);
+
+        // pixels away from the edges receive samples from all of their neighbors
+        let interior = mitchell.pixel(3, 20).weight_sum;
+        assert!(interior > 3.5, "{}", interior);
+        assert!(mitchell.pixel(0, 0).weight_sum < interior);
+        assert!(mitchell
+            .colors()
+            .iter()
+            .all(|c| c.iter().all(|e| e.is_finite())));
+
+        // a wide filter blurs the image, so it's different from the plain average
+        let tent = renderer().filter(FilterKind::Tent).filter_radius(Some(3.0));
+        assert_ne!(tent.build().render(), renderer().build().render());
+        assert_ne!(mitchell, render(FilterKind::Box));
+    }
+
+    #[test]
+    fn test_thread_count() {
+        for &kind in &KINDS {
+            let render = || {
+                renderer()
+                    .resolution(10, 50)
+                    .filter(FilterKind::Gaussian)
+                    .sampler(kind)
+                    .seed(1234)
+                    .aovs(true)
+                    .build()
+                    .render()
+            };
+            let pool = |threads| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap()
+            };
+            let single = pool(1).install(render);
+            assert_eq!(single, pool(4).install(render), "{}", kind);
+            assert_eq!(single, render(), "{}", kind);
+        }
+    }
+
+    #[test]
+    fn test_aovs() {
+        assert!(renderer().build().render().aovs.is_none());
+
+        // the middle pixel of an image with an odd resolution is centered on the sphere
+        let film = renderer().resolution(9, 5).aovs(true).build().render();
+        let aovs = film.aovs.unwrap();
+        assert_eq!(aovs.len(), 45);
+
+        // the corner only sees the sky
+        assert_eq!(aovs[0], Aov::default());
+
+        // the middle of the image is entirely covered by the front of the sphere
+        let center = aovs[2 * 9 + 4];
+        assert!(
+            center.depth >= 0.5 && center.depth < 0.6,
+            "{}",
+            center.depth
+        );
+        assert!(center.normal.z > 0.8);
+        assert!((center.albedo - Color3f::new(0.8, 0.3, 0.3)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn test_non_finite_names() {
+        for &non_finite in &[NonFinite::Drop, NonFinite::Zero] {
+            assert_eq!(non_finite.to_string().parse(), Ok(non_finite));
+        }
+        assert!("clamp".parse::<NonFinite>().is_err());
+    }
+
+    #[test]
+    fn test_seed() {
+        let a = renderer().seed(7).build().render();
+        let b = renderer().seed(7).build().render();
+        let c = renderer().seed(8).build().render();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_samplers() {
+        let independent = renderer().build().render();
+
+        for &kind in &KINDS[1..] {
+            let film = renderer().sampler(kind).build().render();
+            assert!(film.pixels.iter().all(|p| p.weight_sum == 4.0), "{}", kind);
+            assert!(film.get(4, 2).z < 0.5, "{}", kind);
+            assert_ne!(film, independent, "{}", kind);
+            assert_eq!(film, renderer().sampler(kind).build().render(), "{}", kind);
+        }
+    }
+
+    /// An integrator that cycles through a NaN, a negative, a very bright and a valid sample
+    #[derive(Default)]
+    struct Broken {
+        count: AtomicUsize,
+    }
+
+    impl Integrator<f> for Broken {
+        fn radiance(
+            &self,
+            _scene: &Scene<f>,
+            _ray: &Ray<f>,
+            _sampler: &mut dyn Sampler,
+        ) -> Color3f {
+            match self.count.fetch_add(1, Ordering::Relaxed) % 4 {
+                0 => Color3f::new(f::NAN, 1.0, f::INFINITY),
+                1 => Color3f::new(-1.0, 2.0, 2.0),
+                2 => Color3f::new(100.0, 100.0, 100.0),
+                _ => Color3f::new(1.0, 1.0, 1.0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_bad_samples() {
+        for &non_finite in &[NonFinite::Drop, NonFinite::Zero] {
+            let mut renderer = renderer()
+                .non_finite(non_finite)
+                .max_luminance(Some(10.0))
+                .build();
+            renderer.integrator = Box::new(Broken::default());
+            let film = renderer.render();
+
+            let dropped = if non_finite == NonFinite::Drop { 32 } else { 0 };
+            assert_eq!(
+                film.stats,
+                SampleStats {
+                    samples: 128,
+                    non_finite: 32,
+                    dropped,
+                    negative: 32,
+                    clamped: 32,
+                }
+            );
+            assert!(film.stats.has_problems());
+            let weights: f = film.pixels.iter().map(|p| p.weight_sum).sum();
+            assert_eq!(weights, (128 - dropped) as f);
+
+            for color in film.colors() {
+                assert!(color.iter().all(|e| e.is_finite() && *e >= 0.0));
+                assert!(luminance(&color) <= 10.0 + 1e-4, "{:?}", color);
+            }
+        }
+
+        // valid samples aren't changed
+        let film = renderer().build().render();
+        assert_eq!(film.stats.samples, 128);
+        assert!(!film.stats.has_problems());
+    }
+
+    #[test]
+    fn test_progress() {
+        let reports = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let reports_clone = reports.clone();
+        renderer()
+            .progress(move |done, total| reports_clone.lock().unwrap().push((done, total)))
+            .build()
+            .render();
+
+        let mut reports = reports.lock().unwrap().clone();
+        reports.sort();
+        assert_eq!(reports, vec![(8, 32), (16, 32), (24, 32), (32, 32)]);
+    }
+}
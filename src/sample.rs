@@ -1,38 +1,338 @@
-use crate::na::Matrix;
+//! The sample module contains convenience functions for statistical sampling.
+//!
+//! The functions here map uniformly distributed points, such as the values from a `Sampler`, to
+//! points that are distributed over some other shape. The mappings are continuous where they can
+//! be, so that well stratified inputs stay well stratified. Each mapping has a matching function
+//! for the probability density of the points that it generates, which is with respect to area for
+//! points and solid angle for directions.
+//!
+//! Directions on the hemisphere and in cones are around the positive z axis. They can be moved
+//! into place with `common::coordinate_system`.
+
 use crate::na::RealField;
-use crate::na::Vector3;
+use crate::na::{Vector2, Vector3};
 use num::FromPrimitive;
-use rand::{thread_rng, Rng};
 
-/// The sample module contains convenience functions for statistical sampling.
+/// A direction from the angle `phi` around the z axis and the cosine of the angle from it
+fn spherical_direction<N: RealField>(cos_theta: N, phi: N) -> Vector3<N> {
+    let sin_theta = (N::one() - cos_theta * cos_theta).max(N::zero()).sqrt();
+    Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Maps a uniformly distributed point in the unit square to a uniformly distributed direction
+pub fn uniform_sphere<N: RealField>(u: &Vector2<N>) -> Vector3<N> {
+    spherical_direction(N::one() - (u.x + u.x), N::two_pi() * u.y)
+}
+
+/// The probability density of a direction from `uniform_sphere`
+pub fn uniform_sphere_pdf<N: RealField>() -> N {
+    N::one() / (N::two_pi() + N::two_pi())
+}
+
+/// Maps a uniformly distributed point in the unit square to a uniformly distributed direction
+/// in the hemisphere around the z axis
+pub fn uniform_hemisphere<N: RealField>(u: &Vector2<N>) -> Vector3<N> {
+    spherical_direction(u.x, N::two_pi() * u.y)
+}
+
+/// The probability density of a direction from `uniform_hemisphere`
+pub fn uniform_hemisphere_pdf<N: RealField>() -> N {
+    N::one() / N::two_pi()
+}
+
+/// Maps a uniformly distributed point in the unit square to a direction in the hemisphere around
+/// the z axis, whose density is proportional to the cosine of its angle from the axis. This
+/// matches the distribution of light that a diffuse surface reflects.
+pub fn cosine_hemisphere<N: RealField>(u: &Vector2<N>) -> Vector3<N> {
+    // points that are uniformly distributed on the disk are cosine distributed once they're
+    // projected up onto the hemisphere
+    let d = concentric_disk(u);
+    let z = (N::one() - d.x * d.x - d.y * d.y).max(N::zero()).sqrt();
+    Vector3::new(d.x, d.y, z)
+}
+
+/// The probability density of a direction from `cosine_hemisphere`, where `cos_theta` is the z
+/// component of the direction
+pub fn cosine_hemisphere_pdf<N: RealField>(cos_theta: N) -> N {
+    cos_theta.max(N::zero()) * N::frac_1_pi()
+}
 
-/// Returns a randomly sampled vector inside the unit sphere.
+/// Maps a uniformly distributed point in the unit square to a uniformly distributed point inside
+/// the unit disk.
 ///
-/// Note that this uses rejection sampling, a simple, but inefficient sampling strategy.
-pub fn unit_sphere<N: RealField + FromPrimitive>() -> Vector3<N> {
-    let mut rng = thread_rng();
-    let mut v = Vector3::new(
-        N::from_f32(2.0).unwrap(),
-        N::from_f32(2.0).unwrap(),
-        N::from_f32(2.0).unwrap(),
-    );
-    let unit = Vector3::<N>::new(
-        N::from_f32(1.0).unwrap(),
-        N::from_f32(1.0).unwrap(),
-        N::from_f32(1.0).unwrap(),
-    );
-    let scaling_factor = N::from_f32(2.0).unwrap();
-
-    // keep on generating new vectors until the generated vector falls within the unit sphere
-    // also, Rust, why can't you have a do-while loop???
-    while Matrix::norm_squared(&v) >= N::from_f32(1.0).unwrap() {
-        v = Vector3::new(
-            N::from_f32(rng.gen::<f32>()).unwrap(),
-            N::from_f32(rng.gen::<f32>()).unwrap(),
-            N::from_f32(rng.gen::<f32>()).unwrap(),
+/// This is Shirley and Chiu's concentric mapping, which maps squares around the center of the
+/// unit square to circles, so it distorts areas much less than a mapping from polar coordinates.
+pub fn concentric_disk<N: RealField>(u: &Vector2<N>) -> Vector2<N> {
+    let offset = u.map(|e| e + e - N::one());
+
+    if offset.x == N::zero() && offset.y == N::zero() {
+        return Vector2::zeros();
+    }
+    let (r, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, N::frac_pi_4() * (offset.y / offset.x))
+    } else {
+        (
+            offset.y,
+            N::frac_pi_2() - N::frac_pi_4() * (offset.x / offset.y),
         )
-        .map(|e| e * scaling_factor)
-            - unit;
+    };
+    Vector2::new(r * theta.cos(), r * theta.sin())
+}
+
+/// The probability density of a point from `concentric_disk`
+pub fn concentric_disk_pdf<N: RealField>() -> N {
+    N::frac_1_pi()
+}
+
+/// Maps a uniformly distributed point in the unit square to the barycentric coordinates of a
+/// uniformly distributed point in a triangle. The coordinates are the weights of the first two
+/// vertices, and the weight of the third is whatever is left over.
+pub fn uniform_triangle<N: RealField>(u: &Vector2<N>) -> Vector2<N> {
+    let su = u.x.sqrt();
+    Vector2::new(N::one() - su, u.y * su)
+}
+
+/// The probability density of a point from `uniform_triangle`, in a triangle with the given area
+pub fn uniform_triangle_pdf<N: RealField>(area: N) -> N {
+    N::one() / area
+}
+
+/// Maps a uniformly distributed point in the unit square to a uniformly distributed direction in
+/// the cone around the z axis, whose directions have an angle with a cosine of at least
+/// `cos_max` with the axis
+pub fn uniform_cone<N: RealField>(u: &Vector2<N>, cos_max: N) -> Vector3<N> {
+    let cos_theta = (N::one() - u.x) + u.x * cos_max;
+    spherical_direction(cos_theta, N::two_pi() * u.y)
+}
+
+/// The probability density of a direction from `uniform_cone`, which is the inverse of the solid
+/// angle of the cone
+pub fn uniform_cone_pdf<N: RealField>(cos_max: N) -> N {
+    N::one() / (N::two_pi() * (N::one() - cos_max))
+}
+
+/// Maps a uniformly distributed point in the unit cube to a uniformly distributed point inside
+/// the unit sphere
+pub fn unit_ball<N: RealField + FromPrimitive>(u: &Vector3<N>) -> Vector3<N> {
+    // pick a direction from the first two values, and a distance from the center from the last,
+    // where the cube root makes up for the volume of a shell growing with its radius
+    let radius = u.z.powf(N::from_f64(1.0 / 3.0).unwrap());
+    uniform_sphere(&u.xy()) * radius
+}
+
+/// Maps a uniformly distributed point in the unit square to a uniformly distributed point
+/// inside the regular polygon with `sides` sides that is inscribed in the unit circle. The first
+/// vertex of the polygon lies on the positive x axis.
+pub fn unit_polygon<N: RealField + FromPrimitive>(sides: usize, u: &Vector2<N>) -> Vector2<N> {
+    assert!(sides >= 3, "a polygon needs at least three sides");
+
+    // The polygon is a fan of identical triangles around the center, so a triangle is picked
+    // uniformly and then a point is sampled uniformly within it. The fraction of the first
+    // value that is left over after picking the triangle is still uniformly distributed.
+    let n = N::from_usize(sides).unwrap();
+    let scaled = u.x * n;
+    let k = scaled.floor().min(n - N::one());
+    let vertex = |i: N| {
+        let theta = N::two_pi() * i / n;
+        Vector2::new(theta.cos(), theta.sin())
+    };
+    let b = uniform_triangle(&Vector2::new(scaled - k, u.y));
+    vertex(k) * b.x + vertex(k + N::one()) * b.y
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::{Independent, Sampler};
+    use crate::typedefs::*;
+
+    const TRIALS: usize = 20000;
+
+    /// Uniformly distributed points in the unit square
+    fn points(count: usize) -> Vec<Vector2f> {
+        let mut sampler = Independent::new(0);
+        (0..count)
+            .map(|i| {
+                sampler.start_sample((0, 0), i);
+                sampler.get_2d()
+            })
+            .collect()
+    }
+
+    /// The Monte Carlo estimate of the integral of `f`, from samples with the density `pdf`
+    fn estimate<T>(samples: &[T], f: impl Fn(&T) -> f, pdf: impl Fn(&T) -> f) -> f {
+        samples.iter().map(|s| f(s) / pdf(s)).sum::<f>() / samples.len() as f
+    }
+
+    /// The fraction of the samples for which `predicate` holds
+    fn fraction<T>(samples: &[T], predicate: impl Fn(&T) -> bool) -> f {
+        samples.iter().filter(|s| predicate(s)).count() as f / samples.len() as f
+    }
+
+    fn assert_close(actual: f, expected: f, tolerance: f) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_uniform_sphere() {
+        let dirs: Vec<Vector3f> = points(TRIALS).iter().map(uniform_sphere).collect();
+        assert!(dirs.iter().all(|d| (d.norm() - 1.0).abs() < 1e-5));
+
+        // every octant gets an eighth of the directions
+        for octant in 0..8 {
+            let sign = |i: usize, e: f| (octant >> i & 1 == 1) == (e < 0.0);
+            let share = fraction(&dirs, |d| sign(0, d.x) && sign(1, d.y) && sign(2, d.z));
+            assert_close(share, 0.125, 0.01);
+        }
+        let pdf = |_: &Vector3f| uniform_sphere_pdf();
+        assert_close(
+            estimate(&dirs, |d| d.z * d.z, pdf),
+            f::pi() * 4.0 / 3.0,
+            0.1,
+        );
+        assert_close(estimate(&dirs, |d| d.x.max(0.0), pdf), f::pi(), 0.1);
+    }
+
+    #[test]
+    fn test_uniform_hemisphere() {
+        let dirs: Vec<Vector3f> = points(TRIALS).iter().map(uniform_hemisphere).collect();
+        assert!(dirs
+            .iter()
+            .all(|d| (d.norm() - 1.0).abs() < 1e-5 && d.z >= 0.0));
+
+        // the heights are uniformly distributed, since the bands between them have equal areas
+        assert_close(fraction(&dirs, |d| d.z < 0.3), 0.3, 0.015);
+        assert_close(fraction(&dirs, |d| d.x < 0.0 && d.y < 0.0), 0.25, 0.015);
+        let pdf = |_: &Vector3f| uniform_hemisphere_pdf();
+        assert_close(estimate(&dirs, |d| d.z, pdf), f::pi(), 0.05);
+    }
+
+    #[test]
+    fn test_cosine_hemisphere() {
+        let dirs: Vec<Vector3f> = points(TRIALS).iter().map(cosine_hemisphere).collect();
+        assert!(dirs
+            .iter()
+            .all(|d| (d.norm() - 1.0).abs() < 1e-5 && d.z >= 0.0));
+
+        // the cumulative distribution of the cosine is 1 - cos^2
+        assert_close(fraction(&dirs, |d| d.z > 0.5), 0.75, 0.015);
+        assert_close(fraction(&dirs, |d| d.x > 0.0), 0.5, 0.015);
+        let pdf = |d: &Vector3f| cosine_hemisphere_pdf(d.z);
+        assert_close(
+            estimate(&dirs, |d| d.z * d.z, pdf),
+            f::pi() * 2.0 / 3.0,
+            0.03,
+        );
+        assert_close(estimate(&dirs, |d| d.z * d.x.abs(), pdf), 4.0 / 3.0, 0.03);
+        assert_eq!(cosine_hemisphere_pdf(-0.5), 0.0);
+    }
+
+    #[test]
+    fn test_concentric_disk() {
+        let disk: Vec<Vector2f> = points(TRIALS).iter().map(concentric_disk).collect();
+        assert!(disk.iter().all(|p| p.norm() <= 1.0 + 1e-6));
+        assert_eq!(concentric_disk(&Vector2f::new(0.5, 0.5)), Vector2f::zeros());
+
+        // a quarter of the disk's area lies within half of its radius
+        assert_close(fraction(&disk, |p| p.norm() < 0.5), 0.25, 0.015);
+        assert_close(fraction(&disk, |p| p.x > 0.0 && p.y < 0.0), 0.25, 0.015);
+        let pdf = |_: &Vector2f| concentric_disk_pdf();
+        assert_close(
+            estimate(&disk, |p| p.norm_squared(), pdf),
+            f::frac_pi_2(),
+            0.03,
+        );
+    }
+
+    #[test]
+    fn test_uniform_triangle() {
+        let coords: Vec<Vector2f> = points(TRIALS).iter().map(uniform_triangle).collect();
+        assert!(coords
+            .iter()
+            .all(|b| b.x >= 0.0 && b.y >= 0.0 && b.x + b.y <= 1.0 + 1e-6));
+
+        // the corners of the triangle past the midpoints of its edges each hold a quarter of its
+        // area, and the points are centered on the centroid
+        assert_close(fraction(&coords, |b| b.x > 0.5), 0.25, 0.015);
+        assert_close(fraction(&coords, |b| b.y > 0.5), 0.25, 0.015);
+        assert_close(fraction(&coords, |b| b.x + b.y < 0.5), 0.25, 0.015);
+
+        // a triangle with an area of 2, where the first coordinate is the weight of the vertex at
+        // (2, 0), and the second is the weight of the vertex at (0, 2)
+        let pdf = |_: &Vector2f| uniform_triangle_pdf(2.0);
+        assert_close(estimate(&coords, |_| 1.0, pdf), 2.0, 1e-4);
+        assert_close(estimate(&coords, |b| b.x * 2.0, pdf), 4.0 / 3.0, 0.03);
+    }
+
+    #[test]
+    fn test_uniform_cone() {
+        for &cos_max in &[0.9, 0.0, -0.5] {
+            let dirs: Vec<Vector3f> = points(TRIALS)
+                .iter()
+                .map(|u| uniform_cone(u, cos_max))
+                .collect();
+            assert!(dirs
+                .iter()
+                .all(|d| (d.norm() - 1.0).abs() < 1e-5 && d.z >= cos_max - 1e-6));
+
+            // the cosines are uniformly distributed, like on the sphere
+            let middle = (1.0 + cos_max) / 2.0;
+            assert_close(fraction(&dirs, |d| d.z > middle), 0.5, 0.015);
+            let pdf = |_: &Vector3f| uniform_cone_pdf(cos_max);
+            let expected = f::pi() * (1.0 - cos_max * cos_max);
+            assert_close(estimate(&dirs, |d| d.z, pdf), expected, 0.1);
+        }
+        assert_close(uniform_cone_pdf(-1.0), uniform_sphere_pdf(), 1e-6);
+        assert_close(uniform_cone_pdf(0.0), uniform_hemisphere_pdf(), 1e-6);
+    }
+
+    #[test]
+    fn test_unit_ball() {
+        let mut mean = Vector3f::zeros();
+        let mut inner = 0;
+        let mut sampler = Independent::new(1);
+
+        for i in 0..TRIALS {
+            sampler.start_sample((0, 0), i);
+            let u = sampler.get_2d();
+            let p = unit_ball(&Vector3f::new(u.x, u.y, sampler.get_1d()));
+            assert!(p.norm() <= 1.0 + 1e-6);
+            mean += p / TRIALS as f;
+
+            if p.norm() < 0.5 {
+                inner += 1;
+            }
+        }
+        assert!(mean.norm() < 0.05, "{}", mean);
+
+        // an eighth of the ball's volume lies within half of its radius
+        assert_close(inner as f / TRIALS as f, 0.125, 0.015);
+    }
+
+    #[test]
+    fn test_unit_polygon() {
+        for &sides in &[3, 6, 8] {
+            let angle = f::two_pi() / sides as f;
+            let apothem = (angle / 2.0).cos();
+            let mut mean = Vector2f::zeros();
+
+            for u in points(TRIALS) {
+                let p = unit_polygon(sides, &u);
+                mean += p / TRIALS as f;
+
+                // the point must be on the inner side of every edge
+                for i in 0..sides {
+                    let normal_angle = angle * (i as f + 0.5);
+                    let normal = Vector2f::new(normal_angle.cos(), normal_angle.sin());
+                    assert!(p.dot(&normal) <= apothem + 1e-5);
+                }
+            }
+            assert!(mean.norm() < 0.05, "{}", mean);
+        }
     }
-    v
 }
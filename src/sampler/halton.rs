@@ -0,0 +1,132 @@
+use super::{hash, permute, pixel_hash, to_unit, Sampler, ONE_MINUS_EPSILON};
+use crate::typedefs::*;
+
+/// The number of dimensions that use the Halton sequence. Dimensions beyond this get
+/// independent random values, since the sequence needs a larger prime for every dimension, and
+/// its quality drops as the primes get larger.
+const DIMENSIONS: usize = 256;
+
+/// A sampler that uses the Halton sequence, whose `d`th dimension is the radical inverse of the
+/// sample index in the `d`th prime base.
+///
+/// The digits of each dimension are scrambled with random permutations, which are different for
+/// every pixel and every digit. This keeps the sequence well distributed, while breaking up the
+/// correlations between dimensions and between neighboring pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Halton {
+    primes: Vec<u32>,
+    seed: u64,
+    pixel: u64,
+    index: u64,
+    dimension: usize,
+}
+
+/// The first `count` prime numbers
+fn primes(count: usize) -> Vec<u32> {
+    let mut primes: Vec<u32> = Vec::with_capacity(count);
+    let mut n = 2;
+
+    while primes.len() < count {
+        if primes
+            .iter()
+            .take_while(|&&p| p * p <= n)
+            .all(|&p| n % p != 0)
+        {
+            primes.push(n);
+        }
+        n += 1;
+    }
+    primes
+}
+
+/// The radical inverse of `index` in `base`, which mirrors the digits of the index about the
+/// decimal point, with each digit replaced by a random permutation of it. The permutations are
+/// chosen by `seed` and the position of the digit. The zeros after the last digit of the index
+/// are permuted as well, until they're too small to affect the result.
+fn scrambled_radical_inverse(base: u32, mut index: u64, seed: u64) -> f {
+    let inverse_base = 1.0 / base as f64;
+    let mut scale = 1.0;
+    let mut result = 0.0;
+    let mut position = 0;
+
+    while scale > 1e-9 {
+        let digit = (index % base as u64) as u32;
+        let permuted = permute(digit, base, hash(seed, position) as u32);
+        scale *= inverse_base;
+        result += permuted as f64 * scale;
+        index /= base as u64;
+        position += 1;
+    }
+    (result as f).min(ONE_MINUS_EPSILON)
+}
+
+impl Halton {
+    /// Create a sampler, where different seeds give different scrambles of the sequence
+    pub fn new(seed: u64) -> Self {
+        Self {
+            primes: primes(DIMENSIONS),
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.pixel = pixel_hash(self.seed, pixel);
+        self.index = index as u64;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let seed = hash(self.pixel, dimension as u64);
+
+        match self.primes.get(dimension) {
+            Some(&base) => scrambled_radical_inverse(base, self.index, seed),
+            None => to_unit((hash(seed, self.index) >> 32) as u32),
+        }
+    }
+
+    fn get_2d(&mut self) -> Vector2f {
+        Vector2f::new(self.get_1d(), self.get_1d())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::test::samples;
+
+    #[test]
+    fn test_primes() {
+        assert_eq!(primes(10), vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert_eq!(primes(DIMENSIONS)[DIMENSIONS - 1], 1619);
+    }
+
+    #[test]
+    fn test_strata() {
+        // the first `base^k` samples of a dimension have one sample in each interval of width
+        // `base^-k`, no matter how the digits are scrambled
+        let mut sampler = Halton::new(5);
+        let values = samples(&mut sampler, (7, 1), 27, 3);
+
+        for &(dimension, n) in &[(0, 16), (1, 27), (2, 25)] {
+            let mut strata: Vec<usize> = values[..n]
+                .iter()
+                .map(|s| (s[dimension] * n as f) as usize)
+                .collect();
+            strata.sort_unstable();
+            assert_eq!(strata, (0..n).collect::<Vec<_>>(), "{}", dimension);
+        }
+
+        // dimensions past the primes are still valid
+        sampler.start_sample((0, 0), 3);
+        for _ in 0..DIMENSIONS + 10 {
+            assert!((0.0..1.0).contains(&sampler.get_1d()));
+        }
+    }
+}
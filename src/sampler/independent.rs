@@ -0,0 +1,40 @@
+use super::{hash, pixel_hash, to_unit, Sampler};
+use crate::typedefs::*;
+
+/// A sampler that generates independent, uniformly distributed values.
+///
+/// Values are hashed from the pixel, the sample index and the dimension, so they look random,
+/// but they're the same every time they're generated.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct Independent {
+    seed: u64,
+    sample: u64,
+    dimension: u64,
+}
+
+impl Independent {
+    /// Create a sampler, where different seeds give different values
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            sample: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for Independent {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.sample = hash(pixel_hash(self.seed, pixel), index as u64);
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f {
+        self.dimension += 1;
+        to_unit((hash(self.sample, self.dimension) >> 32) as u32)
+    }
+
+    fn get_2d(&mut self) -> Vector2f {
+        Vector2f::new(self.get_1d(), self.get_1d())
+    }
+}
@@ -0,0 +1,251 @@
+//! Samplers generate the numbers in [0, 1) that the renderer turns into random choices, such as
+//! the position of a sample within its pixel, the point on the lens that a ray starts from, or
+//! the direction that a ray scatters in.
+//!
+//! Each sample of a pixel is a point in a space with as many dimensions as there are random
+//! choices along its path, which are requested one (or two) at a time. Rather than picking those
+//! points independently, most samplers spread the points for a pixel evenly across the space,
+//! which makes the estimate of the pixel converge much faster.
+//!
+//! Every sampler here is deterministic: the values for a dimension only depend on the seed, the
+//! pixel, the index of the sample, and the dimension, and not on the order that samples are
+//! generated in.
+
+use crate::typedefs::*;
+use std::fmt;
+use std::str::FromStr;
+
+pub mod halton;
+pub mod independent;
+pub mod sobol;
+pub mod stratified;
+
+pub use halton::Halton;
+pub use independent::Independent;
+pub use sobol::Sobol;
+pub use stratified::Stratified;
+
+/// The largest float that is less than one
+pub const ONE_MINUS_EPSILON: f = 1.0 - f::EPSILON / 2.0;
+
+/// The `Sampler` trait should be implemented for any strategy for generating sample values
+pub trait Sampler {
+    /// Start generating the values for sample `index` of the pixel in column `x` and row `y`.
+    /// The next value that is requested will be the first dimension of that sample.
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize);
+
+    /// The value of the next dimension of the current sample
+    fn get_1d(&mut self) -> f;
+
+    /// The values of the next two dimensions of the current sample. Pairs of dimensions are
+    /// well distributed with respect to each other, so this should be used whenever two values
+    /// are needed together, such as for a point on a surface.
+    fn get_2d(&mut self) -> Vector2f;
+}
+
+/// An owned reference to a sampler trait object that can be sent to another thread
+pub type SamplerRef = Box<dyn Sampler + Send>;
+
+/// The samplers that are built into the crate, which can be selected by name
+#[derive(Clone, Debug, Default, PartialEq, Eq, Copy)]
+pub enum SamplerKind {
+    /// Uniform random values (see `Independent`)
+    #[default]
+    Independent,
+
+    /// Jittered values in a stratified grid (see `Stratified`)
+    Stratified,
+
+    /// The scrambled Halton sequence (see `Halton`)
+    Halton,
+
+    /// The Owen scrambled Sobol sequence (see `Sobol`)
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Create a sampler of this kind, for an image that takes `samples` samples for each pixel.
+    /// Different seeds give different, but equally well distributed, values.
+    pub fn build(&self, samples: usize, seed: u64) -> SamplerRef {
+        match self {
+            SamplerKind::Independent => Box::new(Independent::new(seed)),
+            SamplerKind::Stratified => Box::new(Stratified::new(samples, seed)),
+            SamplerKind::Halton => Box::new(Halton::new(seed)),
+            SamplerKind::Sobol => Box::new(Sobol::new(seed)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!("unknown sampler \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for SamplerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SamplerKind::Independent => write!(f, "independent"),
+            SamplerKind::Stratified => write!(f, "stratified"),
+            SamplerKind::Halton => write!(f, "halton"),
+            SamplerKind::Sobol => write!(f, "sobol"),
+        }
+    }
+}
+
+/// Mix a value into a hash. This uses the finalizer from SplitMix64, so that similar inputs
+/// (such as neighboring pixels) give unrelated outputs.
+pub fn hash(seed: u64, value: u64) -> u64 {
+    let mut z = seed ^ value.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The hash of a pixel, which samplers use to decorrelate the values of different pixels
+fn pixel_hash(seed: u64, (x, y): (usize, usize)) -> u64 {
+    hash(hash(seed, x as u64), y as u64)
+}
+
+/// Convert 32 random bits to a float in [0, 1), keeping as many bits as a float can hold
+fn to_unit(bits: u32) -> f {
+    (bits >> 8) as f * (1.0 / (1 << 24) as f)
+}
+
+/// Map `i` to its position in a random permutation of [0, `len`), which is chosen by `seed`.
+/// This is the hash-based permutation from Kensler, "Correlated Multi-Jittered Sampling", which
+/// doesn't need to store the permutation.
+fn permute(mut i: u32, len: u32, seed: u32) -> u32 {
+    let p = seed;
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // the permutation is over the next power of two, so values that are out of range are
+    // permuted again until they land within it
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < len {
+            return (i.wrapping_add(p)) % len;
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    pub const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    /// The first `dimensions` values of each sample of a pixel
+    pub fn samples(
+        sampler: &mut dyn Sampler,
+        pixel: (usize, usize),
+        count: usize,
+        dimensions: usize,
+    ) -> Vec<Vec<f>> {
+        (0..count)
+            .map(|i| {
+                sampler.start_sample(pixel, i);
+                (0..dimensions).map(|_| sampler.get_1d()).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_names() {
+        for kind in &KINDS {
+            assert_eq!(kind.to_string().parse::<SamplerKind>(), Ok(*kind));
+        }
+        assert!("random".parse::<SamplerKind>().is_err());
+    }
+
+    #[test]
+    fn test_permute() {
+        for &len in &[1, 2, 7, 16, 100] {
+            for seed in 0..4 {
+                let mut values: Vec<u32> = (0..len).map(|i| permute(i, len, seed)).collect();
+                values.sort_unstable();
+                assert_eq!(values, (0..len).collect::<Vec<_>>());
+            }
+        }
+        let a: Vec<u32> = (0..16).map(|i| permute(i, 16, 1)).collect();
+        let b: Vec<u32> = (0..16).map(|i| permute(i, 16, 2)).collect();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        for kind in &KINDS {
+            let mut sampler = kind.build(16, 3);
+            let a = samples(sampler.as_mut(), (4, 5), 16, 10);
+
+            // the values don't depend on what was generated before
+            samples(sampler.as_mut(), (9, 1), 3, 50);
+            sampler.start_sample((4, 5), 7);
+            let b: Vec<f> = (0..10).map(|_| sampler.get_1d()).collect();
+            assert_eq!(a[7], b, "{}", kind);
+
+            // but they do depend on the pixel and the seed
+            let c = samples(sampler.as_mut(), (5, 4), 16, 10);
+            let d = samples(kind.build(16, 4).as_mut(), (4, 5), 16, 10);
+            assert_ne!(a, c, "{}", kind);
+            assert_ne!(a, d, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn test_uniform() {
+        for kind in &KINDS {
+            let mut sampler = kind.build(64, 0);
+            let mut sum = 0.0;
+            let mut count = 0;
+
+            for pixel in 0..16 {
+                for sample in samples(sampler.as_mut(), (pixel, 0), 64, 8) {
+                    for value in sample {
+                        assert!((0.0..1.0).contains(&value), "{} {}", kind, value);
+                        sum += value;
+                        count += 1;
+                    }
+                }
+            }
+            let mean = sum / count as f;
+            assert!((mean - 0.5).abs() < 0.02, "{} {}", kind, mean);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use super::{hash, pixel_hash, to_unit, Sampler};
+use crate::typedefs::*;
+
+/// A sampler that uses the first two dimensions of the Sobol sequence, with Owen scrambling.
+///
+/// This follows Burley, "Practical Hash-based Owen Scrambling". Every pair of dimensions is a
+/// differently scrambled copy of the 2D Sobol sequence, whose samples are also shuffled, so the
+/// pairs are well stratified by themselves without being correlated with each other. Owen
+/// scrambling keeps the stratification of the sequence, and the first `2^k` samples of a pixel
+/// have one sample in each of the `2^k` equal rectangles of every shape.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct Sobol {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+/// The second dimension of the Sobol sequence, as a binary fraction. (The first dimension is the
+/// van der Corput sequence, which is the index with its bits reversed.)
+fn sobol_second(mut index: u32) -> u32 {
+    let mut v = 1 << 31;
+    let mut result = 0;
+
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+/// A hash of `x` whose bits only depend on the bits of `x` that are less significant than them.
+/// This is the Laine-Karras permutation, with the constants from Burley's paper.
+fn laine_karras(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+/// Owen scramble a binary fraction, where each bit is flipped based on the bits that are more
+/// significant than it
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras(x.reverse_bits(), seed).reverse_bits()
+}
+
+impl Sobol {
+    /// Create a sampler, where different seeds give different scrambles of the sequence
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// The seeds for the shuffle and the scrambles of the next dimension or pair of dimensions
+    fn next_seeds(&mut self) -> (u32, u32, u32) {
+        self.dimension += 1;
+        let h = hash(self.pixel, self.dimension);
+        let h2 = hash(h, 0);
+        (h as u32, (h >> 32) as u32, h2 as u32)
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.pixel = pixel_hash(self.seed, pixel);
+        self.index = index as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f {
+        let (shuffle, scramble, _) = self.next_seeds();
+        let index = owen_scramble(self.index, shuffle);
+        to_unit(owen_scramble(index.reverse_bits(), scramble))
+    }
+
+    fn get_2d(&mut self) -> Vector2f {
+        let (shuffle, scramble_x, scramble_y) = self.next_seeds();
+        let index = owen_scramble(self.index, shuffle);
+        Vector2f::new(
+            to_unit(owen_scramble(index.reverse_bits(), scramble_x)),
+            to_unit(owen_scramble(sobol_second(index), scramble_y)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequence() {
+        let values: Vec<u32> = (0..4).map(sobol_second).collect();
+        assert_eq!(values, vec![0, 1 << 31, 3 << 30, 1 << 30]);
+    }
+
+    #[test]
+    fn test_strata() {
+        let mut sampler = Sobol::new(11);
+
+        for &n in &[16, 64] {
+            let points: Vec<Vector2f> = (0..n)
+                .map(|i| {
+                    sampler.start_sample((3, 8), i);
+                    sampler.get_1d();
+                    sampler.get_2d()
+                })
+                .collect();
+
+            // every elementary interval with an area of 1 / n has exactly one point, such as the
+            // strips along each axis, and the squares of a grid
+            let shapes = [(n, 1), (1, n), (4, n / 4), (n / 4, 4)];
+
+            for &(nx, ny) in &shapes {
+                let mut cells: Vec<usize> = points
+                    .iter()
+                    .map(|p| (p.y * ny as f) as usize * nx + (p.x * nx as f) as usize)
+                    .collect();
+                cells.sort_unstable();
+                assert_eq!(cells, (0..n).collect::<Vec<_>>(), "{}x{}", nx, ny);
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use super::{hash, permute, pixel_hash, to_unit, Sampler, ONE_MINUS_EPSILON};
+use crate::typedefs::*;
+
+/// A sampler that divides each dimension into as many equal strata as there are samples per
+/// pixel, and places each sample at a random position in a different stratum.
+///
+/// Pairs of dimensions are stratified together with a grid that's as close to square as
+/// possible. Each dimension uses its own random assignment of samples to strata, so that the
+/// dimensions aren't correlated with each other.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct Stratified {
+    samples: u32,
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl Stratified {
+    /// Create a sampler for `samples` samples per pixel
+    pub fn new(samples: usize, seed: u64) -> Self {
+        Self {
+            samples: samples.max(1) as u32,
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// The hash for the next dimension, which decides the assignment of samples to strata, and
+    /// a hash for the jitter of the current sample within its stratum
+    fn next_dimension(&mut self) -> (u32, u64) {
+        self.dimension += 1;
+        let h = hash(self.pixel, self.dimension);
+        (h as u32, hash(h, self.index as u64))
+    }
+}
+
+impl Sampler for Stratified {
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.pixel = pixel_hash(self.seed, pixel);
+        // samples beyond the expected count reuse the strata
+        self.index = index as u32 % self.samples;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f {
+        let (strata, jitter) = self.next_dimension();
+        let stratum = permute(self.index, self.samples, strata);
+        let value = (stratum as f + to_unit(jitter as u32)) / self.samples as f;
+        value.min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> Vector2f {
+        let (strata, jitter) = self.next_dimension();
+        // the grid may have more cells than there are samples, in which case some are skipped
+        let nx = (self.samples as f).sqrt().ceil() as u32;
+        let ny = self.samples.div_ceil(nx);
+        let cell = permute(self.index, nx * ny, strata);
+
+        let x = ((cell % nx) as f + to_unit(jitter as u32)) / nx as f;
+        let y = ((cell / nx) as f + to_unit((jitter >> 32) as u32)) / ny as f;
+        Vector2f::new(x.min(ONE_MINUS_EPSILON), y.min(ONE_MINUS_EPSILON))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sampler::test::samples;
+
+    #[test]
+    fn test_strata() {
+        for &n in &[4, 9, 10, 16] {
+            let mut sampler = Stratified::new(n, 0);
+
+            // every stratum of each dimension has one sample
+            for dimension in 0..3 {
+                let mut strata: Vec<usize> = samples(&mut sampler, (2, 3), n, 3)
+                    .iter()
+                    .map(|s| (s[dimension] * n as f) as usize)
+                    .collect();
+                strata.sort_unstable();
+                assert_eq!(strata, (0..n).collect::<Vec<_>>());
+            }
+
+            // every cell of the grid has at most one sample
+            let nx = (n as f).sqrt().ceil() as usize;
+            let ny = n.div_ceil(nx);
+            let mut cells: Vec<usize> = (0..n)
+                .map(|i| {
+                    sampler.start_sample((2, 3), i);
+                    let p = sampler.get_2d();
+                    (p.y * ny as f) as usize * nx + (p.x * nx as f) as usize
+                })
+                .collect();
+            cells.sort_unstable();
+            cells.dedup();
+            assert_eq!(cells.len(), n);
+        }
+    }
+}
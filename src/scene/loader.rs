@@ -0,0 +1,1288 @@
+//! Scene description files
+//!
+//! Scenes can be described in a YAML file and loaded with `load`. A scene file is a mapping with
+//! the following top level keys, all of which are optional except for `objects`:
+//!
+//! ```yaml
+//! # The camera that the scene is viewed through. If it's omitted, the camera is at the origin
+//! # and looks down the negative z axis with a 90 degree vertical field of view.
+//! camera:
+//!   type: look_at
+//!   eye: [0, 1, 2]
+//!   target: [0, 0, -1]
+//!   up: [0, 1, 0]  # optional, defaults to the y axis
+//!   fov: 40        # the vertical field of view in degrees, defaults to 90
+//!   aspect: 1.5    # optional, defaults to the aspect ratio of the output image
+//!
+//! # A thin lens camera has depth of field. It takes the same parameters as `look_at`, along with
+//! # the lens's parameters.
+//! camera:
+//!   type: thin_lens
+//!   eye: [0, 1, 2]
+//!   target: [0, 0, -1]
+//!   aperture: 0.1        # the radius of the aperture
+//!   focus_distance: 3.2  # optional, defaults to the distance between the eye and the target
+//!   blades: 6            # optional, makes the aperture a polygon rather than a circle
+//!
+//! # Alternatively, a pinhole camera can be given by its image plane. Its parameters all default
+//! # to the values of `Pinhole::default()`. This is the default if the camera has no `type`.
+//! camera:
+//!   type: pinhole
+//!   origin: [0, 0, 0]
+//!   lower_left: [-2, -1, -1]
+//!   horizontal: [4, 0, 0]
+//!   vertical: [0, 2, 0]
+//!
+//! # Settings for the renderer. Options that are passed on the command line take precedence.
+//! render:
+//!   width: 400
+//!   height: 200
+//!   samples: 100  # samples per pixel
+//!   depth: 50     # the maximum number of bounces per path
+//!   min_depth: 3  # the bounces before paths can be ended by Russian roulette, defaults to 3
+//!   seed: 42            # renders with the same seed are identical, defaults to 0
+//!   sampler: sobol      # independent (the default), stratified, halton or sobol
+//!   filter: mitchell    # box (the default), tent, gaussian, mitchell or lanczos
+//!   filter_radius: 2    # optional, in pixels, defaults to a radius that suits the filter
+//!   non_finite: drop    # drop (the default) or zero samples with NaN or infinite components
+//!   max_luminance: 20   # optional, samples that are brighter are scaled down to this luminance
+//!   # How 8 bit images are made displayable, which doesn't affect high dynamic range output. The
+//!   # operator is one of clamp (the default), reinhard, extended_reinhard, aces or hable.
+//!   tone_map: aces
+//!   exposure: 0.5     # the brightness adjustment in stops, defaults to 0
+//!   white_point: 4.0  # the luminance that extended_reinhard maps to white, defaults to 4
+//!
+//! # The radiance for rays that don't hit anything. This is either a `gradient` with `bottom` and
+//! # `top` colors (the default is a blue sky), or a `constant` with a single `color`.
+//! background:
+//!   type: constant
+//!   color: [0, 0, 0]
+//!
+//! # The background can also be an equirectangular environment map, which is a light that's
+//! # sampled in proportion to its brightness. The image is resolved relative to the scene file.
+//! background:
+//!   type: environment
+//!   file: sky.hdr   # a Radiance HDR or PFM image, whose center is in the -z direction
+//!   rotation: 90    # optional, degrees counterclockwise around the y axis, defaults to 0
+//!   intensity: 2    # optional, scales the radiance of the image, defaults to 1
+//!
+//! # Named materials that objects can refer to
+//! materials:
+//!   red:
+//!     type: diffuse
+//!     albedo: [0.8, 0.3, 0.3]
+//!   gold:
+//!     type: mirror
+//!     albedo: [0.8, 0.6, 0.2]
+//!     fuzziness: 0.1  # optional, defaults to 0
+//!   glass:
+//!     type: dielectric
+//!     ior: 1.5
+//!     albedo: [1, 1, 1]  # optional, defaults to white
+//!   lamp:
+//!     type: emissive
+//!     emission: [4, 4, 4]  # radiance, which can be brighter than 1
+//!
+//! # The geometry in the scene. Each object's material is either the name of a material defined
+//! # above, or a material definition written inline.
+//! objects:
+//!   - type: sphere
+//!     center: [0, 0, -1]
+//!     radius: 0.5
+//!     material: red
+//!   - type: triangle
+//!     vertices: [[-1, 0, -2], [1, 0, -2], [0, 1, -2]]
+//!     material: {type: diffuse, albedo: [0.5, 0.5, 0.5]}
+//!   # OBJ files are resolved relative to the scene file. The materials from the OBJ's material
+//!   # libraries are used unless `material` is specified, in which case it replaces all of them.
+//!   - type: mesh
+//!     file: bunny.obj
+//!     material: gold
+//!
+//! # Lights that aren't part of the geometry. Objects with emissive materials are lights as well.
+//! lights:
+//!   - type: point
+//!     position: [0, 3, 0]
+//!     intensity: [10, 10, 10]  # the power per unit of solid angle
+//!   - type: spot
+//!     position: [0, 3, 0]
+//!     direction: [0, -1, 0]    # where the center of the cone points
+//!     intensity: [20, 20, 20]  # along the center of the cone
+//!     angle: 30                # between the center and the edge of the cone, in degrees
+//!     falloff_start: 20        # optional, where the light starts to fade out, defaults to angle
+//!   - type: directional
+//!     direction: [-1, -2, -1]   # the direction that the light travels in
+//!     irradiance: [3, 3, 2.5]   # on a surface that faces the light
+//!     angular_diameter: 0.53    # optional, the size of the light in degrees, defaults to 0
+//! ```
+//!
+//! Vectors and colors are written as lists of three numbers. Errors, whether they're syntax
+//! errors or invalid values, are reported with the line that they occurred on.
+
+use super::node::{self, Node, Value};
+use super::{CameraDescription, LensDescription, RenderSettings, SceneDescription};
+use crate::background::Background;
+use crate::camera::{Aperture, Pinhole};
+use crate::environment::EnvironmentMap;
+use crate::hittable::{BSDFRef, ObjRef, ObjVec};
+use crate::light::Light;
+use crate::material::dielectric::Dielectric;
+use crate::material::diffuse::Diffuse;
+use crate::material::emissive::Emissive;
+use crate::material::mirror::Mirror;
+use crate::obj;
+use crate::primitives::sphere::Sphere;
+use crate::primitives::triangle::Triangle;
+use crate::typedefs::*;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// An error that occurred while loading a scene description
+#[derive(Debug)]
+pub enum SceneError {
+    /// The scene file could not be read
+    Io { path: PathBuf, source: io::Error },
+
+    /// The scene file was read, but it was malformed or contained invalid values
+    Parse {
+        /// The name of the file that contained the error
+        file: String,
+
+        /// The (1-indexed) line that the error occurred on
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { .. } => None,
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, SceneError>;
+
+/// Load a scene description from a YAML file
+pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneDescription> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse(&path.display().to_string(), &source, dir)
+}
+
+/// Parse a scene description from a YAML string. `file` is the name that is used in error
+/// messages, and `dir` is the directory that relative paths in the scene are resolved against.
+pub fn parse(file: &str, source: &str, dir: &Path) -> Result<SceneDescription> {
+    let root = node::parse(source).map_err(|e| SceneError::Parse {
+        file: file.to_string(),
+        line: e.marker().line(),
+        message: format!("invalid YAML: {}", e),
+    })?;
+    let ctx = Context { file, dir };
+    ctx.scene(&root)
+}
+
+/// The description of a material, which can be used to create any number of BSDFs
+#[derive(Clone, Debug, PartialEq)]
+enum MaterialDesc {
+    Diffuse { albedo: Color3f },
+    Mirror { albedo: Color3f, fuzziness: f },
+    Dielectric { ior: f, albedo: Color3f },
+    Emissive { emission: Color3f },
+}
+
+impl MaterialDesc {
+    fn build(&self) -> BSDFRef<f> {
+        match *self {
+            MaterialDesc::Diffuse { albedo } => Box::new(Diffuse { albedo }),
+            MaterialDesc::Mirror { albedo, fuzziness } => Box::new(Mirror { albedo, fuzziness }),
+            MaterialDesc::Dielectric { ior, albedo } => Box::new(Dielectric { ior, albedo }),
+            MaterialDesc::Emissive { emission } => Box::new(Emissive { emission }),
+        }
+    }
+}
+
+/// Holds the information needed to report errors and resolve paths while walking the document
+struct Context<'a> {
+    file: &'a str,
+    dir: &'a Path,
+}
+
+impl<'a> Context<'a> {
+    fn error<T>(&self, node: &Node, message: String) -> Result<T> {
+        Err(SceneError::Parse {
+            file: self.file.to_string(),
+            line: node.line,
+            message,
+        })
+    }
+
+    /// Make sure that the node is a mapping that only contains keys from `allowed`
+    fn check_keys(&self, node: &Node, what: &str, allowed: &[&str]) -> Result<()> {
+        let entries = match &node.value {
+            Value::Mapping(entries) => entries,
+            _ => return self.error(node, format!("expected {} to be a mapping", what)),
+        };
+
+        for (key, _) in entries {
+            match &key.value {
+                Value::String(k) if allowed.contains(&k.as_str()) => (),
+                Value::String(k) => {
+                    return self.error(
+                        key,
+                        format!(
+                            "unknown key \"{}\" in {} (expected one of: {})",
+                            k,
+                            what,
+                            allowed.join(", ")
+                        ),
+                    )
+                }
+                _ => return self.error(key, format!("keys in {} must be strings", what)),
+            }
+        }
+        Ok(())
+    }
+
+    fn required<'n>(&self, node: &'n Node, key: &str, what: &str) -> Result<&'n Node> {
+        match node.get(key) {
+            Some(value) => Ok(value),
+            None => self.error(node, format!("{} is missing \"{}\"", what, key)),
+        }
+    }
+
+    fn float(&self, node: &Node) -> Result<f> {
+        match node.value {
+            Value::Integer(i) => Ok(i as f),
+            Value::Real(r) => Ok(r as f),
+            _ => self.error(node, format!("expected a number, found {}", node.kind())),
+        }
+    }
+
+    fn unsigned(&self, node: &Node) -> Result<usize> {
+        match node.value {
+            Value::Integer(i) if i >= 0 => Ok(i as usize),
+            _ => self.error(node, "expected a non-negative integer".to_string()),
+        }
+    }
+
+    /// Read a number that must be greater than zero. `what` is used in the error message.
+    fn positive(&self, node: &Node, what: &str) -> Result<f> {
+        match self.float(node)? {
+            x if x > 0.0 => Ok(x),
+            _ => self.error(node, format!("{} must be positive", what)),
+        }
+    }
+
+    /// Read a string and parse it into one of the named options of `T`
+    fn parsed<T: FromStr<Err = String>>(&self, node: &Node) -> Result<T> {
+        match self.string(node)?.parse() {
+            Ok(value) => Ok(value),
+            Err(message) => self.error(node, message),
+        }
+    }
+
+    fn string<'n>(&self, node: &'n Node) -> Result<&'n str> {
+        match &node.value {
+            Value::String(s) => Ok(s),
+            _ => self.error(node, format!("expected a string, found {}", node.kind())),
+        }
+    }
+
+    fn list<'n>(&self, node: &'n Node) -> Result<&'n [Node]> {
+        match &node.value {
+            Value::Sequence(items) => Ok(items),
+            _ => self.error(node, format!("expected a list, found {}", node.kind())),
+        }
+    }
+
+    fn vector(&self, node: &Node) -> Result<Vector3f> {
+        let items = self.list(node)?;
+
+        if items.len() != 3 {
+            return self.error(
+                node,
+                format!("expected a list of 3 numbers, found {} items", items.len()),
+            );
+        }
+        Ok(Vector3f::new(
+            self.float(&items[0])?,
+            self.float(&items[1])?,
+            self.float(&items[2])?,
+        ))
+    }
+
+    /// Read an optional vector, falling back to `default` if the key is absent
+    fn vector_or(&self, node: &Node, key: &str, default: Vector3f) -> Result<Vector3f> {
+        node.get(key)
+            .map_or(Ok(default), |value| self.vector(value))
+    }
+
+    /// The value of the `type` key for a node, or `default` if the node doesn't specify one
+    fn type_of<'n>(&self, node: &'n Node, what: &str, default: Option<&'n str>) -> Result<&'n str> {
+        match (node.get("type"), default) {
+            (Some(ty), _) => self.string(ty),
+            (None, Some(default)) => Ok(default),
+            (None, None) => self.error(node, format!("{} is missing \"type\"", what)),
+        }
+    }
+
+    fn scene(&self, root: &Node) -> Result<SceneDescription> {
+        if root.value == Value::Null {
+            return self.error(root, "the scene is empty".to_string());
+        }
+        self.check_keys(
+            root,
+            "the scene",
+            &[
+                "camera",
+                "render",
+                "background",
+                "materials",
+                "objects",
+                "lights",
+            ],
+        )?;
+
+        let mut materials = HashMap::new();
+
+        if let Some(node) = root.get("materials") {
+            let entries = match &node.value {
+                Value::Mapping(entries) => entries,
+                _ => return self.error(node, "expected materials to be a mapping".to_string()),
+            };
+
+            for (key, value) in entries {
+                let name = self.string(key)?;
+                materials.insert(name.to_string(), self.material_def(value)?);
+            }
+        }
+
+        let objects_node = self.required(root, "objects", "the scene")?;
+        let mut objects: ObjVec<f> = Vec::new();
+
+        for node in self.list(objects_node)? {
+            self.object(node, &materials, &mut objects)?;
+        }
+
+        let mut lights = Vec::new();
+
+        if let Some(node) = root.get("lights") {
+            for light in self.list(node)? {
+                lights.push(self.light(light)?);
+            }
+        }
+
+        // an environment map replaces the background, since it's a light that can be sampled
+        let background = match root.get("background") {
+            Some(node) if self.type_of(node, "the background", None)? == "environment" => {
+                lights.push(self.environment(node)?);
+                Background::Constant(Color3f::zeros())
+            }
+            Some(node) => self.background(node)?,
+            None => Background::default(),
+        };
+
+        Ok(SceneDescription {
+            objects,
+            lights,
+            camera: match root.get("camera") {
+                Some(node) => self.camera(node)?,
+                None => CameraDescription::default(),
+            },
+            background,
+            settings: match root.get("render") {
+                Some(node) => self.settings(node)?,
+                None => RenderSettings::default(),
+            },
+        })
+    }
+
+    fn camera(&self, node: &Node) -> Result<CameraDescription> {
+        match self.type_of(node, "the camera", Some("pinhole"))? {
+            "pinhole" => {
+                self.check_keys(
+                    node,
+                    "the camera",
+                    &["type", "origin", "lower_left", "horizontal", "vertical"],
+                )?;
+                let default = Pinhole::default();
+                Ok(CameraDescription::Pinhole(Pinhole {
+                    origin: self.vector_or(node, "origin", default.origin)?,
+                    lower_left: self.vector_or(node, "lower_left", default.lower_left)?,
+                    horizontal: self.vector_or(node, "horizontal", default.horizontal)?,
+                    vertical: self.vector_or(node, "vertical", default.vertical)?,
+                }))
+            }
+            ty @ "look_at" | ty @ "thin_lens" => {
+                let mut keys = vec!["type", "eye", "target", "up", "fov", "aspect"];
+
+                if ty == "thin_lens" {
+                    keys.extend(&["aperture", "focus_distance", "blades"]);
+                }
+                self.check_keys(node, "the camera", &keys)?;
+                let eye = self.vector(self.required(node, "eye", "the camera")?)?;
+                let target_node = self.required(node, "target", "the camera")?;
+                let target = self.vector(target_node)?;
+                let up = self.vector_or(node, "up", Vector3f::new(0.0, 1.0, 0.0))?;
+
+                if let Err(message) = Pinhole::check_look_at(&eye, &target, &up) {
+                    // point at the value that's most likely to be wrong
+                    let culprit = if eye == target {
+                        target_node
+                    } else {
+                        node.get("up").unwrap_or(node)
+                    };
+                    return self.error(culprit, message.to_string());
+                }
+                let fov = node.get("fov").map_or(Ok(90.0), |n| self.float(n))?;
+
+                if fov <= 0.0 || fov >= 180.0 {
+                    return self.error(
+                        node.get("fov").unwrap(),
+                        "the field of view must be between 0 and 180 degrees".to_string(),
+                    );
+                }
+                let aspect = node
+                    .get("aspect")
+                    .map(|n| self.positive(n, "the aspect ratio"))
+                    .transpose()?;
+                let lens = match ty {
+                    "thin_lens" => Some(self.lens(node)?),
+                    _ => None,
+                };
+                Ok(CameraDescription::LookAt {
+                    eye,
+                    target,
+                    up,
+                    vfov: fov,
+                    aspect,
+                    lens,
+                })
+            }
+            other => self.error(node, format!("unknown camera type \"{}\"", other)),
+        }
+    }
+
+    fn lens(&self, node: &Node) -> Result<LensDescription> {
+        let radius = self.float(self.required(node, "aperture", "a thin lens camera")?)?;
+
+        if radius < 0.0 {
+            return self.error(
+                node.get("aperture").unwrap(),
+                "the aperture can't be negative".to_string(),
+            );
+        }
+        let aperture = match node.get("blades") {
+            Some(n) => match self.unsigned(n)? {
+                blades if blades >= 3 => Aperture::Polygonal(blades),
+                _ => return self.error(n, "the aperture needs at least 3 blades".to_string()),
+            },
+            None => Aperture::Circular,
+        };
+
+        Ok(LensDescription {
+            radius,
+            focus_distance: node
+                .get("focus_distance")
+                .map(|n| self.positive(n, "the focus distance"))
+                .transpose()?,
+            aperture,
+        })
+    }
+
+    fn background(&self, node: &Node) -> Result<Background<f>> {
+        match self.type_of(node, "the background", None)? {
+            "gradient" => {
+                self.check_keys(node, "the background", &["type", "bottom", "top"])?;
+                let (default_bottom, default_top) = match Background::default() {
+                    Background::Gradient { bottom, top } => (bottom, top),
+                    _ => unreachable!(),
+                };
+                Ok(Background::Gradient {
+                    bottom: self.vector_or(node, "bottom", default_bottom)?,
+                    top: self.vector_or(node, "top", default_top)?,
+                })
+            }
+            "constant" => {
+                self.check_keys(node, "the background", &["type", "color"])?;
+                let color = self.required(node, "color", "the background")?;
+                Ok(Background::Constant(self.vector(color)?))
+            }
+            other => self.error(node, format!("unknown background type \"{}\"", other)),
+        }
+    }
+
+    fn environment(&self, node: &Node) -> Result<Light<f>> {
+        self.check_keys(
+            node,
+            "the background",
+            &["type", "file", "rotation", "intensity"],
+        )?;
+        let file = self.required(node, "file", "an environment map")?;
+        let rotation = node.get("rotation").map_or(Ok(0.0), |n| self.float(n))?;
+        let intensity = node
+            .get("intensity")
+            .map_or(Ok(1.0), |n| self.positive(n, "the intensity"))?;
+
+        match EnvironmentMap::load(self.dir.join(self.string(file)?), rotation, intensity) {
+            Ok(map) => Ok(Light::Environment(Box::new(map))),
+            Err(e) => self.error(file, format!("could not load environment map: {}", e)),
+        }
+    }
+
+    /// Read a vector that is normalized to be a direction, which can't be zero
+    fn direction(&self, node: &Node) -> Result<Vector3f> {
+        let direction = self.vector(node)?;
+
+        if direction == Vector3f::zeros() {
+            return self.error(node, "the direction can't be zero".to_string());
+        }
+        Ok(direction.normalize())
+    }
+
+    /// Read an angle in degrees, which must be between `min` and `max`
+    fn angle(&self, node: &Node, what: &str, min: f, max: f) -> Result<f> {
+        match self.float(node)? {
+            angle if angle >= min && angle <= max => Ok(angle),
+            _ => self.error(
+                node,
+                format!("{} must be between {} and {} degrees", what, min, max),
+            ),
+        }
+    }
+
+    fn light(&self, node: &Node) -> Result<Light<f>> {
+        match self.type_of(node, "a light", None)? {
+            "point" => {
+                self.check_keys(node, "a point light", &["type", "position", "intensity"])?;
+                let position = self.required(node, "position", "a point light")?;
+                let intensity = self.required(node, "intensity", "a point light")?;
+                Ok(Light::Point {
+                    position: self.vector(position)?,
+                    intensity: self.vector(intensity)?,
+                })
+            }
+            "spot" => {
+                self.check_keys(
+                    node,
+                    "a spot light",
+                    &[
+                        "type",
+                        "position",
+                        "direction",
+                        "intensity",
+                        "angle",
+                        "falloff_start",
+                    ],
+                )?;
+                let position = self.required(node, "position", "a spot light")?;
+                let direction = self.required(node, "direction", "a spot light")?;
+                let intensity = self.required(node, "intensity", "a spot light")?;
+                let angle = self.required(node, "angle", "a spot light")?;
+                let angle = self.angle(angle, "the angle of a spot light", 0.0, 180.0)?;
+                let falloff_start = match node.get("falloff_start") {
+                    Some(n) => self.angle(n, "the start of the falloff", 0.0, angle)?,
+                    None => angle,
+                };
+                Ok(Light::Spot {
+                    position: self.vector(position)?,
+                    direction: self.direction(direction)?,
+                    intensity: self.vector(intensity)?,
+                    cos_angle: angle.to_radians().cos(),
+                    cos_falloff: falloff_start.to_radians().cos(),
+                })
+            }
+            "directional" => {
+                self.check_keys(
+                    node,
+                    "a directional light",
+                    &["type", "direction", "irradiance", "angular_diameter"],
+                )?;
+                let direction = self.required(node, "direction", "a directional light")?;
+                let irradiance = self.required(node, "irradiance", "a directional light")?;
+                let diameter = match node.get("angular_diameter") {
+                    Some(n) => self.angle(n, "the angular diameter", 0.0, 180.0)?,
+                    None => 0.0,
+                };
+                Ok(Light::Directional {
+                    direction: self.direction(direction)?,
+                    irradiance: self.vector(irradiance)?,
+                    cos_max: (diameter / 2.0).to_radians().cos(),
+                })
+            }
+            other => self.error(node, format!("unknown light type \"{}\"", other)),
+        }
+    }
+
+    fn settings(&self, node: &Node) -> Result<RenderSettings> {
+        self.check_keys(
+            node,
+            "the render settings",
+            &[
+                "width",
+                "height",
+                "samples",
+                "depth",
+                "min_depth",
+                "seed",
+                "sampler",
+                "filter",
+                "filter_radius",
+                "non_finite",
+                "max_luminance",
+                "tone_map",
+                "exposure",
+                "white_point",
+            ],
+        )?;
+        let get = |key| node.get(key).map(|n| self.unsigned(n)).transpose();
+        // counts that have to be at least one, like the size of the image
+        let count = |key, what: &str| {
+            node.get(key)
+                .map(|n| match self.unsigned(n)? {
+                    0 => self.error(n, format!("{} must be positive", what)),
+                    count => Ok(count),
+                })
+                .transpose()
+        };
+
+        Ok(RenderSettings {
+            width: count("width", "the width")?,
+            height: count("height", "the height")?,
+            samples: count("samples", "the number of samples")?,
+            depth: get("depth")?.map(|d| d as u),
+            min_depth: get("min_depth")?.map(|d| d as u),
+            seed: get("seed")?.map(|s| s as u64),
+            sampler: node.get("sampler").map(|n| self.parsed(n)).transpose()?,
+            filter: node.get("filter").map(|n| self.parsed(n)).transpose()?,
+            filter_radius: node
+                .get("filter_radius")
+                .map(|n| self.positive(n, "the filter radius"))
+                .transpose()?,
+            non_finite: node.get("non_finite").map(|n| self.parsed(n)).transpose()?,
+            max_luminance: node
+                .get("max_luminance")
+                .map(|n| self.positive(n, "the maximum luminance"))
+                .transpose()?,
+            tone_map: node.get("tone_map").map(|n| self.parsed(n)).transpose()?,
+            exposure: node.get("exposure").map(|n| self.float(n)).transpose()?,
+            white_point: node
+                .get("white_point")
+                .map(|n| self.positive(n, "the white point"))
+                .transpose()?,
+        })
+    }
+
+    fn material_def(&self, node: &Node) -> Result<MaterialDesc> {
+        match self.type_of(node, "a material", None)? {
+            "diffuse" => {
+                self.check_keys(node, "a diffuse material", &["type", "albedo"])?;
+                let albedo = self.required(node, "albedo", "a diffuse material")?;
+                Ok(MaterialDesc::Diffuse {
+                    albedo: self.vector(albedo)?,
+                })
+            }
+            "mirror" => {
+                self.check_keys(node, "a mirror material", &["type", "albedo", "fuzziness"])?;
+                let albedo = self.required(node, "albedo", "a mirror material")?;
+                Ok(MaterialDesc::Mirror {
+                    albedo: self.vector(albedo)?,
+                    fuzziness: node.get("fuzziness").map_or(Ok(0.0), |n| self.float(n))?,
+                })
+            }
+            "dielectric" => {
+                self.check_keys(node, "a dielectric material", &["type", "ior", "albedo"])?;
+                let ior = self.required(node, "ior", "a dielectric material")?;
+                Ok(MaterialDesc::Dielectric {
+                    ior: self.positive(ior, "the index of refraction")?,
+                    albedo: self.vector_or(node, "albedo", Color3f::new(1.0, 1.0, 1.0))?,
+                })
+            }
+            "emissive" => {
+                self.check_keys(node, "an emissive material", &["type", "emission"])?;
+                let emission = self.required(node, "emission", "an emissive material")?;
+                Ok(MaterialDesc::Emissive {
+                    emission: self.vector(emission)?,
+                })
+            }
+            other => self.error(node, format!("unknown material type \"{}\"", other)),
+        }
+    }
+
+    /// A material reference, which is either the name of a material or an inline definition
+    fn material(
+        &self,
+        node: &Node,
+        materials: &HashMap<String, MaterialDesc>,
+    ) -> Result<MaterialDesc> {
+        match &node.value {
+            Value::String(name) => match materials.get(name) {
+                Some(material) => Ok(material.clone()),
+                None => self.error(node, format!("material \"{}\" is not defined", name)),
+            },
+            _ => self.material_def(node),
+        }
+    }
+
+    fn object(
+        &self,
+        node: &Node,
+        materials: &HashMap<String, MaterialDesc>,
+        objects: &mut ObjVec<f>,
+    ) -> Result<()> {
+        match self.type_of(node, "an object", None)? {
+            "sphere" => {
+                self.check_keys(node, "a sphere", &["type", "center", "radius", "material"])?;
+                let center = self.required(node, "center", "a sphere")?;
+                let radius = self.required(node, "radius", "a sphere")?;
+                let material = self.required(node, "material", "a sphere")?;
+                objects.push(ObjRef {
+                    hittable: Box::new(Sphere {
+                        center: self.vector(center)?,
+                        radius: self.positive(radius, "the sphere radius")?,
+                    }),
+                    material: self.material(material, materials)?.build(),
+                });
+            }
+            "triangle" => {
+                self.check_keys(node, "a triangle", &["type", "vertices", "material"])?;
+                let vertices_node = self.required(node, "vertices", "a triangle")?;
+                let material = self.required(node, "material", "a triangle")?;
+                let vertices = self.list(vertices_node)?;
+
+                if vertices.len() != 3 {
+                    return self.error(
+                        vertices_node,
+                        format!("a triangle needs 3 vertices, found {}", vertices.len()),
+                    );
+                }
+                objects.push(ObjRef {
+                    hittable: Box::new(Triangle {
+                        vertices: [
+                            self.vector(&vertices[0])?,
+                            self.vector(&vertices[1])?,
+                            self.vector(&vertices[2])?,
+                        ],
+                    }),
+                    material: self.material(material, materials)?.build(),
+                });
+            }
+            "mesh" => {
+                self.check_keys(node, "a mesh", &["type", "file", "material"])?;
+                let file = self.required(node, "file", "a mesh")?;
+                let material = node
+                    .get("material")
+                    .map(|m| self.material(m, materials))
+                    .transpose()?;
+                let mut mesh = match obj::load(self.dir.join(self.string(file)?)) {
+                    Ok(mesh) => mesh,
+                    Err(e) => return self.error(file, format!("could not load mesh: {}", e)),
+                };
+
+                if let Some(material) = material {
+                    for obj in mesh.iter_mut() {
+                        obj.material = material.build();
+                    }
+                }
+                objects.append(&mut mesh);
+            }
+            other => self.error(node, format!("unknown object type \"{}\"", other))?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::filter::FilterKind;
+    use crate::hittable::any_hit;
+    use crate::pfm;
+    use crate::raster::Raster;
+    use crate::renderer::NonFinite;
+    use crate::sampler::{Independent, SamplerKind};
+    use crate::tonemap::Operator;
+    use std::env;
+
+    fn parse_str(source: &str) -> Result<SceneDescription> {
+        parse("test.yaml", source, Path::new(""))
+    }
+
+    /// Assert that a scene fails to parse with an error on a particular line
+    fn assert_error_line(source: &str, expected_line: usize) {
+        match parse_str(source) {
+            Err(SceneError::Parse { line, message, .. }) => {
+                assert_eq!(line, expected_line, "wrong line for error: {}", message)
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected the scene to be invalid"),
+        }
+    }
+
+    #[test]
+    fn test_example_scene() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/cover.yaml");
+        let scene = load(path).unwrap();
+        assert_eq!(scene.objects.len(), 4);
+        assert_eq!(scene.settings.width, Some(400));
+        assert_eq!(scene.settings.height, Some(200));
+        assert_eq!(scene.background, Background::default());
+    }
+
+    #[test]
+    fn test_minimal_scene() {
+        let scene = parse_str(
+            "
+objects:
+  - type: sphere
+    center: [0, 0, -1]
+    radius: 0.5
+    material: {type: diffuse, albedo: [0.5, 0.5, 0.5]}
+",
+        )
+        .unwrap();
+        assert_eq!(scene.objects.len(), 1);
+        assert_eq!(scene.settings, RenderSettings::default());
+
+        let ray = scene
+            .camera
+            .build((200, 100))
+            .get_ray(0.5, 0.5, &mut Independent::new(0));
+        let (hr, _) = any_hit(&scene.objects, &ray, Some(0.001), None).unwrap();
+        assert_eq!(hr.t, 0.5);
+    }
+
+    #[test]
+    fn test_full_scene() {
+        let scene = parse_str(
+            "
+camera:
+  origin: [0, 0, 1]
+render:
+  width: 20
+  height: 10
+  samples: 4
+  depth: 5
+  min_depth: 2
+  seed: 1234
+  sampler: halton
+  filter: lanczos
+  filter_radius: 2
+  non_finite: zero
+  max_luminance: 50
+  tone_map: extended_reinhard
+  exposure: -1.5
+  white_point: 8
+background:
+  type: constant
+  color: [0.1, 0.2, 0.3]
+materials:
+  red:
+    type: diffuse
+    albedo: [1, 0, 0]
+  shiny:
+    type: mirror
+    albedo: [1, 1, 1]
+    fuzziness: 0.5
+  glass:
+    type: dielectric
+    ior: 1.5
+  lamp:
+    type: emissive
+    emission: [4, 4, 2]
+objects:
+  - type: sphere
+    center: [0, 0, -1]
+    radius: 0.5
+    material: red
+  - type: triangle
+    vertices: [[-1, -1, -3], [1, -1, -3], [0, 1, -3]]
+    material: shiny
+  - type: sphere
+    center: [0, 3, -1]
+    radius: 1
+    material: lamp
+",
+        )
+        .unwrap();
+        assert_eq!(scene.objects.len(), 3);
+        assert_eq!(
+            scene.settings,
+            RenderSettings {
+                width: Some(20),
+                height: Some(10),
+                samples: Some(4),
+                depth: Some(5),
+                min_depth: Some(2),
+                seed: Some(1234),
+                sampler: Some(SamplerKind::Halton),
+                filter: Some(FilterKind::Lanczos),
+                filter_radius: Some(2.0),
+                non_finite: Some(NonFinite::Zero),
+                max_luminance: Some(50.0),
+                tone_map: Some(Operator::ExtendedReinhard),
+                exposure: Some(-1.5),
+                white_point: Some(8.0),
+            }
+        );
+        assert_eq!(
+            scene.background,
+            Background::Constant(Color3f::new(0.1, 0.2, 0.3))
+        );
+        let ray = scene
+            .camera
+            .build((20, 10))
+            .get_ray(0.5, 0.5, &mut Independent::new(0));
+        assert_eq!(ray.origin, Vector3f::new(0.0, 0.0, 1.0));
+
+        let (hr, obj) = any_hit(&scene.objects, &ray, Some(0.001), None).unwrap();
+        assert_eq!(obj.material.emitted(&ray, &hr), Color3f::zeros());
+        let up = Ray3f::new(
+            &Vector3f::new(0.0, 1.0, -1.0),
+            &Vector3f::new(0.0, 1.0, 0.0),
+        );
+        let (hr, obj) = any_hit(&scene.objects, &up, Some(0.001), None).unwrap();
+        assert_eq!(obj.material.emitted(&up, &hr), Color3f::new(4.0, 4.0, 2.0));
+        assert!(obj
+            .material
+            .scatter(&up, &hr, &mut Independent::new(0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_look_at_camera() {
+        let source = "
+camera:
+  type: look_at
+  eye: [0, 0, 1]
+  target: [0, 0, -1]
+  fov: 60
+objects: []
+";
+        let scene = parse_str(source).unwrap();
+        assert_eq!(
+            scene.camera,
+            CameraDescription::LookAt {
+                eye: Vector3f::new(0.0, 0.0, 1.0),
+                target: Vector3f::new(0.0, 0.0, -1.0),
+                up: Vector3f::new(0.0, 1.0, 0.0),
+                vfov: 60.0,
+                aspect: None,
+                lens: None,
+            }
+        );
+
+        // the aspect ratio follows the resolution unless the scene sets it
+        let ray = scene
+            .camera
+            .build((300, 100))
+            .get_ray(1.0, 0.5, &mut Independent::new(0));
+        let expected = 3.0 * (30.0 as f).to_radians().tan();
+        assert!((ray.direction.x / -ray.direction.z - expected).abs() < 1e-5);
+
+        let scene = parse_str(&source.replace("fov: 60", "fov: 60\n  aspect: 1")).unwrap();
+        let ray = scene
+            .camera
+            .build((300, 100))
+            .get_ray(1.0, 0.5, &mut Independent::new(0));
+        let expected = (30.0 as f).to_radians().tan();
+        assert!((ray.direction.x / -ray.direction.z - expected).abs() < 1e-5);
+
+        // the camera defaults to looking down the negative z axis
+        let scene = parse_str("objects: []").unwrap();
+        assert_eq!(scene.camera, CameraDescription::default());
+    }
+
+    #[test]
+    fn test_thin_lens_camera() {
+        let source = "
+camera:
+  type: thin_lens
+  eye: [0, 0, 1]
+  target: [0, 0, -1]
+  aperture: 0.25
+  blades: 6
+objects: []
+";
+        let scene = parse_str(source).unwrap();
+        match scene.camera {
+            CameraDescription::LookAt { lens, .. } => assert_eq!(
+                lens,
+                Some(LensDescription {
+                    radius: 0.25,
+                    focus_distance: None,
+                    aperture: Aperture::Polygonal(6),
+                })
+            ),
+            _ => panic!("expected a look-at camera"),
+        }
+
+        // the camera focuses on its target by default
+        let camera = scene.camera.build((100, 100));
+        for _ in 0..10 {
+            let ray = camera.get_ray(0.5, 0.5, &mut Independent::new(0));
+            let p = ray.point_at_param((-1.0 - ray.origin.z) / ray.direction.z);
+            assert!((p - Vector3f::new(0.0, 0.0, -1.0)).norm() < 1e-5);
+        }
+
+        // lens parameters aren't allowed on a camera without a lens
+        assert_error_line(&source.replace("thin_lens", "look_at"), 6);
+        assert_error_line(&source.replace("blades: 6", "blades: 2"), 7);
+        assert_error_line(&source.replace("  aperture: 0.25\n", ""), 3);
+    }
+
+    #[test]
+    fn test_mesh() {
+        let dir = env::temp_dir().join("trt_scene_mesh_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("tri.obj"),
+            "v -1 -1 -2\nv 1 -1 -2\nv 0 1 -2\nf 1 2 3\n",
+        )
+        .unwrap();
+        let source = "
+objects:
+  - type: mesh
+    file: tri.obj
+";
+        let scene = parse("mesh.yaml", source, &dir).unwrap();
+        assert_eq!(scene.objects.len(), 1);
+
+        let source = "
+objects:
+  - type: mesh
+    file: missing.obj
+";
+        match parse("mesh.yaml", source, &dir) {
+            Err(SceneError::Parse { line, .. }) => assert_eq!(line, 4),
+            _ => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_lights() {
+        let source = "
+objects: []
+lights:
+  - type: point
+    position: [0, 3, 0]
+    intensity: [10, 10, 10]
+  - type: spot
+    position: [0, 3, 0]
+    direction: [0, -2, 0]
+    intensity: [20, 20, 20]
+    angle: 60
+    falloff_start: 0
+  - type: directional
+    direction: [0, 0, -3]
+    irradiance: [3, 3, 2]
+    angular_diameter: 180
+";
+        let scene = parse_str(source).unwrap();
+        assert_eq!(scene.lights.len(), 3);
+        assert_eq!(
+            scene.lights[0],
+            Light::Point {
+                position: Vector3f::new(0.0, 3.0, 0.0),
+                intensity: Color3f::new(10.0, 10.0, 10.0),
+            }
+        );
+
+        match scene.lights[1] {
+            Light::Spot {
+                direction,
+                cos_angle,
+                cos_falloff,
+                ..
+            } => {
+                assert_eq!(direction, Vector3f::new(0.0, -1.0, 0.0));
+                assert!((cos_angle - 0.5).abs() < 1e-6);
+                assert_eq!(cos_falloff, 1.0);
+            }
+            ref light => panic!("expected a spot light, found {:?}", light),
+        }
+
+        match scene.lights[2] {
+            Light::Directional {
+                direction, cos_max, ..
+            } => {
+                assert_eq!(direction, Vector3f::new(0.0, 0.0, -1.0));
+                assert!(cos_max.abs() < 1e-6);
+            }
+            ref light => panic!("expected a directional light, found {:?}", light),
+        }
+
+        assert_error_line(
+            &source.replace("direction: [0, -2, 0]", "direction: [0, 0, 0]"),
+            9,
+        );
+        assert_error_line(&source.replace("falloff_start: 0", "falloff_start: 70"), 12);
+        assert_error_line(&source.replace("angle: 60", "angle: -5"), 11);
+        assert_error_line(
+            &source.replace("angular_diameter: 180", "angular_diameter: 200"),
+            16,
+        );
+        assert_error_line(&source.replace("type: point", "type: area"), 4);
+        assert_error_line(&source.replace("    intensity: [10, 10, 10]\n", ""), 4);
+    }
+
+    #[test]
+    fn test_environment() {
+        let dir = env::temp_dir().join("trt_scene_environment_test");
+        fs::create_dir_all(&dir).unwrap();
+        let mut image = Raster::new(4, 2);
+        image.set(1, 0, Color3f::new(2.0, 2.0, 2.0));
+        pfm::save(dir.join("sky.pfm"), &image).unwrap();
+
+        let source = "
+background:
+  type: environment
+  file: sky.pfm
+  rotation: 45
+  intensity: 3
+objects: []
+";
+        let scene = parse("env.yaml", source, &dir).unwrap();
+        assert_eq!(scene.background, Background::Constant(Color3f::zeros()));
+        assert_eq!(
+            scene.lights,
+            vec![Light::Environment(Box::new(EnvironmentMap::new(
+                image, 45.0, 3.0
+            )))]
+        );
+
+        let missing = source.replace("sky.pfm", "missing.pfm");
+        match parse("env.yaml", &missing, &dir) {
+            Err(SceneError::Parse { line, .. }) => assert_eq!(line, 4),
+            _ => panic!("expected an error"),
+        }
+        let dark = source.replace("intensity: 3", "intensity: 0");
+        match parse("env.yaml", &dark, &dir) {
+            Err(SceneError::Parse { line, .. }) => assert_eq!(line, 6),
+            _ => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        // syntax error
+        assert_error_line("objects: [\n  {type: sphere\n", 3);
+
+        // empty scene
+        assert_error_line("", 1);
+
+        // unknown top level key
+        assert_error_line("objects: []\ncamra: {}\n", 2);
+
+        // missing radius
+        assert_error_line(
+            "objects:\n  - type: sphere\n    center: [0, 0, 0]\n    material: {type: diffuse, albedo: [1, 1, 1]}\n",
+            2,
+        );
+
+        // wrong type for a vector component
+        assert_error_line(
+            "objects:\n  - type: sphere\n    center: [0, zero, 0]\n    radius: 1\n    material: {type: diffuse, albedo: [1, 1, 1]}\n",
+            3,
+        );
+
+        // wrong vector length
+        assert_error_line(
+            "objects:\n  - type: sphere\n    center: [0, 0]\n    radius: 1\n    material: {type: diffuse, albedo: [1, 1, 1]}\n",
+            3,
+        );
+
+        // a sphere without any volume
+        assert_error_line(
+            "objects:\n  - type: sphere\n    center: [0, 0, 0]\n    radius: 0\n    material: {type: diffuse, albedo: [1, 1, 1]}\n",
+            4,
+        );
+        assert_error_line(
+            "objects:\n  - type: sphere\n    center: [0, 0, 0]\n    radius: -1\n    material: {type: diffuse, albedo: [1, 1, 1]}\n",
+            4,
+        );
+
+        // a dielectric that light can't travel through
+        assert_error_line(
+            "objects:\n  - type: sphere\n    center: [0, 0, 0]\n    radius: 1\n    material:\n      type: dielectric\n      ior: 0\n",
+            7,
+        );
+
+        // undefined material
+        assert_error_line(
+            "objects:\n  - type: sphere\n    center: [0, 0, 0]\n    radius: 1\n    material: nope\n",
+            5,
+        );
+
+        // unknown object type
+        assert_error_line("objects:\n  - type: cube\n", 2);
+
+        // negative width
+        assert_error_line("render:\n  width: -5\nobjects: []\n", 2);
+        assert_error_line("render:\n  width: 0\nobjects: []\n", 2);
+        assert_error_line("render:\n  samples: 4\n  height: 0\nobjects: []\n", 3);
+        assert_error_line("render:\n  samples: 0\nobjects: []\n", 2);
+        assert_error_line("render:\n  tone_map: filmic\nobjects: []\n", 2);
+        assert_error_line("render:\n  non_finite: keep\nobjects: []\n", 2);
+        assert_error_line("render:\n  seed: -1\nobjects: []\n", 2);
+        assert_error_line("render:\n  sampler: random\nobjects: []\n", 2);
+        assert_error_line("render:\n  filter: sinc\nobjects: []\n", 2);
+        assert_error_line("render:\n  filter_radius: 0\nobjects: []\n", 2);
+        assert_error_line("render:\n  max_luminance: -1\nobjects: []\n", 2);
+        assert_error_line("render:\n  white_point: 0\nobjects: []\n", 2);
+
+        // look-at camera without a target, and with an impossible field of view
+        assert_error_line(
+            "camera:\n  type: look_at\n  eye: [0, 0, 0]\nobjects: []\n",
+            2,
+        );
+        assert_error_line(
+            "camera:\n  type: look_at\n  eye: [0, 0, 0]\n  target: [0, 0, -1]\n  fov: 180\nobjects: []\n",
+            5,
+        );
+
+        // look-at cameras that can't be oriented
+        assert_error_line(
+            "camera:\n  type: look_at\n  eye: [1, 1, 1]\n  target: [1, 1, 1]\nobjects: []\n",
+            4,
+        );
+        assert_error_line(
+            "camera:\n  type: look_at\n  eye: [0, 0, 0]\n  target: [0, 0, -1]\n  up: [0, 0, 1]\nobjects: []\n",
+            5,
+        );
+        assert_error_line(
+            "camera:\n  type: look_at\n  eye: [0, 2, 0]\n  target: [0, 0, 0]\nobjects: []\n",
+            2,
+        );
+
+        let err = parse_str("objects: []\nbackground: {type: constant}\n")
+            .err()
+            .unwrap();
+        assert!(err.to_string().starts_with("test.yaml:2:"));
+    }
+}
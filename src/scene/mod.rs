@@ -0,0 +1,368 @@
+//! Methods to construct scene data
+//!
+//! This module contains methods that construct a scene, whether it's the
+//! default image, randomly generated circle, or deserialized from a file
+
+use crate::accelerator::{Accelerator, BVH};
+use crate::background::Background;
+use crate::camera::{Aperture, CameraRef, Pinhole, ThinLens};
+use crate::filter::FilterKind;
+use crate::hittable::{ObjRef, ObjVec};
+use crate::integrator::EPSILON;
+use crate::light::{Light, LightSample};
+use crate::material::diffuse::Diffuse;
+use crate::material::mirror::Mirror;
+use crate::na::{self, RealField, Vector2, Vector3};
+use crate::primitives::sphere::Sphere;
+use crate::ray::Ray;
+use crate::renderer::NonFinite;
+use crate::sampler::SamplerKind;
+use crate::tonemap::Operator;
+use crate::typedefs::*;
+
+pub mod loader;
+mod node;
+
+pub use loader::{load, SceneError};
+
+/// Render settings that can be specified by a scene. Any setting that is `None` was left
+/// unspecified, and should fall back to the renderer's default.
+#[derive(Clone, Debug, Default, PartialEq, Copy)]
+pub struct RenderSettings {
+    /// The width of the output image, in pixels
+    pub width: Option<usize>,
+
+    /// The height of the output image, in pixels
+    pub height: Option<usize>,
+
+    /// The number of samples to take for each pixel
+    pub samples: Option<usize>,
+
+    /// The maximum number of bounces for each path
+    pub depth: Option<u>,
+
+    /// The number of bounces before paths can be ended by Russian roulette
+    pub min_depth: Option<u>,
+
+    /// The seed for the random choices of the render, so that it can be reproduced
+    pub seed: Option<u64>,
+
+    /// The sampler that generates the random choices for each sample
+    pub sampler: Option<SamplerKind>,
+
+    /// The filter that spreads each sample over the pixels around it
+    pub filter: Option<FilterKind>,
+
+    /// The radius of the filter, in pixels
+    pub filter_radius: Option<f>,
+
+    /// What happens to samples with a NaN or infinite radiance
+    pub non_finite: Option<NonFinite>,
+
+    /// The largest luminance that a sample can have before it's clamped
+    pub max_luminance: Option<f>,
+
+    /// The tone mapping operator for 8 bit images
+    pub tone_map: Option<Operator>,
+
+    /// The exposure adjustment for 8 bit images, in stops
+    pub exposure: Option<f>,
+
+    /// The luminance that the extended Reinhard operator maps to white
+    pub white_point: Option<f>,
+}
+
+/// The camera that a scene is viewed from. The camera itself can only be created once the
+/// resolution of the image is known, since its aspect ratio may depend on it.
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum CameraDescription {
+    /// A pinhole camera with an explicit image plane, which is used as is
+    Pinhole(Pinhole<f>),
+
+    /// A camera that looks from one point towards another (see `Pinhole::look_at`)
+    LookAt {
+        eye: Vector3f,
+        target: Vector3f,
+        up: Vector3f,
+
+        /// The vertical field of view, in degrees
+        vfov: f,
+
+        /// The ratio of the image's width to its height. If this is `None`, the aspect ratio of
+        /// the output image is used, so that the render isn't stretched.
+        aspect: Option<f>,
+
+        /// The lens of the camera, if it has depth of field
+        lens: Option<LensDescription>,
+    },
+}
+
+/// The lens of a camera that has depth of field (see `ThinLens`)
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct LensDescription {
+    /// The radius of the aperture
+    pub radius: f,
+
+    /// The distance to the plane of focus. If this is `None`, the camera focuses on its target.
+    pub focus_distance: Option<f>,
+
+    /// The shape of the aperture
+    pub aperture: Aperture,
+}
+
+impl CameraDescription {
+    /// Create the camera for an image with the given resolution
+    pub fn build(&self, (width, height): (usize, usize)) -> CameraRef<f> {
+        match *self {
+            CameraDescription::Pinhole(camera) => Box::new(camera),
+            CameraDescription::LookAt {
+                eye,
+                target,
+                up,
+                vfov,
+                aspect,
+                lens,
+            } => {
+                let aspect = aspect.unwrap_or(width as f / height as f);
+                let pinhole = Pinhole::look_at(&eye, &target, &up, vfov, aspect);
+
+                match lens {
+                    Some(lens) => Box::new(ThinLens::new(
+                        &pinhole,
+                        lens.radius,
+                        lens.focus_distance.unwrap_or_else(|| (target - eye).norm()),
+                        lens.aperture,
+                    )),
+                    None => Box::new(pinhole),
+                }
+            }
+        }
+    }
+}
+
+impl Default for CameraDescription {
+    /// A camera at the origin that looks down the negative z axis. For a 2:1 image, this is the
+    /// same as `Pinhole::default()`.
+    fn default() -> Self {
+        CameraDescription::LookAt {
+            eye: Vector3f::new(0.0, 0.0, 0.0),
+            target: Vector3f::new(0.0, 0.0, -1.0),
+            up: Vector3f::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            aspect: None,
+            lens: None,
+        }
+    }
+}
+
+/// The contents of a scene that light interacts with, in a form that's ready to render
+pub struct Scene<N: RealField> {
+    /// The objects in the scene
+    pub objects: BVH<N>,
+
+    /// The radiance for rays that escape the scene
+    pub background: Background<N>,
+
+    /// The lights that can be sampled directly
+    pub lights: Vec<Light<N>>,
+}
+
+impl<N: RealField> Scene<N> {
+    /// Create a scene, building an acceleration structure for the objects. Every object with an
+    /// emissive material becomes an area light.
+    pub fn new(objects: ObjVec<N>, background: Background<N>) -> Self {
+        let objects = BVH::new(objects);
+        let lights = objects
+            .objects()
+            .iter()
+            .enumerate()
+            .filter(|(_, obj)| obj.material.is_emissive())
+            .map(|(object, _)| Light::Area { object })
+            .collect();
+
+        Self {
+            objects,
+            background,
+            lights,
+        }
+    }
+
+    /// Add lights that aren't part of the scene's geometry, such as point lights
+    pub fn with_lights(mut self, lights: Vec<Light<N>>) -> Self {
+        self.lights.extend(lights);
+        self
+    }
+
+    /// The number of lights, as a number that densities can be divided by
+    fn light_count(&self) -> N {
+        N::from_usize(self.lights.len().max(1)).unwrap()
+    }
+
+    /// Pick one of the lights and sample a direction towards it from `reference`. `u_light` is
+    /// a uniformly distributed value that picks the light, and `u` is a uniformly distributed
+    /// point in the unit square that picks the direction.
+    ///
+    /// Every light is equally likely to be picked, and the density of the sample includes the
+    /// probability of picking its light.
+    pub fn sample_light(
+        &self,
+        reference: &Vector3<N>,
+        u_light: N,
+        u: &Vector2<N>,
+    ) -> Option<LightSample<N>> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let count = self.lights.len();
+        let index: f64 = na::try_convert(u_light * self.light_count()).unwrap_or(0.0);
+        let index = (index as usize).min(count - 1);
+        let mut sample = self.lights[index].sample(self.objects.objects(), reference, u)?;
+        sample.pdf /= self.light_count();
+        Some(sample)
+    }
+
+    /// The density that `sample_light` has for the direction from `reference` towards the
+    /// emissive object `obj`, which is zero if the object can't be sampled
+    pub fn light_pdf(&self, obj: &ObjRef<N>, reference: &Vector3<N>, direction: &Vector3<N>) -> N {
+        if self.lights.is_empty() || !obj.material.is_emissive() {
+            return N::zero();
+        }
+        obj.hittable.pdf(reference, direction) / self.light_count()
+    }
+
+    /// The radiance from the lights that are infinitely far away, which arrives along a ray that
+    /// escapes the scene in `direction`. This doesn't include the background.
+    pub fn escaped_light(&self, direction: &Vector3<N>) -> Vector3<N> {
+        self.lights
+            .iter()
+            .map(|light| light.emitted(direction))
+            .fold(Vector3::zeros(), |total, radiance| total + radiance)
+    }
+
+    /// The density that `sample_light` has for `direction`, from the lights that are infinitely
+    /// far away
+    pub fn escaped_light_pdf(&self, direction: &Vector3<N>) -> N {
+        let pdf = self
+            .lights
+            .iter()
+            .filter(|light| light.is_infinite())
+            .map(|light| light.pdf(self.objects.objects(), &Vector3::zeros(), direction))
+            .fold(N::zero(), |total, pdf| total + pdf);
+        pdf / self.light_count()
+    }
+
+    /// Whether nothing blocks the segment that starts at `origin` and goes `distance` along the
+    /// unit `direction`. The ends of the segment are excluded, so that the surfaces that it
+    /// connects don't block it.
+    pub fn unoccluded(&self, origin: &Vector3<N>, direction: &Vector3<N>, distance: N) -> bool {
+        let ray = Ray {
+            origin: *origin,
+            direction: *direction,
+        };
+        let epsilon = N::from_f32(EPSILON).unwrap();
+        !self
+            .objects
+            .occluded(&ray, Some(epsilon), Some(distance - epsilon))
+    }
+}
+
+/// Everything that is needed to render a scene
+pub struct SceneDescription {
+    /// The objects in the scene
+    pub objects: ObjVec<f>,
+
+    /// The camera that the scene is viewed from
+    pub camera: CameraDescription,
+
+    /// The radiance for rays that escape the scene
+    pub background: Background<f>,
+
+    /// The lights that aren't part of the geometry
+    pub lights: Vec<Light<f>>,
+
+    /// The render settings that the scene asked for
+    pub settings: RenderSettings,
+}
+
+/// Macro to initialize the ObjVec used for scenes
+///
+/// This vector is similar to the `vec!` macro, except it is customized for how the ObjVec is
+/// commonly iniitalized (the elements of each tuple must be Boxed).
+macro_rules! obj_vec {
+    (
+        // We need the type here otherwise the vector will automatically adopt the types of the
+        // first elements passed in, rather than using `dyn Trait`, which causes compilation
+        // errors. Additionally, this lets the user define what kind of numeric type to use for the
+        // vectors (whether it's f32, f64).
+        $t:ty; [ $( ( $prim:expr, $mat:expr ) ),* ]
+    ) => {
+        {
+            let vector: ObjVec<$t> = vec![
+                $(
+                    ObjRef{
+                        hittable: Box::new($prim),
+                        material: Box::new($mat),
+                    }
+                ),*
+            ];
+            vector
+        }
+    };
+}
+
+/// Constructs the default scene found on the cover of the ray tracing in one weekend book
+pub fn default_scene() -> ObjVec<f> {
+    obj_vec!(f; [
+        (
+            Sphere {
+                radius: 0.5,
+                center: Vector3f::new(0.0, 0.0, -1.0),
+            },
+            Diffuse {
+                albedo: Vector3f::new(0.8, 0.3, 0.3),
+            }
+        ),
+        (
+            Sphere {
+                radius: 100.0,
+                center: Vector3f::new(0.0, -100.5, -1.0),
+            },
+            Diffuse {
+                albedo: Vector3f::new(0.8, 0.8, 0.0),
+            }
+        ),
+        (
+            Sphere {
+                radius: 0.5,
+                center: Vector3f::new(1.0, 0.0, -1.0),
+            },
+            Mirror {
+                albedo: Vector3f::new(0.8, 0.6, 0.2),
+                fuzziness: 0.0,
+            }
+        ),
+        (
+            Sphere {
+                radius: 0.5,
+                center: Vector3f::new(-1.0, 0.0, -1.0),
+            },
+            Mirror {
+                albedo: Vector3f::new(0.8, 0.8, 0.8),
+                fuzziness: 0.0,
+            }
+        )
+    ])
+}
+
+/// A simple test scene for debugging
+pub fn test_scene() -> ObjVec<f> {
+    obj_vec!(f; [
+             (Sphere {
+                 center: Vector3f::new(0.0, 0.0, -1.0),
+                 radius: 0.5,
+             },
+             Diffuse {
+                 albedo: Vector3f::new(0.8, 0.3, 0.3)
+             })
+    ])
+}
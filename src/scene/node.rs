@@ -0,0 +1,212 @@
+//! A YAML document tree that remembers which line each node came from
+//!
+//! `yaml_rust::Yaml` discards source positions once a document is loaded, which makes it
+//! impossible to point users at the offending line when a scene description is invalid. This
+//! module builds an equivalent tree from the low level parser events, keeping the line of every
+//! node.
+
+use std::collections::HashMap;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, ScanError, TScalarStyle};
+use yaml_rust::Yaml;
+
+/// The contents of a YAML node
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Sequence(Vec<Node>),
+    Mapping(Vec<(Node, Node)>),
+}
+
+/// A YAML node, along with the (1-indexed) line that it starts on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub value: Value,
+    pub line: usize,
+}
+
+impl Node {
+    /// A human readable description of the type of the node, for error messages
+    pub fn kind(&self) -> &'static str {
+        match self.value {
+            Value::Null => "null",
+            Value::Boolean(_) => "a boolean",
+            Value::Integer(_) | Value::Real(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Sequence(_) => "a list",
+            Value::Mapping(_) => "a mapping",
+        }
+    }
+
+    /// Look up the value for a key, if this node is a mapping that contains the key
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Mapping(entries) => entries
+                .iter()
+                .find(|(k, _)| k.value == Value::String(key.to_string()))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// A stack frame for a collection that is still being loaded
+struct Frame {
+    node: Node,
+    anchor: usize,
+
+    /// The key that is waiting for its value, if the frame is a mapping
+    key: Option<Node>,
+}
+
+/// Receives events from the YAML parser and assembles them into a tree of `Node`s
+#[derive(Default)]
+struct Loader {
+    stack: Vec<Frame>,
+    anchors: HashMap<usize, Node>,
+    root: Option<Node>,
+}
+
+impl Loader {
+    fn push(&mut self, value: Value, anchor: usize, mark: Marker) {
+        self.stack.push(Frame {
+            node: Node {
+                value,
+                line: mark.line(),
+            },
+            anchor,
+            key: None,
+        });
+    }
+
+    /// Insert a finished node into the collection that is currently being built
+    fn insert(&mut self, node: Node, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        let frame = match self.stack.last_mut() {
+            Some(frame) => frame,
+            None => {
+                self.root = Some(node);
+                return;
+            }
+        };
+
+        match &mut frame.node.value {
+            Value::Sequence(items) => items.push(node),
+            Value::Mapping(entries) => match frame.key.take() {
+                Some(key) => entries.push((key, node)),
+                None => frame.key = Some(node),
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Loader {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::SequenceStart(anchor) => self.push(Value::Sequence(Vec::new()), anchor, mark),
+            Event::MappingStart(anchor) => self.push(Value::Mapping(Vec::new()), anchor, mark),
+            Event::SequenceEnd | Event::MappingEnd => {
+                let frame = self.stack.pop().unwrap();
+                self.insert(frame.node, frame.anchor);
+            }
+            Event::Scalar(v, style, anchor, _) => {
+                let value = if style != TScalarStyle::Plain {
+                    Value::String(v)
+                } else {
+                    match Yaml::from_str(&v) {
+                        Yaml::Integer(i) => Value::Integer(i),
+                        Yaml::Real(r) => r.parse().map(Value::Real).unwrap_or(Value::String(r)),
+                        Yaml::Boolean(b) => Value::Boolean(b),
+                        Yaml::Null => Value::Null,
+                        _ => Value::String(v),
+                    }
+                };
+                let node = Node {
+                    value,
+                    line: mark.line(),
+                };
+                self.insert(node, anchor);
+            }
+            Event::Alias(id) => {
+                let mut node = self.anchors.get(&id).cloned().unwrap_or(Node {
+                    value: Value::Null,
+                    line: mark.line(),
+                });
+                node.line = mark.line();
+                self.insert(node, 0);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Parse the first document in a YAML string. An empty document results in a null node.
+pub fn parse(source: &str) -> Result<Node, ScanError> {
+    let mut loader = Loader::default();
+    Parser::new(source.chars()).load(&mut loader, false)?;
+    Ok(loader.root.unwrap_or(Node {
+        value: Value::Null,
+        line: 1,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let source = "a: 1\nb:\n  - 2.5\n  - \"three\"\nc: {d: true}\n";
+        let root = parse(source).unwrap();
+        assert_eq!(root.line, 1);
+
+        let a = root.get("a").unwrap();
+        assert_eq!(a.value, Value::Integer(1));
+        assert_eq!(a.line, 1);
+
+        let b = root.get("b").unwrap();
+        assert_eq!(b.line, 3);
+        match &b.value {
+            Value::Sequence(items) => {
+                assert_eq!(items[0].value, Value::Real(2.5));
+                assert_eq!(items[0].line, 3);
+                assert_eq!(items[1].value, Value::String("three".into()));
+                assert_eq!(items[1].line, 4);
+            }
+            _ => panic!("expected a sequence"),
+        }
+
+        let d = root.get("c").unwrap().get("d").unwrap();
+        assert_eq!(d.value, Value::Boolean(true));
+        assert_eq!(d.line, 5);
+        assert!(root.get("e").is_none());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let source = "a: &anchor [1, 2]\nb: *anchor\n";
+        let root = parse(source).unwrap();
+        let a = root.get("a").unwrap();
+        let b = root.get("b").unwrap();
+        assert_eq!(a.value, b.value);
+        assert_eq!(b.line, 2);
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(parse("").unwrap().value, Value::Null);
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let err = parse("a: [1, 2\nb: 3\n").unwrap_err();
+        assert!(err.marker().line() >= 2);
+    }
+}
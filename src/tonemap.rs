@@ -0,0 +1,245 @@
+//! Tone mapping, which turns the high dynamic range radiance of a render into colors that can be
+//! displayed
+//!
+//! The radiance is first scaled by the exposure, and then compressed into [0, 1] by one of the
+//! tone mapping operators. Finally, the sRGB transfer function is applied, so that the values are
+//! ready to be stored in an 8 bit image.
+
+use crate::typedefs::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// A curve that maps high dynamic range values to the range [0, 1]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Copy)]
+pub enum Operator {
+    /// Values above 1 are clipped, which is what happens without any tone mapping
+    #[default]
+    Clamp,
+
+    /// The global operator from Reinhard et al., "Photographic Tone Reproduction for Digital
+    /// Images", applied to the luminance so that hues are preserved. It never quite reaches white.
+    Reinhard,
+
+    /// The Reinhard operator, extended so that luminance at the white point is mapped to white
+    ExtendedReinhard,
+
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve
+    Aces,
+
+    /// John Hable's filmic curve from Uncharted 2
+    Hable,
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(Operator::Clamp),
+            "reinhard" => Ok(Operator::Reinhard),
+            "extended_reinhard" => Ok(Operator::ExtendedReinhard),
+            "aces" => Ok(Operator::Aces),
+            "hable" => Ok(Operator::Hable),
+            _ => Err(format!("unknown tone mapping operator \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operator::Clamp => write!(f, "clamp"),
+            Operator::Reinhard => write!(f, "reinhard"),
+            Operator::ExtendedReinhard => write!(f, "extended_reinhard"),
+            Operator::Aces => write!(f, "aces"),
+            Operator::Hable => write!(f, "hable"),
+        }
+    }
+}
+
+/// The luminance of a linear color with the Rec. 709 primaries that sRGB uses
+pub fn luminance(color: &Color3f) -> f {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// The exact, piecewise sRGB transfer function, which encodes a linear value in [0, 1]
+pub fn srgb_oetf(x: f) -> f {
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The curve from Hable's filmic operator, before it's normalized by the white point
+fn hable_partial(x: f) -> f {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+/// How the radiance of a render is converted to a displayable image
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub struct ToneMap {
+    /// The curve that compresses the radiance
+    pub operator: Operator,
+
+    /// The exposure adjustment in stops, where each stop doubles the brightness
+    pub exposure: f,
+
+    /// The luminance that is mapped to white by the extended Reinhard operator
+    pub white_point: f,
+}
+
+impl Default for ToneMap {
+    /// Clip the radiance without adjusting the exposure
+    fn default() -> Self {
+        Self {
+            operator: Operator::default(),
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl ToneMap {
+    /// Map a linear color to a linear display value, with every component in [0, 1]
+    pub fn apply(&self, color: &Color3f) -> Color3f {
+        let color = color * (2.0 as f).powf(self.exposure);
+
+        let mapped = match self.operator {
+            Operator::Clamp => color,
+            Operator::Reinhard | Operator::ExtendedReinhard => {
+                let l = luminance(&color);
+
+                if l <= 0.0 {
+                    return Color3f::zeros();
+                }
+                let white = match self.operator {
+                    Operator::ExtendedReinhard => self.white_point,
+                    _ => f::INFINITY,
+                };
+                let mapped = l * (1.0 + l / (white * white)) / (1.0 + l);
+                color * (mapped / l)
+            }
+            Operator::Aces => color.map(|x| {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                (x * (a * x + b)) / (x * (c * x + d) + e)
+            }),
+            Operator::Hable => {
+                // the exposure bias and white point that Hable used
+                let white_scale = 1.0 / hable_partial(11.2);
+                color.map(|x| hable_partial(2.0 * x) * white_scale)
+            }
+        };
+        mapped.map(|x| x.clamp(0.0, 1.0))
+    }
+
+    /// Convert a linear color to 8 bit sRGB, which is ready to be displayed or saved
+    pub fn to_srgb8(&self, color: &Color3f) -> [u8; 3] {
+        let c = self
+            .apply(color)
+            .map(|x| (srgb_oetf(x) * 255.0).round() as u8);
+        [c.x, c.y, c.z]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OPERATORS: [Operator; 5] = [
+        Operator::Clamp,
+        Operator::Reinhard,
+        Operator::ExtendedReinhard,
+        Operator::Aces,
+        Operator::Hable,
+    ];
+
+    #[test]
+    fn test_names() {
+        for operator in &OPERATORS {
+            assert_eq!(operator.to_string().parse::<Operator>(), Ok(*operator));
+        }
+        assert!("filmic".parse::<Operator>().is_err());
+    }
+
+    #[test]
+    fn test_srgb() {
+        assert_eq!(srgb_oetf(0.0), 0.0);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_oetf(0.5) - 0.735_357).abs() < 1e-5);
+        assert!((srgb_oetf(0.001) - 0.012_92).abs() < 1e-6);
+
+        // the two pieces of the curve meet
+        let knee = 0.003_130_8;
+        assert!((srgb_oetf(knee) - srgb_oetf(knee + 1e-7)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_operators() {
+        for &operator in &OPERATORS {
+            let tone_map = ToneMap {
+                operator,
+                ..ToneMap::default()
+            };
+            let grey = |x: f| tone_map.apply(&Color3f::repeat(x)).x;
+
+            // black stays black (up to rounding), bright values are compressed into [0, 1], and
+            // the curves never decrease
+            assert!(grey(0.0) < 1e-6, "{}", operator);
+            assert!(grey(1e6) <= 1.0 && grey(1e6) > 0.9, "{}", operator);
+            let values: Vec<f> = (0..100).map(|i| grey(i as f * 0.1)).collect();
+            assert!(values.windows(2).all(|w| w[0] <= w[1]), "{}", operator);
+        }
+
+        let tone_map = |operator| ToneMap {
+            operator,
+            ..ToneMap::default()
+        };
+        let grey = Color3f::repeat(0.5);
+        assert_eq!(tone_map(Operator::Clamp).apply(&grey), grey);
+        assert_eq!(
+            tone_map(Operator::Reinhard).apply(&Color3f::repeat(1.0)),
+            Color3f::repeat(0.5)
+        );
+
+        // the extended operator reaches white at the white point, and is nearly the same as the
+        // simple operator for dark values
+        let extended = tone_map(Operator::ExtendedReinhard);
+        assert!((extended.apply(&Color3f::repeat(4.0)).x - 1.0).abs() < 1e-6);
+        let dark = Color3f::repeat(0.01);
+        assert!((extended.apply(&dark) - tone_map(Operator::Reinhard).apply(&dark)).norm() < 1e-4);
+
+        // the Reinhard operators keep the ratios between the channels
+        let color = tone_map(Operator::Reinhard).apply(&Color3f::new(2.0, 1.0, 0.5));
+        assert!((color.x / color.y - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let brighter = ToneMap {
+            exposure: 1.0,
+            ..ToneMap::default()
+        };
+        let darker = ToneMap {
+            exposure: -2.0,
+            ..ToneMap::default()
+        };
+        let color = Color3f::new(0.1, 0.2, 0.4);
+        assert_eq!(brighter.apply(&color), color * 2.0);
+        assert_eq!(darker.apply(&color), color / 4.0);
+    }
+
+    #[test]
+    fn test_srgb8() {
+        let tone_map = ToneMap::default();
+        assert_eq!(
+            tone_map.to_srgb8(&Color3f::new(0.0, 1.0, 5.0)),
+            [0, 255, 255]
+        );
+        assert_eq!(
+            tone_map.to_srgb8(&Color3f::new(0.5, -1.0, f::NAN)),
+            [188, 0, 0]
+        );
+    }
+}
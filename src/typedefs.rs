@@ -1,6 +1,6 @@
 /// Convenience type aliases for commonly used vector types. Note that all types are
 /// signed.
-use crate::na::Vector3;
+use crate::na::{Vector2, Vector3};
 use crate::ray::Ray;
 
 /// A typedef to define the precision of floating points
@@ -18,6 +18,9 @@ pub type u = u32;
 /// A three dimensional integer vector
 pub type Vector3d = Vector3<i>;
 
+/// A two dimensional floating point vector
+pub type Vector2f = Vector2<f>;
+
 /// A three dimensional floating point vector
 pub type Vector3f = Vector3<f>;
 